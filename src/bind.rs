@@ -1,14 +1,21 @@
 use std::collections::BTreeMap;
 use std::sync::{Arc, RwLock};
 use std::fmt;
+use std::path::PathBuf;
 use std::slice;
 use std::ops::Deref;
+use std::any::Any;
 
-use typemap::TypeMap;
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use typemap::{self, CloneAny, TypeMap};
 
 use item::Item;
 use configuration::Configuration;
 
+/// Arbitrary, typed data.
+pub type Extensions = TypeMap<CloneAny + Sync + Send>;
+
 /// Bind data.
 
 #[derive(Clone)]
@@ -19,12 +26,20 @@ pub struct Data {
     /// The bind's dependencies.
     pub dependencies: BTreeMap<String, Arc<Bind>>,
 
+    /// The projections of the dependencies that are only needed in part.
+    pub projections: BTreeMap<String, Arc<serde_json::Value>>,
+
     /// The global configuration
     pub configuration: Arc<Configuration>,
 
-    // TODO: not a fan of exposing the Arc
     /// Arbitrary, bind-level data
-    pub extensions: Arc<RwLock<TypeMap<::typemap::CloneAny + Sync + Send>>>,
+    ///
+    /// This is shared by the bind's items, which may be processed in
+    /// parallel, so it's only accessible through `with_ext` and `with_ext_mut`.
+    extensions: Arc<RwLock<Extensions>>,
+
+    /// Bind-level data keyed by name, which templates see
+    meta: Arc<RwLock<Map<String, Value>>>,
 }
 
 impl Data {
@@ -32,10 +47,93 @@ impl Data {
         Data {
             name: name,
             dependencies: BTreeMap::new(),
+            projections: BTreeMap::new(),
             configuration: configuration,
             extensions: Arc::new(RwLock::new(TypeMap::custom())),
+            meta: Arc::new(RwLock::new(Map::new())),
         }
     }
+
+    /// Read the bind-level extensions.
+    ///
+    /// The extensions are locked for the duration of the closure, which
+    /// blocks any writers, so only look values up or clone them out; don't
+    /// do any lengthy work or call back into the bind from inside of it.
+    /// Values that are read often should be stored behind an `Arc` so
+    /// that they're cheap to clone out.
+    pub fn with_ext<F, R>(&self, f: F) -> R
+    where F: FnOnce(&Extensions) -> R {
+        f(&self.extensions.read().unwrap())
+    }
+
+    /// A clone of a bind-level extension, if it's set.
+    ///
+    /// This is short for `with_ext` when only one value is needed, e.g.
+    /// `bind.dependency("posts")?.extension::<Tags>()`.
+    pub fn extension<K>(&self) -> Option<K::Value>
+    where K: typemap::Key, K::Value: Any + Clone + Sync + Send {
+        self.with_ext(|extensions| extensions.get::<K>().cloned())
+    }
+
+    /// The bind-level values keyed by name.
+    ///
+    /// These are shared by the bind's items, and are exposed to
+    /// templates as `rule_meta`. See `Item::meta` for the item-level ones.
+    pub fn meta(&self) -> Map<String, Value> {
+        self.meta.read().unwrap().clone()
+    }
+
+    /// A bind-level value, if it's set.
+    pub fn get_meta(&self, key: &str) -> Option<Value> {
+        self.meta.read().unwrap().get(key).cloned()
+    }
+
+    /// Set a bind-level value, replacing any previous value.
+    pub fn set_meta<K, V: ?Sized>(&self, key: K, value: &V) -> ::Result<()>
+    where K: Into<String>, V: Serialize {
+        let value = serde_json::to_value(value)?;
+        self.meta.write().unwrap().insert(key.into(), value);
+        Ok(())
+    }
+
+    /// The bind of a dependency.
+    ///
+    /// This fails if the rule doesn't depend on it.
+    pub fn dependency(&self, name: &str) -> ::Result<&Bind> {
+        self.dependencies.get(name).map(|bind| &**bind).ok_or_else(|| {
+            From::from(format!("`{}` does not depend on `{}`", self.name, name))
+        })
+    }
+
+    /// A temporary directory for this rule's intermediate files.
+    ///
+    /// Handlers that invoke external tools should put their intermediate
+    /// files here rather than in the input or output directories. It's
+    /// created on demand under the cache directory, emptied at the start
+    /// of every build, and removed according to `Configuration::scratch`.
+    pub fn scratch_dir(&self) -> ::Result<PathBuf> {
+        let name =
+            self.name.chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect::<String>();
+
+        let path = self.configuration.scratch_root().join(name);
+
+        self.configuration.sandbox().create_dir_all(&path)?;
+
+        Ok(path)
+    }
+
+    /// Modify the bind-level extensions.
+    ///
+    /// The extensions are locked for the duration of the closure, which
+    /// blocks every other reader and writer, including the bind's other
+    /// items, so compute values beforehand and only insert them inside.
+    /// Calling `with_ext` or `with_ext_mut` from inside deadlocks.
+    pub fn with_ext_mut<F, R>(&self, f: F) -> R
+    where F: FnOnce(&mut Extensions) -> R {
+        f(&mut self.extensions.write().unwrap())
+    }
 }
 
 /// The resulting bind of a `Rule`
@@ -46,6 +144,7 @@ impl Data {
 pub struct Bind {
     items: Vec<Item>,
     data: Arc<Data>,
+    error: Option<String>,
 }
 
 impl Bind {
@@ -53,14 +152,42 @@ impl Bind {
         Bind {
             items: Vec::new(),
             data: Arc::new(data),
+            error: None,
         }
     }
 
+    /// Mark the bind as the result of a failed rule.
+    ///
+    /// This discards any items that were attached before the failure.
+    pub fn fail<E>(&mut self, error: E)
+    where E: Into<String> {
+        self.items.clear();
+        self.error = Some(error.into());
+    }
+
+    /// Whether the rule that produced this bind succeeded.
+    ///
+    /// This is only ever false for rules that allow failure.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The error that the rule failed with, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_ref().map(|e| &e[..])
+    }
+
     pub fn attach(&mut self, mut item: Item) {
         item.attach_to(self.data.clone());
         self.items.push(item);
     }
 
+    /// The projection of a dependency registered with
+    /// `Builder::depends_on_projection`.
+    pub fn projection(&self, dependency: &str) -> Option<&serde_json::Value> {
+        self.data.projections.get(dependency).map(|projection| &**projection)
+    }
+
     /// Access the bind data as an `Arc`
     pub fn data(&self) -> &Data {
         &self.data
@@ -77,6 +204,28 @@ impl Bind {
         &self.items
     }
 
+    /// Every run of `size` consecutive items, overlapping.
+    ///
+    /// See `util::handle::bind::neighbors` to give each item its neighbors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn windows(&self, size: usize) -> slice::Windows<Item> {
+        self.items.windows(size)
+    }
+
+    /// The items in groups of `size`, the last of which may be smaller.
+    ///
+    /// See `util::handle::bind::chunks` to give each item its group.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn chunks(&self, size: usize) -> slice::Chunks<Item> {
+        self.items.chunks(size)
+    }
+
     // TODO
     // is this necessary?
     // just have items/items_mut and re-use their iter/iter_mut?
@@ -0,0 +1,501 @@
+//! Command-line argument parsing.
+//!
+//! Each command describes its options and arguments with a `Spec`,
+//! from which its help is generated. The global options, such as
+//! `--jobs`, are part of every spec, so they're accepted and described
+//! the same way by every command.
+//!
+//! ```ignore
+//! let spec =
+//!     Spec::new("diff-builds")
+//!     .option(Opt::value("redirects", "F", "Write a redirect map to F").short('r'))
+//!     .arg("old")
+//!     .arg("new");
+//!
+//! let matches = spec.parse(&args)?;
+//! let old = matches.arg("old").unwrap();
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt;
+use std::process;
+use std::str::FromStr;
+
+/// An option, which is either a flag or takes a value.
+#[derive(Clone, Debug)]
+pub struct Opt {
+    short: Option<char>,
+    long: &'static str,
+    value: Option<&'static str>,
+    help: &'static str,
+    default: Option<&'static str>,
+    required: bool,
+}
+
+impl Opt {
+    /// An option without a value, e.g. `--verbose`.
+    pub fn flag(long: &'static str, help: &'static str) -> Opt {
+        Opt {
+            short: None,
+            long: long,
+            value: None,
+            help: help,
+            default: None,
+            required: false,
+        }
+    }
+
+    /// An option with a value, e.g. `--jobs N`.
+    pub fn value(long: &'static str, name: &'static str, help: &'static str) -> Opt {
+        Opt {
+            value: Some(name),
+            ..Opt::flag(long, help)
+        }
+    }
+
+    /// Also accept a single-letter form, e.g. `-j`.
+    pub fn short(mut self, short: char) -> Opt {
+        self.short = Some(short);
+        self
+    }
+
+    /// The value if the option isn't given.
+    pub fn default(mut self, default: &'static str) -> Opt {
+        self.default = Some(default);
+        self
+    }
+
+    /// Fail if the option isn't given.
+    pub fn required(mut self) -> Opt {
+        self.required = true;
+        self
+    }
+
+    /// How the option is listed in the help, e.g. `-j N, --jobs N`.
+    fn signature(&self) -> String {
+        let value = self.value.map_or_else(String::new, |value| format!(" {}", value));
+
+        match self.short {
+            Some(short) => format!("-{}{}, --{}{}", short, value, self.long, value),
+            None => format!("--{}{}", self.long, value),
+        }
+    }
+}
+
+/// The options that every command accepts.
+pub fn globals() -> Vec<Opt> {
+    vec![
+        Opt::flag("help", "Print this message").short('h'),
+        Opt::value("chdir", "DIR", "Run as if started in DIR, rather than\n\
+                                    the closest directory with a Diecast.toml").short('C'),
+        Opt::value("jobs", "N", "Number of jobs to run in parallel").short('j'),
+        Opt::flag("verbose", "Use verbose output").short('v'),
+        Opt::flag("quiet", "Only print warnings").short('q'),
+        Opt::value("message-format", "FMT", "Print progress as `human` or `json` lines"),
+    ]
+}
+
+/// A positional argument.
+#[derive(Clone, Debug)]
+struct Arg {
+    name: &'static str,
+    required: bool,
+}
+
+/// The options and arguments of a command.
+#[derive(Clone, Debug)]
+pub struct Spec {
+    name: String,
+    options: Vec<Opt>,
+    args: Vec<Arg>,
+    about: Option<String>,
+    options_first: bool,
+}
+
+impl Spec {
+    /// The spec of a command, with the global options.
+    pub fn new<N>(name: N) -> Spec
+    where N: Into<String> {
+        Spec {
+            name: name.into(),
+            options: vec![],
+            args: vec![],
+            about: None,
+            options_first: false,
+        }
+    }
+
+    pub fn option(mut self, option: Opt) -> Spec {
+        self.options.push(option);
+        self
+    }
+
+    /// A required positional argument.
+    pub fn arg(mut self, name: &'static str) -> Spec {
+        self.args.push(Arg {
+            name: name,
+            required: true,
+        });
+
+        self
+    }
+
+    /// An optional positional argument, which must come after the required ones.
+    pub fn optional_arg(mut self, name: &'static str) -> Spec {
+        self.args.push(Arg {
+            name: name,
+            required: false,
+        });
+
+        self
+    }
+
+    /// Text that's shown after the options in the help.
+    pub fn about<A>(mut self, about: A) -> Spec
+    where A: Into<String> {
+        self.about = Some(about.into());
+        self
+    }
+
+    /// Stop at the first positional argument, leaving it and everything
+    /// after it as the rest, e.g. for a command and its arguments.
+    pub fn options_first(mut self) -> Spec {
+        self.options_first = true;
+        self
+    }
+
+    /// The generated help.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("Usage:\n    diecast {} [options]", self.name);
+
+        for option in self.options.iter().filter(|option| option.required) {
+            usage.push_str(&format!(" --{} {}", option.long, option.value.unwrap_or("")));
+        }
+
+        for arg in &self.args {
+            if arg.required {
+                usage.push_str(&format!(" <{}>", arg.name));
+            } else {
+                usage.push_str(&format!(" [<{}>]", arg.name));
+            }
+        }
+
+        let globals = globals();
+
+        let width =
+            self.options.iter().chain(globals.iter())
+            .map(|option| option.signature().len())
+            .max()
+            .unwrap_or(0) + 2;
+
+        let list = |options: &[Opt]| -> String {
+            let mut list = String::new();
+
+            for option in options {
+                let signature = option.signature();
+                let indent = format!("\n    {}", " ".repeat(width));
+
+                list.push_str(&format!("\n    {}{}{}",
+                    signature, " ".repeat(width - signature.len()),
+                    option.help.replace('\n', &indent)));
+
+                if let Some(default) = option.default {
+                    list.push_str(&format!(" [default: {}]", default));
+                }
+            }
+
+            list
+        };
+
+        if !self.options.is_empty() {
+            usage.push_str("\n\nOptions:");
+            usage.push_str(&list(&self.options));
+        }
+
+        usage.push_str("\n\nGlobal options:");
+        usage.push_str(&list(&globals));
+        usage.push('\n');
+
+        if let Some(ref about) = self.about {
+            usage.push('\n');
+            usage.push_str(about.trim());
+            usage.push('\n');
+        }
+
+        usage
+    }
+
+    fn find_long(&self, globals: &[Opt], long: &str) -> Option<Opt> {
+        self.options.iter().chain(globals.iter()).find(|option| option.long == long).cloned()
+    }
+
+    fn find_short(&self, globals: &[Opt], short: char) -> Option<Opt> {
+        self.options.iter().chain(globals.iter()).find(|option| option.short == Some(short)).cloned()
+    }
+
+    /// Parse the arguments that follow the command's name.
+    pub fn parse(&self, args: &[String]) -> Result<Matches, Error> {
+        let invalid = |message: String| Error::Invalid(message, self.usage());
+
+        let globals = globals();
+        let mut matches = Matches::default();
+        let mut positional = vec![];
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if !positional.is_empty() && self.options_first {
+                positional.push(arg.clone());
+                continue;
+            }
+
+            if arg == "--" {
+                positional.extend(args.by_ref().cloned());
+                break;
+            }
+
+            if arg.starts_with("--") {
+                let (long, inline) = match arg[2..].find('=') {
+                    Some(index) => (&arg[2 .. index + 2], Some(String::from(&arg[index + 3..]))),
+                    None => (&arg[2..], None),
+                };
+
+                let option = self.find_long(&globals, long)
+                    .ok_or_else(|| invalid(format!("unknown option `--{}`", long)))?;
+
+                match option.value {
+                    Some(_) => {
+                        let value = inline.or_else(|| args.next().cloned()).ok_or_else(|| {
+                            invalid(format!("`--{}` needs a value", long))
+                        })?;
+
+                        matches.values.insert(String::from(option.long), value);
+                    },
+                    None if inline.is_some() => {
+                        return Err(invalid(format!("`--{}` doesn't take a value", long)));
+                    },
+                    None => {
+                        matches.flags.insert(String::from(option.long));
+                    },
+                }
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                let shorts = &arg[1..];
+
+                for (index, short) in shorts.char_indices() {
+                    let option = self.find_short(&globals, short)
+                        .ok_or_else(|| invalid(format!("unknown option `-{}`", short)))?;
+
+                    if option.value.is_none() {
+                        matches.flags.insert(String::from(option.long));
+                        continue;
+                    }
+
+                    // the value is the rest of the group, e.g. `-j4`, or the next argument
+                    let rest = &shorts[index + short.len_utf8()..];
+
+                    let value =
+                        if !rest.is_empty() { String::from(rest) }
+                        else {
+                            args.next().cloned().ok_or_else(|| {
+                                invalid(format!("`-{}` needs a value", short))
+                            })?
+                        };
+
+                    matches.values.insert(String::from(option.long), value);
+                    break;
+                }
+            } else {
+                positional.push(arg.clone());
+            }
+
+            if matches.is_present("help") {
+                return Err(Error::Help(self.usage()));
+            }
+        }
+
+        if self.options_first {
+            matches.rest = positional;
+        } else {
+            if positional.len() > self.args.len() {
+                return Err(invalid(format!("unexpected argument `{}`", positional[self.args.len()])));
+            }
+
+            for (arg, value) in self.args.iter().zip(positional) {
+                matches.args.insert(String::from(arg.name), value);
+            }
+
+            if let Some(arg) = self.args.iter().find(|arg| arg.required && !matches.args.contains_key(arg.name)) {
+                return Err(invalid(format!("missing argument <{}>", arg.name)));
+            }
+        }
+
+        for option in &self.options {
+            if matches.values.contains_key(option.long) {
+                continue;
+            }
+
+            if option.required {
+                return Err(invalid(format!("missing option `--{}`", option.long)));
+            }
+
+            if let Some(default) = option.default {
+                matches.values.insert(String::from(option.long), String::from(default));
+            }
+        }
+
+        Ok(matches)
+    }
+}
+
+/// The parsed options and arguments of a command.
+#[derive(Clone, Debug, Default)]
+pub struct Matches {
+    flags: HashSet<String>,
+    values: HashMap<String, String>,
+    args: HashMap<String, String>,
+    rest: Vec<String>,
+}
+
+impl Matches {
+    /// Whether a flag was given.
+    pub fn is_present(&self, long: &str) -> bool {
+        self.flags.contains(long)
+    }
+
+    /// The value of an option, or its default.
+    pub fn value_of(&self, long: &str) -> Option<&str> {
+        self.values.get(long).map(|value| &value[..])
+    }
+
+    /// The value of an option, parsed.
+    pub fn parse_value<T>(&self, long: &str) -> ::Result<Option<T>>
+    where T: FromStr, T::Err: fmt::Display {
+        match self.value_of(long) {
+            Some(value) => {
+                value.parse().map(Some).map_err(|e| {
+                    From::from(format!("invalid value `{}` for `--{}`: {}", value, long, e))
+                })
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// The value of a positional argument.
+    pub fn arg(&self, name: &str) -> Option<&str> {
+        self.args.get(name).map(|value| &value[..])
+    }
+
+    /// The arguments from the first positional one on, if the spec
+    /// parses options first.
+    pub fn rest(&self) -> &[String] {
+        &self.rest
+    }
+}
+
+/// Why the arguments weren't parsed.
+#[derive(Debug)]
+pub enum Error {
+    /// Help was requested; this is the help.
+    Help(String),
+
+    /// The version was requested; this is the version.
+    Version(String),
+
+    /// The arguments don't match the spec; this is why, and the help.
+    Invalid(String, String),
+}
+
+impl Error {
+    /// Print the help or the problem and exit, successfully if it
+    /// was requested.
+    pub fn exit(&self) -> ! {
+        match *self {
+            Error::Help(ref text) | Error::Version(ref text) => {
+                println!("{}", text);
+                process::exit(0);
+            },
+            Error::Invalid(..) => {
+                eprintln!("{}", self);
+                process::exit(1);
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Help(ref text) | Error::Version(ref text) => f.write_str(text),
+            Error::Invalid(ref message, ref usage) => write!(f, "{}\n\n{}", message, usage),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Help(_) => "help requested",
+            Error::Version(_) => "version requested",
+            Error::Invalid(..) => "invalid arguments",
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Spec, Opt, Error};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    fn spec() -> Spec {
+        Spec::new("diff-builds")
+            .option(Opt::value("redirects", "F", "Write a redirect map to F").short('r'))
+            .option(Opt::value("concurrency", "N", "Requests at once").default("8"))
+            .option(Opt::flag("dry-run", "Only report").short('n'))
+            .arg("old")
+            .optional_arg("new")
+    }
+
+    #[test]
+    fn parse_options_and_arguments() {
+        let matches = spec().parse(&args(&["-nr", "map.txt", "old.json", "--jobs=4", "new.json"])).unwrap();
+
+        assert!(matches.is_present("dry-run"));
+        assert_eq!(matches.value_of("redirects"), Some("map.txt"));
+        assert_eq!(matches.parse_value::<usize>("jobs").unwrap(), Some(4));
+        assert_eq!(matches.value_of("concurrency"), Some("8"));
+        assert_eq!(matches.arg("old"), Some("old.json"));
+        assert_eq!(matches.arg("new"), Some("new.json"));
+    }
+
+    #[test]
+    fn reject_invalid_arguments() {
+        match spec().parse(&args(&["--bogus", "old.json"])) {
+            Err(Error::Invalid(ref message, _)) => assert!(message.contains("--bogus")),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match spec().parse(&args(&[])) {
+            Err(Error::Invalid(ref message, _)) => assert!(message.contains("<old>")),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match spec().parse(&args(&["old.json", "-h"])) {
+            Err(Error::Help(ref usage)) => assert!(usage.contains("--redirects F")),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn stop_at_the_command() {
+        let matches =
+            Spec::new("").options_first()
+            .parse(&args(&["-C", "site", "build", "-j", "2"]))
+            .unwrap();
+
+        assert_eq!(matches.value_of("chdir"), Some("site"));
+        assert_eq!(matches.rest(), &args(&["build", "-j", "2"])[..]);
+    }
+}
@@ -1,42 +1,53 @@
-use docopt::Docopt;
-
 use site::Site;
-use command::Command;
+use command::{self, Command};
+use command::args::{Spec, Opt};
 use configuration::Configuration;
+use util::dates;
 
-#[derive(Deserialize, Debug)]
-struct Options {
-    flag_jobs: Option<usize>,
-    flag_verbose: bool,
+fn spec() -> Spec {
+    Spec::new("build")
+        .option(Opt::flag("keep-going", "Continue building independent rules after a failure")
+                .short('k'))
+        .option(Opt::flag("strict-unused", "Fail if a rule's pattern matches no files"))
+        .option(Opt::flag("deny-warnings", "Fail if handlers warned about anything"))
+        .option(Opt::value("as-of", "DATE", "Build the site as of a date, e.g. YYYY-MM-DD"))
+        .option(Opt::value("profile", "NAME", "Apply a [profile.NAME] from Diecast.toml,\n\
+                                               overriding DIECAST_ENV"))
 }
 
-static USAGE: &'static str = "
-Usage:
-    diecast build [options]
-
-Options:
-    -h, --help          Print this message
-    -j N, --jobs N      Number of jobs to run in parallel
-    -v, --verbose       Use verbose output
-";
-
-pub struct Build;
+#[derive(Default)]
+pub struct Build {
+    args: Option<Vec<String>>,
+}
 
 impl Build {
-    pub fn configure(&mut self, configuration: &mut Configuration) {
+    pub fn configure(&mut self, configuration: &mut Configuration) -> ::Result<()> {
         // 1. merge options into configuration; options overrides config
         // 2. construct site from configuration
         // 3. build site
 
-        let options: Options = Docopt::new(USAGE)
-            .and_then(|d| d.help(true).deserialize())
-            .unwrap_or_else(|e| e.exit());
+        let matches = command::parse(&spec(), self.args.as_ref());
 
-        if let Some(jobs) = options.flag_jobs {
-            configuration.threads = jobs;
+        // options override the profile, so it goes first
+        if let Some(profile) = matches.value_of("profile") {
+            configuration.set_profile(profile)?;
         }
 
-        configuration.is_verbose = options.flag_verbose;
+        command::configure(&matches, configuration)?;
+
+        configuration.keep_going = matches.is_present("keep-going");
+        configuration.strict_unused = matches.is_present("strict-unused");
+        configuration.deny_warnings = matches.is_present("deny-warnings");
+
+        if let Some(date) = matches.value_of("as-of") {
+            let as_of =
+                dates::parse(date, &configuration.timezone)
+                .map_err(|e| format!("invalid --as-of date: {}", e))?;
+
+            configuration.as_of = Some(as_of);
+        }
+
+        Ok(())
     }
 }
 
@@ -45,8 +56,13 @@ impl Command for Build {
         "Build the site"
     }
 
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
     fn run(&mut self, site: &mut Site) -> ::Result<()> {
-        self.configure(site.configuration_mut());
+        self.configure(site.configuration_mut())?;
         site.build()
     }
 }
@@ -0,0 +1,357 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use command::{self, Command};
+use command::args::{Spec, Opt};
+use site::Site;
+use util::html;
+
+fn spec() -> Spec {
+    Spec::new("check-links")
+        .option(Opt::flag("external", "Also request external URLs"))
+        .option(Opt::value("concurrency", "N", "Number of external URLs to request at once")
+                .default("8"))
+        .about("This scans the HTML files in the output directory, which should\n\
+                already be built, and reports links and resources that point to\n\
+                files that don't exist or to anchors that aren't defined.\n\
+                \n\
+                Checking external URLs requires the `check-external` feature.")
+}
+
+/// A link that couldn't be resolved.
+struct Broken {
+    page: PathBuf,
+    url: String,
+    reason: String,
+}
+
+/// Decode the percent-encoded bytes of a URL path.
+fn decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let escaped =
+            bytes[index] == b'%'
+            && index + 2 < bytes.len()
+            && (bytes[index + 1] as char).is_digit(16)
+            && (bytes[index + 2] as char).is_digit(16);
+
+        if escaped {
+            decoded.push(u8::from_str_radix(&path[index + 1 .. index + 3], 16).unwrap());
+            index += 3;
+            continue;
+        }
+
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Whether the URL points outside of the site, e.g. `https://`, `mailto:`, or `//`.
+/// The path without the site's base path, e.g. `/a/` for `/blog/a/`.
+///
+/// The base path is only stripped at a boundary, so `/blogroll/` stays
+/// as it is under `/blog`.
+fn strip_base<'a>(path: &'a str, prefix: &str) -> &'a str {
+    let is_under_prefix =
+        path.starts_with(prefix) &&
+        (path.len() == prefix.len() || path[prefix.len()..].starts_with('/'));
+
+    if !prefix.is_empty() && is_under_prefix {
+        &path[prefix.len()..]
+    } else {
+        path
+    }
+}
+
+fn is_external(url: &str) -> bool {
+    if url.starts_with("//") {
+        return true;
+    }
+
+    match url.find(':') {
+        Some(colon) => !url[..colon].contains('/'),
+        None => false,
+    }
+}
+
+/// Resolve a relative path, removing `.` and `..` components.
+///
+/// Returns `None` if it escapes the root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            },
+            _ => (),
+        }
+    }
+
+    Some(normalized)
+}
+
+#[derive(Default)]
+pub struct CheckLinks {
+    args: Option<Vec<String>>,
+}
+
+impl CheckLinks {
+    /// The file in the output directory that a URL path refers to, if any.
+    fn target(files: &BTreeSet<PathBuf>, path: &Path, is_directory: bool) -> Option<PathBuf> {
+        if !is_directory && files.contains(path) {
+            return Some(path.to_path_buf());
+        }
+
+        let index = path.join("index.html");
+
+        if files.contains(&index) {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "check-external")]
+    fn check_external(urls: BTreeSet<String>, concurrency: usize) -> Vec<(String, String)> {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+        use std::time::Duration;
+
+        use ureq;
+
+        let queue = Arc::new(Mutex::new(urls.into_iter().collect::<Vec<_>>()));
+        let failures = Arc::new(Mutex::new(vec![]));
+
+        let agent =
+            ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(15))
+            .build();
+
+        let workers =
+            (0 .. concurrency.max(1))
+            .map(|_| {
+                let queue = queue.clone();
+                let failures = failures.clone();
+                let agent = agent.clone();
+
+                thread::spawn(move || {
+                    loop {
+                        let url = match queue.lock().unwrap().pop() {
+                            Some(url) => url,
+                            None => break,
+                        };
+
+                        // some servers don't support HEAD
+                        let result = match agent.head(&url).call() {
+                            Err(ureq::Error::Status(405, _)) => agent.get(&url).call(),
+                            result => result,
+                        };
+
+                        if let Err(e) = result {
+                            failures.lock().unwrap().push((url, e.to_string()));
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            let _ = worker.join();
+        }
+
+        let mut failures = failures.lock().unwrap().clone();
+        failures.sort();
+        failures
+    }
+
+    #[cfg(not(feature = "check-external"))]
+    fn check_external(_urls: BTreeSet<String>, _concurrency: usize) -> Vec<(String, String)> {
+        unreachable!()
+    }
+}
+
+impl Command for CheckLinks {
+    fn description(&self) -> &'static str {
+        "Report broken links in the output"
+    }
+
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
+    fn run(&mut self, site: &mut Site) -> ::Result<()> {
+        let matches = command::parse(&spec(), self.args.as_ref());
+        let external = matches.is_present("external");
+        let concurrency = matches.parse_value::<usize>("concurrency")?.unwrap_or(8);
+
+        if external && !cfg!(feature = "check-external") {
+            return Err(From::from("checking external URLs requires the `check-external` feature"));
+        }
+
+        let configuration = site.configuration();
+        let output = &configuration.output;
+
+        if !output.exists() {
+            return Err(From::from(
+                format!("the output directory {} doesn't exist; build the site first",
+                        output.display())));
+        }
+
+        // links may be prefixed with the path the site is hosted under
+        let prefix = configuration.base_path();
+
+        let mut files = BTreeSet::new();
+
+        for entry in WalkDir::new(output) {
+            let entry = entry?;
+
+            if entry.file_type().is_file() {
+                files.insert(entry.path().strip_prefix(output)?.to_path_buf());
+            }
+        }
+
+        let pages =
+            files.iter()
+            .filter(|file| file.extension().map_or(false, |e| e == "html" || e == "htm"))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut urls = BTreeMap::new();
+        let mut anchors = BTreeMap::new();
+
+        for page in &pages {
+            let contents = fs::read_to_string(output.join(page))?;
+
+            urls.insert(page.clone(), html::attributes(&contents, &["href", "src"]));
+
+            anchors.insert(
+                page.clone(),
+                html::attributes(&contents, &["id", "name"]).into_iter()
+                    .map(|(_, anchor)| anchor)
+                    .collect::<BTreeSet<_>>());
+        }
+
+        let mut broken = vec![];
+        let mut external = BTreeSet::new();
+        let mut checked = 0;
+
+        for (page, page_urls) in &urls {
+            for &(_, ref url) in page_urls {
+                checked += 1;
+
+                if is_external(url) {
+                    if url.starts_with("http://") || url.starts_with("https://") {
+                        external.insert(url.clone());
+                    }
+
+                    continue;
+                }
+
+                let without_query = url.split('?').next().unwrap();
+                let mut parts = without_query.splitn(2, '#');
+                let path = decode(parts.next().unwrap());
+                let fragment = parts.next().map(decode);
+
+                let target = if path.is_empty() {
+                    Some(page.clone())
+                } else {
+                    let is_directory = path.ends_with('/');
+
+                    let relative =
+                        if path.starts_with('/') {
+                            PathBuf::from(strip_base(&path, prefix).trim_start_matches('/'))
+                        } else {
+                            page.parent().unwrap_or(Path::new("")).join(&path)
+                        };
+
+                    normalize(&relative)
+                        .and_then(|relative| CheckLinks::target(&files, &relative, is_directory))
+                };
+
+                let target = match target {
+                    Some(target) => target,
+                    None => {
+                        broken.push(Broken {
+                            page: page.clone(),
+                            url: url.clone(),
+                            reason: String::from("missing target"),
+                        });
+
+                        continue;
+                    },
+                };
+
+                if let Some(fragment) = fragment {
+                    let defined =
+                        fragment.is_empty()
+                        || fragment == "top"
+                        || anchors.get(&target).map_or(true, |a| a.contains(&fragment));
+
+                    if !defined {
+                        broken.push(Broken {
+                            page: page.clone(),
+                            url: url.clone(),
+                            reason: String::from("missing anchor"),
+                        });
+                    }
+                }
+            }
+        }
+
+        if external {
+
+            println!("checking {} external URL(s)", external.len());
+
+            for (url, reason) in CheckLinks::check_external(external, concurrency) {
+                for (page, page_urls) in &urls {
+                    if page_urls.iter().any(|&(_, ref u)| *u == url) {
+                        broken.push(Broken {
+                            page: page.clone(),
+                            url: url.clone(),
+                            reason: reason.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if broken.is_empty() {
+            println!("{} link(s) in {} page(s) checked", checked, pages.len());
+            return Ok(());
+        }
+
+        for link in &broken {
+            println!("  {}  {} -> {}", link.reason, link.page.display(), link.url);
+        }
+
+        Err(From::from(format!("{} broken link(s)", broken.len())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::strip_base;
+
+    #[test]
+    fn strip_base_path_at_boundary() {
+        assert_eq!(strip_base("/blog/a/", "/blog"), "/a/");
+        assert_eq!(strip_base("/blog", "/blog"), "");
+        assert_eq!(strip_base("/blogroll/", "/blog"), "/blogroll/");
+        assert_eq!(strip_base("/a/", "/blog"), "/a/");
+        assert_eq!(strip_base("/a/", ""), "/a/");
+    }
+}
@@ -1,15 +1,8 @@
-use docopt::Docopt;
-
-use command::Command;
+use command::{self, Command};
+use command::args::{Spec, Opt};
 use configuration::Configuration;
 use site::Site;
 
-#[derive(Deserialize, Debug)]
-struct Options {
-    flag_verbose: bool,
-    flag_ignore_hidden: bool,
-}
-
 // TODO
 // the help message includes the wrong command
 // e.g. if someone did:
@@ -17,27 +10,26 @@ struct Options {
 //     .command("mess", clean)
 //
 // the `diecast help mess` will still show the `clean` command
-static USAGE: &'static str = "
-Usage:
-    diecast clean [options]
-
-Options:
-    -h, --help            Print this message
-    -v, --verbose         Use verbose output
-    -i, --ignore-hidden   Don't clean out hidden files and directories
-
-This removes the output directory.
-";
+fn spec() -> Spec {
+    Spec::new("clean")
+        .option(Opt::flag("ignore-hidden", "Don't clean out hidden files and directories")
+                .short('i'))
+        .about("This removes the output directory.")
+}
 
-pub struct Clean;
+#[derive(Default)]
+pub struct Clean {
+    args: Option<Vec<String>>,
+}
 
 impl Clean {
-    pub fn configure(&mut self, configuration: &mut Configuration) {
-        let options: Options = Docopt::new(USAGE)
-            .and_then(|d| d.help(true).deserialize())
-            .unwrap_or_else(|e| e.exit());
+    pub fn configure(&mut self, configuration: &mut Configuration) -> ::Result<()> {
+        let matches = command::parse(&spec(), self.args.as_ref());
+
+        command::configure(&matches, configuration)?;
+        configuration.ignore_hidden = matches.is_present("ignore-hidden");
 
-        configuration.ignore_hidden = options.flag_ignore_hidden;
+        Ok(())
     }
 }
 
@@ -46,8 +38,13 @@ impl Command for Clean {
         "Remove output directory"
     }
 
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
     fn run(&mut self, site: &mut Site) -> ::Result<()> {
-        self.configure(site.configuration_mut());
+        self.configure(site.configuration_mut())?;
 
         let target = &site.configuration().output;
 
@@ -1,28 +1,16 @@
-use docopt::Docopt;
-
 use site::Site;
-use command::Command;
+use command::{self, Command};
+use command::args::Spec;
 use configuration::Configuration;
 
-#[derive(Deserialize, Debug)]
-struct Options {
-    flag_jobs: Option<usize>,
-    flag_verbose: bool,
+fn spec() -> Spec {
+    Spec::new("deploy")
 }
 
-static USAGE: &'static str = "
-Usage:
-    diecast deploy [options]
-
-Options:
-    -h, --help          Print this message
-    -j N, --jobs N      Number of jobs to run in parallel
-    -v, --verbose       Use verbose output
-";
-
 pub struct Deploy<P>
 where P: Fn(&Site) -> ::Result<()> {
-    procedure: P
+    procedure: P,
+    args: Option<Vec<String>>,
 }
 
 impl<P> Deploy<P>
@@ -30,23 +18,18 @@ where P: Fn(&Site) -> ::Result<()> {
     pub fn new(procedure: P) -> Deploy<P> {
         Deploy {
             procedure: procedure,
+            args: None,
         }
     }
 
-    pub fn configure(&mut self, configuration: &mut Configuration) {
+    pub fn configure(&mut self, configuration: &mut Configuration) -> ::Result<()> {
         // 1. merge options into configuration; options overrides config
         // 2. construct site from configuration
         // 3. build site
 
-        let options: Options = Docopt::new(USAGE)
-            .and_then(|d| d.help(true).deserialize())
-            .unwrap_or_else(|e| e.exit());
+        let matches = command::parse(&spec(), self.args.as_ref());
 
-        if let Some(jobs) = options.flag_jobs {
-            configuration.threads = jobs;
-        }
-
-        configuration.is_verbose = options.flag_verbose;
+        command::configure(&matches, configuration)
     }
 }
 
@@ -56,8 +39,13 @@ where P: Fn(&Site) -> ::Result<()> {
         "Deploy the site"
     }
 
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
     fn run(&mut self, site: &mut Site) -> ::Result<()> {
-        self.configure(site.configuration_mut());
+        self.configure(site.configuration_mut())?;
         site.build()?;
         (self.procedure)(site)
     }
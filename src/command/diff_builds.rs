@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io::Write;
+
+use command::{self, Command};
+use command::args::{Spec, Opt};
+use manifest::Manifest;
+use site::Site;
+
+fn spec() -> Spec {
+    Spec::new("diff-builds")
+        .option(Opt::value("redirects", "F", "Write a redirect map for moved pages to F")
+                .short('r'))
+        .arg("old")
+        .arg("new")
+        .about("This compares the manifests of two builds, reporting pages that were\n\
+                added, removed, changed, or moved. Moved pages may break inbound links.")
+}
+
+#[derive(Default)]
+pub struct DiffBuilds {
+    args: Option<Vec<String>>,
+}
+
+impl Command for DiffBuilds {
+    fn description(&self) -> &'static str {
+        "Compare the manifests of two builds"
+    }
+
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
+    fn run(&mut self, _site: &mut Site) -> ::Result<()> {
+        let matches = command::parse(&spec(), self.args.as_ref());
+
+        let old = Manifest::load(matches.arg("old").unwrap())?;
+        let new = Manifest::load(matches.arg("new").unwrap())?;
+
+        let diff = old.diff(&new);
+
+        if diff.is_empty() {
+            println!("no differences");
+            return Ok(());
+        }
+
+        for entry in &diff.added {
+            println!("  added    {}", entry.url());
+        }
+
+        for entry in &diff.removed {
+            println!("  removed  {}", entry.url());
+        }
+
+        for entry in &diff.changed {
+            println!("  changed  {}", entry.url());
+        }
+
+        for &(ref from, ref to) in &diff.moved {
+            println!("  moved    {} → {}", from.url(), to.url());
+        }
+
+        if let Some(path) = matches.value_of("redirects") {
+            File::create(path)?.write_all(diff.redirects().as_bytes())?;
+            println!("wrote redirect map to {}", path);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use site::Site;
+use command::{self, Command};
+use command::args::{Spec, Opt};
+
+fn spec() -> Spec {
+    Spec::new("export")
+        .option(Opt::value("profile", "NAME", "Apply a [profile.NAME] from Diecast.toml,\n\
+                                               overriding DIECAST_ENV"))
+        .arg("path")
+        .about("This builds the site and writes its rules and their items, with their\n\
+                routes, dates, and metadata, to the given path as JSON, so that other\n\
+                tools can inspect the site, e.g. `diecast export context.json`.")
+}
+
+#[derive(Default)]
+pub struct Export {
+    args: Option<Vec<String>>,
+}
+
+impl Command for Export {
+    fn description(&self) -> &'static str {
+        "Export the structure of the site as JSON"
+    }
+
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
+    fn run(&mut self, site: &mut Site) -> ::Result<()> {
+        let matches = command::parse(&spec(), self.args.as_ref());
+        let path = matches.arg("path").unwrap();
+
+        {
+            let configuration = site.configuration_mut();
+
+            if let Some(profile) = matches.value_of("profile") {
+                configuration.set_profile(profile)?;
+            }
+
+            command::configure(&matches, configuration)?;
+        }
+
+        site.build()?;
+
+        let model = site.model();
+
+        model.save(path)?;
+
+        println!("exported {} rule(s) to {}", model.rules.len(), path);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! Commands provided by other programs.
+//!
+//! Like cargo, an unknown command `diecast foo` runs a `diecast-foo`
+//! program found in `PATH`, so that plugins can be shipped separately.
+//! It's passed the global options that come before the command's name,
+//! except `--chdir` since diecast has already entered that directory,
+//! followed by the arguments after the name. If it fails, `run`
+//! returns an `Exit` so that `main` can exit with the same code.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::process;
+
+use command::Command;
+use site::Site;
+
+pub struct External {
+    name: String,
+    program: PathBuf,
+    args: Vec<String>,
+}
+
+impl External {
+    /// Find the program of a command in `PATH`.
+    pub fn find(name: &str) -> Option<External> {
+        let file = format!("diecast-{}{}", name, env::consts::EXE_SUFFIX);
+        let path = env::var_os("PATH")?;
+
+        env::split_paths(&path)
+            .map(|directory| directory.join(&file))
+            .find(|program| program.is_file())
+            .map(|program| {
+                External {
+                    name: String::from(name),
+                    program: program,
+                    args: vec![],
+                }
+            })
+    }
+}
+
+/// A command that exited unsuccessfully, with its exit code.
+#[derive(Debug)]
+pub struct Exit {
+    pub name: String,
+    pub code: i32,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`diecast-{}` exited with code {}", self.name, self.code)
+    }
+}
+
+impl Error for Exit {
+    fn description(&self) -> &str {
+        "the command failed"
+    }
+}
+
+impl Command for External {
+    fn description(&self) -> &'static str {
+        "Run a command provided by another program"
+    }
+
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = args.to_vec();
+        Ok(())
+    }
+
+    fn run(&mut self, _site: &mut Site) -> ::Result<()> {
+        let mut command = process::Command::new(&self.program);
+        command.args(&self.args);
+
+        let status = command.status().map_err(|e| {
+            format!("could not run `{}`: {}", self.program.display(), e)
+        })?;
+
+        if !status.success() {
+            match status.code() {
+                Some(code) => {
+                    return Err(Box::new(Exit {
+                        name: self.name.clone(),
+                        code: code,
+                    }));
+                },
+                None => {
+                    return Err(From::from(
+                        format!("`diecast-{}` was terminated by a signal", self.name)));
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
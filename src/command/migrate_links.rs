@@ -0,0 +1,171 @@
+use std::fs::{self, File};
+use std::io::Write;
+
+use regex::{self, Regex};
+
+use command::{self, Command};
+use command::args::{Spec, Opt};
+use manifest::Manifest;
+use site::Site;
+use util::handle::item::split_front_matter;
+
+fn spec() -> Spec {
+    Spec::new("migrate-links")
+        .option(Opt::value("from", "URL", "The URL prefix to replace, e.g. /old/").required())
+        .option(Opt::value("to", "URL", "The URL prefix to replace it with, e.g. /new/").required())
+        .option(Opt::value("extensions", "E", "Comma-separated extensions of the content files\n\
+                                               to update")
+                .short('e')
+                .default("md,markdown,html,htm"))
+        .option(Opt::value("redirects", "F", "Write a redirect map from the old URLs of the\n\
+                                              last build's pages to their new URLs to F")
+                .short('r'))
+        .option(Opt::flag("dry-run", "Only report what would change").short('n'))
+        .about("This replaces links that start with the old prefix in the content files\n\
+                of the input directory, leaving their front matter untouched. Pages at\n\
+                the old URLs are found in the configured manifest, which should be from\n\
+                a build made before the migration, so that inbound links from elsewhere\n\
+                can be redirected.")
+}
+
+#[derive(Default)]
+pub struct MigrateLinks {
+    args: Option<Vec<String>>,
+}
+
+impl MigrateLinks {
+    /// Replace the links in a content file, preserving its front matter.
+    ///
+    /// Returns the new contents and the number of links replaced.
+    fn migrate(contents: &str, link: &Regex, to: &str) -> (String, usize) {
+        let (front_matter, body) = match split_front_matter(contents) {
+            Some((_, body)) => contents.split_at(contents.len() - body.len()),
+            None => ("", contents),
+        };
+
+        let count = link.find_iter(body).count();
+
+        if count == 0 {
+            return (String::from(contents), 0);
+        }
+
+        let replacement = format!("${{before}}{}", to.replace('$', "$$"));
+        let body = link.replace_all(body, &replacement[..]);
+
+        (format!("{}{}", front_matter, body), count)
+    }
+}
+
+impl Command for MigrateLinks {
+    fn description(&self) -> &'static str {
+        "Move links from one URL prefix to another"
+    }
+
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
+    fn run(&mut self, site: &mut Site) -> ::Result<()> {
+        let matches = command::parse(&spec(), self.args.as_ref());
+
+        let (from, to) = (matches.value_of("from").unwrap(), matches.value_of("to").unwrap());
+        let dry_run = matches.is_present("dry-run");
+
+        if !from.starts_with('/') || !to.starts_with('/') {
+            return Err(From::from("the prefixes must be root-relative URLs, e.g. /old/"));
+        }
+
+        let extensions =
+            matches.value_of("extensions").unwrap().split(',')
+            .map(|e| e.trim().trim_start_matches('.'))
+            .filter(|e| !e.is_empty())
+            .collect::<Vec<_>>();
+
+        // only match the prefix where a link would start, e.g. after `](`, `href="`,
+        // or the space of a reference definition
+        let link = Regex::new(
+            &format!(r#"(?P<before>[(\["'<=\s]){}"#, regex::escape(from)))?;
+
+        let configuration = site.configuration();
+        let input = &configuration.input;
+
+        let mut files = 0;
+        let mut links = 0;
+
+        for entry in configuration.walk_input() {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let is_content =
+                path.extension()
+                .and_then(|e| e.to_str())
+                .map_or(false, |e| extensions.iter().any(|x| x.eq_ignore_ascii_case(e)));
+
+            let ignored = configuration.is_ignored(path.strip_prefix(input)?);
+
+            if !is_content || ignored {
+                continue;
+            }
+
+            let contents = match fs::read_to_string(path) {
+                Ok(contents) => contents,
+                // not text
+                Err(_) => continue,
+            };
+
+            let (migrated, count) = MigrateLinks::migrate(&contents, &link, to);
+
+            if count == 0 {
+                continue;
+            }
+
+            println!("  {:>4} link(s)  {}", count, path.strip_prefix(input)?.display());
+
+            if !dry_run {
+                fs::write(path, migrated)?;
+            }
+
+            files += 1;
+            links += count;
+        }
+
+        if let Some(path) = matches.value_of("redirects") {
+            let manifest = match configuration.manifest {
+                Some(ref manifest) if manifest.exists() => Manifest::load(manifest)?,
+                _ => {
+                    return Err(From::from(
+                        "a redirect map requires the manifest of a previous build; \
+                         configure `diecast.manifest` and build before migrating"));
+                },
+            };
+
+            let mut map = String::new();
+
+            for entry in &manifest.entries {
+                let url = entry.url();
+
+                if url.starts_with(&from[..]) {
+                    map.push_str(&format!("{} {}{}\n", url, to, &url[from.len()..]));
+                }
+            }
+
+            if dry_run {
+                print!("{}", map);
+            } else {
+                File::create(path)?.write_all(map.as_bytes())?;
+                println!("wrote redirect map to {}", path);
+            }
+        }
+
+        let verb = if dry_run { "would replace" } else { "replaced" };
+
+        println!("{} {} link(s) in {} file(s)", verb, links, files);
+
+        Ok(())
+    }
+}
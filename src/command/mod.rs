@@ -1,19 +1,43 @@
 use std::collections::HashMap;
+use std::env;
 use std::error::Error;
+use std::process;
+use std::sync::Arc;
 
-use docopt::{self, Docopt};
+use ctrlc;
 
+use configuration::Configuration;
+use job::{self, reporter};
 use site::Site;
 
+use self::args::{Spec, Opt, Matches};
+
+pub mod args;
 pub mod build;
+pub mod check_links;
 pub mod clean;
 pub mod deploy;
+pub mod diff_builds;
+pub mod export;
+pub mod external;
+pub mod migrate_links;
+pub mod verify;
 
 pub trait Command {
     // TODO
     // not sure that it should have a description method
     // this should probably be provided separately?
     fn description(&self) -> &'static str;
+
+    /// Receive the arguments that follow the command's name.
+    ///
+    /// `Builder::build` passes them before the command is run. The
+    /// built-in commands parse the process's arguments if they aren't
+    /// given any.
+    fn parse_args(&mut self, _args: &[String]) -> ::Result<()> {
+        Ok(())
+    }
+
     fn run(&mut self, site: &mut Site) -> ::Result<()>;
 }
 
@@ -23,28 +47,140 @@ where C: Command {
         (**self).description()
     }
 
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        (**self).parse_args(args)
+    }
+
     fn run(&mut self, site: &mut Site) -> ::Result<()> {
         (**self).run(site)
     }
 }
 
-#[derive(Deserialize, Debug)]
-struct Options {
-    arg_command: Option<String>,
-    arg_args: Vec<String>,
+/// Split the process's arguments into the command and its arguments.
+///
+/// Global options that come before the command are passed on to it,
+/// e.g. `diecast -j 4 build` is `build -j 4`.
+fn split(argv: &[String]) -> Result<(Option<String>, Vec<String>), args::Error> {
+    let (command, mut before, after) = split_around(argv)?;
+
+    before.extend(after);
+
+    Ok((command, before))
+}
+
+/// Split the process's arguments into those before the command, the
+/// command, and those after it.
+fn split_around(argv: &[String])
+-> Result<(Option<String>, Vec<String>, Vec<String>), args::Error> {
+    let matches =
+        Spec::new("<command>")
+        .option(Opt::flag("version", "Print version info").short('V'))
+        .options_first()
+        .parse(argv)?;
+
+    if matches.is_present("version") {
+        return Err(args::Error::Version(version()));
+    }
+
+    let rest = matches.rest();
+    let before = &argv[.. argv.len() - rest.len()];
+
+    match rest.split_first() {
+        Some((command, after)) => Ok((Some(command.clone()), before.to_vec(), after.to_vec())),
+        None => Ok((None, before.to_vec(), vec![])),
+    }
+}
+
+/// Remove the `-C DIR` and `--chdir DIR` options from arguments,
+/// returning the last directory.
+fn take_chdir(args: &mut Vec<String>) -> Option<String> {
+    let mut chdir = None;
+    let mut kept = Vec::with_capacity(args.len());
+    let mut rest = args.drain(..);
+
+    while let Some(arg) = rest.next() {
+        if arg == "--" {
+            kept.push(arg);
+            kept.extend(rest.by_ref());
+            break;
+        }
+
+        if arg == "-C" || arg == "--chdir" {
+            chdir = rest.next();
+        } else if arg.starts_with("--chdir=") {
+            chdir = Some(String::from(&arg["--chdir=".len()..]));
+        } else {
+            kept.push(arg);
+        }
+    }
+
+    drop(rest);
+    *args = kept;
+
+    chdir
+}
+
+/// Parse a command's arguments against its spec.
+///
+/// These are the arguments that follow the command's name, or the
+/// process's if there are none. This exits with the command's help if
+/// they don't match the spec or help is requested.
+pub fn parse(spec: &Spec, args: Option<&Vec<String>>) -> Matches {
+    let args = match args {
+        Some(args) => args.clone(),
+        None => {
+            let argv = env::args().skip(1).collect::<Vec<_>>();
+
+            split(&argv).map(|(_, args)| args).unwrap_or_else(|e| e.exit())
+        },
+    };
+
+    spec.parse(&args).unwrap_or_else(|e| e.exit())
+}
+
+/// Apply the global options that affect the configuration.
+pub fn configure(matches: &Matches, configuration: &mut Configuration) -> ::Result<()> {
+    if let Some(jobs) = matches.parse_value("jobs")? {
+        configuration.threads = jobs;
+    }
+
+    configuration.is_verbose = matches.is_present("verbose");
+
+    match matches.value_of("message-format") {
+        Some("json") => configuration.reporter = Arc::new(reporter::Json),
+        Some("human") | None => {
+            if matches.is_present("quiet") {
+                configuration.reporter = Arc::new(reporter::Quiet);
+            }
+        },
+        Some(format) => {
+            return Err(From::from(
+                format!("unknown message format `{}`; expected `human` or `json`", format)));
+        },
+    }
+
+    Ok(())
 }
 
-static USAGE: &'static str = "
-Usage:
-    diecast <command> [<args>...]
-    diecast [options]
+/// Cancel the build on Ctrl-C, or exit on a second one.
+///
+/// The build finishes the jobs that are running and fails, so the
+/// previous output stays live. See `job::cancel`.
+fn handle_interrupts() {
+    let installed = ctrlc::set_handler(|| {
+        if job::is_cancelled() {
+            process::exit(130);
+        }
 
-Options:
-    -h, --help           Print this message
-    -v, --version        Print version info
+        eprintln!("interrupted; waiting for the running jobs to finish");
+        job::cancel();
+    });
 
-Possible commands include:
-";
+    // another handler may have been installed by the embedder
+    if let Err(e) = installed {
+        debug!("could not handle Ctrl-C: {}", e);
+    }
+}
 
 pub fn version() -> String {
     format!("diecast {}", match option_env!("CFG_VERSION") {
@@ -68,8 +204,13 @@ impl Builder {
         };
 
         builder
-            .command("build", build::Build)
-            .command("clean", clean::Clean)
+            .command("build", build::Build::default())
+            .command("check-links", check_links::CheckLinks::default())
+            .command("clean", clean::Clean::default())
+            .command("diff-builds", diff_builds::DiffBuilds::default())
+            .command("export", export::Export::default())
+            .command("migrate-links", migrate_links::MigrateLinks::default())
+            .command("verify", verify::Verify::default())
     }
 
     pub fn command<S, C>(mut self, name: S, command: C) -> Builder
@@ -78,84 +219,121 @@ impl Builder {
         self
     }
 
+    /// The help, which lists the commands.
+    pub fn usage(&self) -> String {
+        let mut commands =
+            self.commands.iter()
+            .collect::<Vec<(&String, &Box<Command>)>>();
+
+        commands.sort_by(|a, b| a.0.cmp(b.0));
+
+        let width = commands.iter().map(|&(name, _)| name.len()).max().unwrap_or(0) + 2;
+
+        let mut about = String::from(
+            "Possible commands include those below, and any `diecast-<command>`\n\
+             program in PATH:\n");
+
+        for &(name, command) in &commands {
+            about.push_str(&format!("    {}{}{}\n",
+                name, " ".repeat(width - name.len()), command.description()));
+        }
+
+        Spec::new("<command>")
+            .option(Opt::flag("version", "Print version info").short('V'))
+            .optional_arg("args...")
+            .about(about)
+            .usage()
+    }
+
     pub fn build(mut self) -> Result<Box<Command>, Box<Error>> {
-        let mut usage = String::from(USAGE);
+        handle_interrupts();
 
-        {
-            let mut cmds =
-                self.commands.iter()
-                .collect::<Vec<(&String, &Box<Command>)>>();
+        let usage = self.usage();
+        let argv = env::args().skip(1).collect::<Vec<_>>();
 
-            cmds.sort_by(|a, b| a.0.cmp(b.0));
+        let (cmd, mut before, mut after) = match split_around(&argv) {
+            Ok(split) => split,
+            Err(args::Error::Help(_)) => return Err(Box::new(args::Error::Help(usage))),
+            Err(args::Error::Invalid(message, _)) => {
+                return Err(Box::new(args::Error::Invalid(message, usage)));
+            },
+            Err(e) => return Err(Box::new(e)),
+        };
 
-            for &(k, v) in &cmds {
-                usage.push_str("    ");
-                usage.push_str(&k);
+        let cmd = cmd.unwrap_or_else(String::new);
 
-                // TODO: proper padding
-                if k.len() > 11 {
-                    panic!("the command name should be less than 12 characters");
-                }
+        // `-C` may follow a built-in command, but the arguments of
+        // another program are its own
+        let mut chdir = take_chdir(&mut before);
 
-                let pad = 12 - k.len();
-                usage.push_str(&::std::iter::repeat(' ').take(pad).collect::<String>());
-                usage.push_str(&v.description());
-                usage.push('\n');
-            }
+        if self.commands.contains_key(&cmd) || cmd == "help" {
+            chdir = take_chdir(&mut after).or(chdir);
+        }
+
+        // relative paths, including those of the configuration that's
+        // loaded after this, are then relative to the directory
+        if let Some(directory) = chdir {
+            env::set_current_dir(&directory).map_err(|e| {
+                format!("could not change to the directory `{}`: {}", directory, e)
+            })?;
         }
 
-        let options: Options =
-            Docopt::new(usage.clone())
-                .and_then(|d| {
-                    d.options_first(true)
-                        .help(true)
-                        .version(Some(version()))
-                        .decode()
-                })?;
-
-        let cmd = options.arg_command.unwrap();
-
-        let err =
-            Err(From::from(docopt::Error::WithProgramUsage(
-                Box::new(docopt::Error::Help),
-                String::from(usage))));
-
-        // TODO
-        // the way this was changed prevents any commands from being passed
-        // to the sub-command. what needs to happen is that the flags need to
-        // be passed onto the sub-command once it is chosen
-        //
-        // Use Docopt.argv() to explicitly pass the _full_ arguments including
-        // the binary name.
-        //
-        // construct these args here, same as cargo is doing here:
-        // https://github.com/rust-lang/cargo/blob/master/src/bin/cargo.rs#L141
-        //
-        // UPDATE
-        // false alarm? The command runs Docopt itself on env::args() so
-        // everything seems to be working fine, it's just that the command has
-        // to ensure that Docopt is run and its effects take place
-        //
-        // that said, perhaps it _is_ beneficial to explicitly pass the argv to
-        // the command, so that the root command can support `diecast help subcommand`,
-        // which ends up rewriting the argv to [diecast, subcommand, -h]
-        //
-        // that may also be necessary to support external diecast-cmd binaries
-        // in PATH?
-        let command: Box<Command> = match &cmd[..] {
-            "" | "help" if options.arg_args.is_empty() => return err,
+        let err = Err(Box::new(args::Error::Help(usage.clone())) as Box<Error>);
+
+        // `diecast help <command>` is `diecast <command> -h`, where the
+        // command's name follows `help` rather than the global options
+        let (cmd, args) =
+            if cmd == "help" && !after.is_empty() {
+                (after[0].clone(), vec![String::from("-h")])
+            } else {
+                before.extend(after);
+                (cmd, before)
+            };
+
+        let mut command: Box<Command> = match &cmd[..] {
+            "" | "help" => return err,
             cmd => {
                 if let Some(command) = self.commands.remove(cmd) {
                     command
+                } else if let Some(external) = external::External::find(cmd) {
+                    Box::new(external)
                 } else {
-                    // here look in PATH to find program named diecast-$cmd
-                    // if not found, then output this message:
-                    println!("unknown command `{}`", cmd);
-                    return err;
+                    return Err(Box::new(args::Error::Invalid(
+                        format!("unknown command `{}`", cmd), usage)));
                 }
             },
         };
 
+        command.parse_args(&args)?;
+
         Ok(command)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{split_around, take_chdir};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&arg| String::from(arg)).collect()
+    }
+
+    #[test]
+    fn forward_options_without_chdir() {
+        let (command, mut before, after) =
+            split_around(&args(&["-C", "site", "-j", "2", "deploy", "-C", "x"])).unwrap();
+
+        assert_eq!(take_chdir(&mut before), Some(String::from("site")));
+        assert_eq!(command, Some(String::from("deploy")));
+        assert_eq!(before, args(&["-j", "2"]));
+        assert_eq!(after, args(&["-C", "x"]));
+    }
+
+    #[test]
+    fn take_the_last_chdir() {
+        let mut options = args(&["--chdir=a", "-v", "-C", "b", "--", "-C", "c"]);
+
+        assert_eq!(take_chdir(&mut options), Some(String::from("b")));
+        assert_eq!(options, args(&["-v", "--", "-C", "c"]));
+    }
+}
@@ -0,0 +1,56 @@
+use command::{self, Command};
+use command::args::Spec;
+use manifest::Manifest;
+use site::Site;
+
+fn spec() -> Spec {
+    Spec::new("verify")
+        .optional_arg("manifest")
+        .about("This checks the files in the output directory against a build manifest,\n\
+                which defaults to the configured one, reporting files that are missing\n\
+                or whose contents changed since they were built.")
+}
+
+#[derive(Default)]
+pub struct Verify {
+    args: Option<Vec<String>>,
+}
+
+impl Command for Verify {
+    fn description(&self) -> &'static str {
+        "Check the output against a manifest"
+    }
+
+    fn parse_args(&mut self, args: &[String]) -> ::Result<()> {
+        self.args = Some(args.to_vec());
+        Ok(())
+    }
+
+    fn run(&mut self, site: &mut Site) -> ::Result<()> {
+        let matches = command::parse(&spec(), self.args.as_ref());
+
+        let path = match matches.arg("manifest") {
+            Some(path) => From::from(path),
+            None => {
+                match site.configuration().manifest {
+                    Some(ref path) => path.clone(),
+                    None => return Err(From::from("no manifest was given or configured")),
+                }
+            },
+        };
+
+        let manifest = Manifest::load(&path)?;
+        let mismatched = manifest.verify(&site.configuration().output)?;
+
+        if mismatched.is_empty() {
+            println!("{} file(s) verified", manifest.entries.len());
+            return Ok(());
+        }
+
+        for entry in &mismatched {
+            println!("  mismatch  {}", entry.url());
+        }
+
+        Err(From::from(format!("{} file(s) don't match the manifest", mismatched.len())))
+    }
+}
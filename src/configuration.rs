@@ -1,14 +1,206 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
 use std::sync::Arc;
+use std::env;
+use std::time::Duration;
 
 use num_cpus;
+use serde::de::DeserializeOwned;
 use toml;
+use chrono::Utc;
+use chrono_tz::Tz;
 use regex::Regex;
+use walkdir::WalkDir;
 
-use pattern::Pattern;
+use job::reporter::{Reporter, Human};
+use pattern::{Pattern, AnyOf, Gitignore};
+use support;
+use util::dates::{self, DateTime};
+use vfs::{Vfs, Disk, Sandbox};
+
+/// When the rules' scratch directories are removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scratch {
+    /// Remove them at the end of every build.
+    Remove,
+
+    /// Keep them if the build failed, to help debug external tools.
+    KeepOnFailure,
+
+    /// Never remove them, other than at the start of the next build.
+    Keep,
+}
+
+/// How files that are copied verbatim, such as images, get to the output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Passthrough {
+    /// Copy the bytes.
+    Copy,
+
+    /// Hard-link the input file, which requires that the input and
+    /// output be on the same file system. The output then shares the
+    /// input's contents, so it must not be modified in place.
+    HardLink,
+
+    /// Clone the input file, which requires a copy-on-write file
+    /// system such as Btrfs or XFS.
+    Reflink,
+
+    /// Symlink the input file, which makes unchanged statics nearly free
+    /// to rebuild on any file system. The output then points into the
+    /// input directory, so it must be deployed by following links, e.g.
+    /// with `rsync -L`, and it changes whenever the input does.
+    Symlink,
+}
+
+impl Passthrough {
+    /// Parse `copy`, `hardlink`, `reflink`, or `symlink`.
+    pub fn parse(name: &str) -> Option<Passthrough> {
+        match name {
+            "copy" => Some(Passthrough::Copy),
+            "hardlink" => Some(Passthrough::HardLink),
+            "reflink" => Some(Passthrough::Reflink),
+            "symlink" => Some(Passthrough::Symlink),
+            _ => None,
+        }
+    }
+}
+
+/// How previewed pages are told to reload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Livereload {
+    /// Don't inject the client.
+    Off,
+
+    /// Connect with a WebSocket, falling back to server-sent events.
+    WebSocket,
+
+    /// Connect with server-sent events, which work through proxies that
+    /// don't forward WebSockets, falling back to polling.
+    Sse,
+}
+
+impl Livereload {
+    /// Parse `off`, `websocket`, or `sse`.
+    pub fn parse(name: &str) -> Option<Livereload> {
+        match name {
+            "off" => Some(Livereload::Off),
+            "websocket" => Some(Livereload::WebSocket),
+            "sse" => Some(Livereload::Sse),
+            _ => None,
+        }
+    }
+}
+
+/// What happens when two items are written to the same path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collisions {
+    /// Fail the build, listing every collision.
+    Fail,
+
+    /// Print a warning; the item written last wins.
+    Warn,
+}
+
+impl Collisions {
+    /// Parse `fail` or `warn`.
+    pub fn parse(name: &str) -> Option<Collisions> {
+        match name {
+            "fail" => Some(Collisions::Fail),
+            "warn" => Some(Collisions::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// The `[site]` table of `Diecast.toml`, which templates see as `site`.
+///
+/// ```toml
+/// [site]
+/// title = "My Blog"
+/// author = "Jane Doe"
+/// description = "Notes on things"
+/// base_url = "https://example.com/"
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SiteMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+
+    /// The configuration's `base_url`, which defaults to this
+    pub base_url: Option<String>,
+
+    /// Any other keys
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, toml::Value>,
+}
+
+/// A rule declared in a `[[rule]]` table of `Diecast.toml`.
+///
+/// The `handler` names a preset of `rule::Presets`, which is given the
+/// table to build the rule's handler from, e.g.
+///
+/// ```toml
+/// [[rule]]
+/// name = "statics"
+/// pattern = "images/**/*"
+/// handler = "copy"
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RuleTable {
+    pub name: String,
+
+    /// A glob of input paths
+    pub pattern: String,
+
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+
+    /// The name of the preset
+    pub handler: String,
+
+    /// Any other keys, as options of the preset
+    #[serde(flatten)]
+    pub options: BTreeMap<String, toml::Value>,
+}
+
+/// The keys recognized in the `[diecast]` table.
+static KEYS: [&'static str; 24] = [
+    "input", "output", "cache", "manifest", "ignore", "base_url", "data",
+    "gzip", "brotli", "precompress_threshold", "passthrough", "seed",
+    "watchdog", "timezone", "date_format", "languages", "livereload",
+    "tombstones", "collisions", "deterministic", "follow_links", "max_depth",
+    "include_hidden", "output_manifest",
+];
+
+/// The problems found while loading the configuration.
+#[derive(Debug)]
+pub struct Invalid {
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "the configuration is invalid:")?;
+
+        for error in &self.errors {
+            writeln!(f, "  * {}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for Invalid {
+    fn description(&self) -> &str {
+        "the configuration is invalid"
+    }
+}
 
 // TODO: audit
 
@@ -16,17 +208,50 @@ use pattern::Pattern;
 /// an Arc of this is given to each Item
 #[derive(Clone)]
 pub struct Configuration {
+    /// The configuration file as written
+    base: toml::Value,
+
+    /// The configuration file with the selected profile applied
     toml: toml::Value,
 
+    /// The selected profile, if any
+    pub profile: Option<String>,
+
+    /// The project's root directory, which other paths are relative to
+    pub root: PathBuf,
+
     /// The input directory
     pub input: PathBuf,
 
     /// The output directory
     pub output: PathBuf,
 
-    // TODO: necessary?
-    // The cache directory
-    // cache: PathBuf,
+    /// The directory of data files; see `util::data`
+    pub data: PathBuf,
+
+    /// Where to write the build manifest, if anywhere
+    pub manifest: Option<PathBuf>,
+
+    /// The cache directory
+    pub cache: PathBuf,
+
+    /// The absolute URL that the site is deployed at, e.g. `https://example.com/blog`
+    pub base_url: Option<String>,
+
+    /// The previous build's output, which stays live while the
+    /// new output is staged, so that unchanged files can be reused
+    pub previous: Option<PathBuf>,
+
+    /// The subdirectory of the site's output that `output` is, for a
+    /// rule that writes into one; see `rule::Builder::output`
+    pub subdirectory: Option<PathBuf>,
+
+    /// The file system that handlers access
+    pub vfs: Arc<Vfs + Sync + Send>,
+
+    /// Whether handlers are confined to the input, output,
+    /// and cache directories
+    pub is_strict: bool,
 
     /// The root command that was invoked
     pub command: String,
@@ -37,16 +262,117 @@ pub struct Configuration {
     /// Verbosity flag
     pub is_verbose: bool,
 
+    /// How the progress of a build is shown
+    ///
+    /// See `job::reporter`. The status lines by default.
+    pub reporter: Arc<Reporter + Sync + Send>,
+
     /// a global pattern used to ignore files and paths
     ///
-    /// the following are from hakyll
+    /// It's matched against paths relative to the input directory, like
+    /// the patterns of `bind::select`, and an ignored directory's contents
+    /// are skipped. In `Diecast.toml` it's a regex or a list of them,
+    /// which are combined with `pattern::AnyOf`. The following are from hakyll
     /// e.g.
-    /// config.ignore = regex!("^\.|^#|~$|\.swp$")
+    /// config.ignore = regex!("(^|/)#|~$|\.swp$")
     pub ignore: Option<Arc<Pattern + Sync + Send>>,
 
+    /// The patterns of the project's `.diecastignore`, if it has one
+    ///
+    /// These are in the syntax of `.gitignore` and are matched against
+    /// paths relative to the input directory, in addition to `ignore`.
+    pub ignore_file: Option<Arc<Gitignore>>,
+
+    /// Whether the input directory is walked through symbolic links
+    pub follow_links: bool,
+
+    /// How deep the input directory is walked, where 1 is only the files
+    /// directly in it. Unlimited by default.
+    pub max_depth: Option<usize>,
+
+    /// Whether hidden files and directories, whose names start with a
+    /// dot, are input paths. Defaults to true.
+    pub include_hidden: bool,
+
     /// Whether we're in preview mode
     pub is_preview: bool,
 
+    /// The date to build the site as of, instead of the current time
+    ///
+    /// This determines which scheduled and expiring content is published.
+    pub as_of: Option<DateTime>,
+
+    /// The timezone of dates that don't have an offset, e.g. `Europe/Paris`
+    pub timezone: Tz,
+
+    /// The default format of dates in templates, e.g. `%B %-d, %Y`
+    pub date_format: String,
+
+    /// Whether to also write the manifest into the output directory,
+    /// as `manifest::OUTPUT_NAME`, for tools that only see the output
+    pub output_manifest: bool,
+
+    /// Whether to write a page in place of every page that the previous
+    /// build's `manifest` has but this build doesn't; see `util::tombstone`
+    pub tombstones: bool,
+
+    /// Whether builds are made reproducible at some cost: input paths
+    /// are selected in sorted order and the manifest is sorted by target
+    pub deterministic: bool,
+
+    /// Whether to write gzipped siblings of text outputs
+    pub gzip: bool,
+
+    /// Whether to write brotli-compressed siblings of text outputs
+    pub brotli: bool,
+
+    /// The size in bytes below which outputs aren't precompressed
+    pub precompress_threshold: usize,
+
+    /// Whether rules whose patterns match no files are an error
+    /// rather than a warning
+    pub strict_unused: bool,
+
+    /// Whether the build fails if handlers warned about anything
+    ///
+    /// See `diag`.
+    pub deny_warnings: bool,
+
+    /// Whether to keep processing independent binds after one fails
+    ///
+    /// By default the build stops at the first failure.
+    pub keep_going: bool,
+
+    /// When the rules' scratch directories are removed
+    pub scratch: Scratch,
+
+    /// The seed of the random number generators in `util::random`
+    pub seed: u64,
+
+    /// The codes of the site's languages, e.g. `en`, the default first
+    ///
+    /// See `util::i18n`.
+    pub languages: Vec<String>,
+
+    /// How files that are copied verbatim get to the output
+    ///
+    /// Linking and cloning fall back to copying where they aren't supported.
+    pub passthrough: Passthrough,
+
+    /// What happens when two items are written to the same path, by
+    /// default a warning
+    pub collisions: Collisions,
+
+    /// How previewed pages are told to reload
+    ///
+    /// This only applies when previewing; see `livereload_injected`.
+    pub livereload: Livereload,
+
+    /// How long a handler may run before it's reported as stuck
+    ///
+    /// See `job::watchdog`. Disabled by default.
+    pub watchdog: Option<Duration>,
+
     // TODO
     // should this just be implicit in the ignore field?
     // e.g. ^\.
@@ -55,60 +381,778 @@ pub struct Configuration {
     pub ignore_hidden: bool,
 }
 
-// TODO configuration hierarchy
-// CLI -> toml -> code -> defaults
+/// Look up a value in a table by its dotted key.
+fn lookup<'a>(table: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.').fold(Some(table), |value, part| value.and_then(|v| v.get(part)))
+}
+
+impl Default for Configuration {
+    /// The defaults, without reading `Diecast.toml` or the environment.
+    fn default() -> Configuration {
+        Configuration {
+            base: toml::Value::Table(BTreeMap::new()),
+            toml: toml::Value::Table(BTreeMap::new()),
+            profile: None,
+            root: PathBuf::from("."),
+            // TODO: setting it to error by default seems like a wart
+            input: PathBuf::from("input"),
+            output: PathBuf::from("output"),
+            cache: PathBuf::from(".cache"),
+            base_url: None,
+            previous: None,
+            subdirectory: None,
+            vfs: Arc::new(Disk),
+            is_strict: false,
+            data: PathBuf::from("data"),
+            manifest: None,
+            command: String::new(),
+            threads: num_cpus::get(),
+            is_verbose: false,
+            reporter: Arc::new(Human),
+            ignore: None,
+            ignore_file: None,
+            follow_links: false,
+            max_depth: None,
+            include_hidden: true,
+            is_preview: false,
+            as_of: None,
+            timezone: Tz::UTC,
+            date_format: String::from("%B %-d, %Y"),
+            tombstones: false,
+            output_manifest: false,
+            deterministic: false,
+            gzip: false,
+            brotli: false,
+            precompress_threshold: 1024,
+            strict_unused: false,
+            deny_warnings: false,
+            keep_going: false,
+            scratch: Scratch::KeepOnFailure,
+            passthrough: Passthrough::Copy,
+            livereload: Livereload::WebSocket,
+            collisions: Collisions::Warn,
+            seed: 0,
+            languages: vec![],
+            watchdog: None,
+            ignore_hidden: false,
+        }
+    }
+}
+
+/// Configuration is resolved in order of precedence:
+///
+/// 1. command-line options
+/// 2. environment variables, e.g. `DIECAST_OUTPUT`
+/// 3. `Diecast.toml`, with the selected profile applied
+/// 4. defaults
+///
+/// The environment variables are `DIECAST_INPUT`, `DIECAST_OUTPUT`,
+/// `DIECAST_CACHE`, `DIECAST_DATA`, `DIECAST_MANIFEST`, `DIECAST_BASE_URL`,
+/// `DIECAST_TIMEZONE`, `DIECAST_LANGUAGES`, `DIECAST_IGNORE`,
+/// `DIECAST_PASSTHROUGH`, `DIECAST_LIVERELOAD`, `DIECAST_COLLISIONS`,
+/// `DIECAST_MAX_DEPTH`, `DIECAST_TOMBSTONES`, `DIECAST_OUTPUT_MANIFEST`,
+/// `DIECAST_DETERMINISTIC`, `DIECAST_FOLLOW_LINKS`, `DIECAST_INCLUDE_HIDDEN`,
+/// `DIECAST_GZIP`, `DIECAST_BROTLI`, `DIECAST_THREADS`,
+/// `DIECAST_PRECOMPRESS_THRESHOLD`, `DIECAST_SEED`, and `DIECAST_WATCHDOG`.
+/// `DIECAST_ENV` selects a profile.
 impl Configuration {
-    pub fn new() -> Configuration {
-        // if there's no file just set an empty toml table
-        // otherwise forcibly attempt to read the contents and parsing them
-        // if either of those two fails the program should and will panic
-        let toml =
-            File::open("Diecast.toml")
-            .map(|mut file| {
+    /// Load the configuration from `Diecast.toml` and the environment.
+    ///
+    /// `Diecast.toml` is looked for in the project root; see `find_root`.
+    ///
+    /// Every problem that's found is collected, rather than stopping
+    /// at the first, so that they can all be fixed at once.
+    pub fn new() -> Result<Configuration, Invalid> {
+        let mut errors = vec![];
+
+        let root = match Configuration::find_root() {
+            Ok(root) => root,
+            Err(e) => {
+                return Err(Invalid {
+                    errors: vec![e.to_string()],
+                });
+            },
+        };
+
+        // if there's no file just use an empty table
+        let toml = match File::open(root.join("Diecast.toml")) {
+            Ok(mut file) => {
                 let mut contents = String::new();
-                file.read_to_string(&mut contents).unwrap();
-                let parsed: toml::Value = contents.parse().unwrap();
 
-                parsed.as_table().expect("configuration must be a table!");
+                match file.read_to_string(&mut contents) {
+                    Ok(_) => {
+                        match contents.parse::<toml::Value>() {
+                            Ok(toml::Value::Table(table)) => toml::Value::Table(table),
+                            Ok(_) => {
+                                errors.push(String::from("Diecast.toml must be a table"));
+                                toml::Value::Table(BTreeMap::new())
+                            },
+                            Err(e) => {
+                                errors.push(format!("could not parse Diecast.toml: {}", e));
+                                toml::Value::Table(BTreeMap::new())
+                            },
+                        }
+                    },
+                    Err(e) => {
+                        errors.push(format!("could not read Diecast.toml: {}", e));
+                        toml::Value::Table(BTreeMap::new())
+                    },
+                }
+            },
+            Err(_) => toml::Value::Table(BTreeMap::new()),
+        };
+
+        let mut configuration = Configuration::default();
+        configuration.root = root;
+        configuration.base = toml.clone();
+        configuration.toml = toml;
+
+        configuration.input = configuration.rooted("input");
+        configuration.output = configuration.rooted("output");
+        configuration.cache = configuration.rooted(".cache");
+        configuration.data = configuration.rooted("data");
+
+        errors.extend(configuration.unknown_keys());
+
+        let ignore_file = configuration.root.join(".diecastignore");
+
+        match configuration.load_ignore_file(&ignore_file) {
+            Ok(()) => (),
+            Err(e) => errors.push(format!("could not load .diecastignore: {}", e)),
+        }
+
+        match env::var("DIECAST_ENV") {
+            Ok(ref profile) if !profile.is_empty() => {
+                if let Err(e) = configuration.set_profile(profile) {
+                    errors.push(format!("DIECAST_ENV: {}", e));
+                }
+            },
+            _ => {
+                let base = configuration.base.clone();
 
-                parsed
+                errors.extend(configuration.apply_toml(&base));
+                errors.extend(configuration.apply_env());
+            },
+        }
+
+        if errors.is_empty() {
+            Ok(configuration)
+        } else {
+            Err(Invalid {
+                errors: errors,
             })
-            .unwrap_or(toml::Value::Table(BTreeMap::new()));
-
-        let ignore =
-            toml.get("diecast.ignore")
-            .and_then(toml::Value::as_str)
-            .and_then(|s| {
-                match Regex::new(s) {
-                    Ok(r) => Some(Arc::new(r) as Arc<Pattern + Send + Sync>),
-                    Err(e) => {
-                        panic!("could not parse regex: {}", e);
+        }
+    }
+
+    /// The project's root directory.
+    ///
+    /// This is the closest directory at or above the current directory
+    /// that contains a `Diecast.toml`, or the current directory itself
+    /// if there is none. The paths in `Diecast.toml`, and the default
+    /// paths, are relative to the root.
+    ///
+    /// The command-line interface changes the current directory for
+    /// `-C DIR` before the configuration is loaded.
+    pub fn find_root() -> ::Result<PathBuf> {
+        let start = env::current_dir()?;
+
+        let root =
+            start.ancestors()
+            .find(|directory| directory.join("Diecast.toml").is_file())
+            .map_or_else(|| start.clone(), Path::to_path_buf);
+
+        Ok(root)
+    }
+
+    /// A path from `Diecast.toml`, relative to the root.
+    ///
+    /// Paths stay relative when the root is the current directory.
+    fn rooted(&self, path: &str) -> PathBuf {
+        let path = PathBuf::from(path);
+
+        let is_current =
+            self.root == Path::new(".")
+            || env::current_dir().map_or(false, |current| current == self.root);
+
+        if is_current || path.is_absolute() {
+            path
+        } else {
+            self.root.join(path)
+        }
+    }
+
+    /// Keys in `[diecast]` tables that aren't recognized, e.g. due to typos.
+    fn unknown_keys(&self) -> Vec<String> {
+        let mut tables = vec![(String::from("diecast"), self.base.get("diecast"))];
+
+        if let Some(profiles) = self.base.get("profile").and_then(toml::Value::as_table) {
+            for (name, profile) in profiles {
+                tables.push((format!("profile.{}.diecast", name), profile.get("diecast")));
+            }
+        }
+
+        let mut errors = vec![];
+
+        for (name, table) in tables {
+            match table {
+                Some(&toml::Value::Table(ref table)) => {
+                    for key in table.keys() {
+                        if !KEYS.contains(&&key[..]) {
+                            errors.push(format!("unknown key `{}` in `[{}]`", key, name));
+                        }
+                    }
+                },
+                Some(_) => errors.push(format!("`{}` must be a table", name)),
+                None => (),
+            }
+        }
+
+        errors
+    }
+
+    /// Set the fields that are backed by the `diecast` table, from the
+    /// keys of the given table that are present.
+    ///
+    /// This is applied over the current values, so that a profile only
+    /// overrides what it sets. Returns any values that are invalid.
+    fn apply_toml(&mut self, table: &toml::Value) -> Vec<String> {
+        let mut errors = vec![];
+
+        let get = |key: &str| lookup(table, key);
+
+        {
+            let mut string = |key: &str| -> Option<String> {
+                match get(key) {
+                    Some(&toml::Value::String(ref value)) => Some(value.clone()),
+                    Some(_) => {
+                        errors.push(format!("`{}` must be a string", key));
+                        None
                     },
+                    None => None,
                 }
-            });
+            };
 
-        let input =
-            toml.get("diecast.input")
-            .and_then(toml::Value::as_str)
-            .map_or_else(|| PathBuf::from("input"), PathBuf::from);
+            let input = string("diecast.input");
+            let output = string("diecast.output");
+            let cache = string("diecast.cache");
+            let manifest = string("diecast.manifest");
+            let data = string("diecast.data");
+            let base_url = string("diecast.base_url").or_else(|| string("site.base_url"));
+            let passthrough = string("diecast.passthrough");
+            let livereload = string("diecast.livereload");
+            let collisions = string("diecast.collisions");
+            let timezone = string("diecast.timezone");
+            let date_format = string("diecast.date_format");
 
-        let output =
-            toml.get("diecast.output")
-            .and_then(toml::Value::as_str)
-            .map_or_else(|| PathBuf::from("output"), PathBuf::from);
+            // either a regex or a list of them
+            let ignore = match get("diecast.ignore") {
+                Some(&toml::Value::String(ref ignore)) => Some(vec![ignore.clone()]),
+                Some(&toml::Value::Array(ref values)) => {
+                    let ignore =
+                        values.iter()
+                        .filter_map(|value| value.as_str().map(String::from))
+                        .collect::<Vec<_>>();
 
-        Configuration {
-            toml: toml,
-            // TODO: setting it to error by default seems like a wart
-            input: input,
-            output: output,
-            command: String::new(),
-            threads: num_cpus::get(),
-            is_verbose: false,
-            ignore: ignore,
-            is_preview: false,
-            ignore_hidden: false,
+                    if ignore.len() != values.len() {
+                        errors.push(String::from(
+                            "`diecast.ignore` must be a string or an array of strings"));
+                    }
+
+                    Some(ignore)
+                },
+                Some(_) => {
+                    errors.push(String::from(
+                        "`diecast.ignore` must be a string or an array of strings"));
+                    None
+                },
+                None => None,
+            };
+
+            if let Some(ignore) = ignore {
+                let mut patterns = AnyOf::new();
+
+                for ignore in ignore {
+                    match Regex::new(&ignore) {
+                        Ok(r) => patterns.push(r),
+                        Err(e) => errors.push(format!("`diecast.ignore` is not a valid regex: {}", e)),
+                    }
+                }
+
+                self.ignore =
+                    if patterns.is_empty() { None }
+                    else { Some(Arc::new(patterns) as Arc<Pattern + Send + Sync>) };
+            }
+
+            if let Some(input) = input {
+                self.input = self.rooted(&input);
+            }
+
+            if let Some(output) = output {
+                self.output = self.rooted(&output);
+            }
+
+            if let Some(cache) = cache {
+                self.cache = self.rooted(&cache);
+            }
+
+            if let Some(manifest) = manifest {
+                self.manifest = Some(self.rooted(&manifest));
+            }
+
+            if let Some(data) = data {
+                self.data = self.rooted(&data);
+            }
+
+            if base_url.is_some() {
+                self.base_url = base_url;
+            }
+
+            if let Some(date_format) = date_format {
+                self.date_format = date_format;
+            }
+
+            if let Some(timezone) = timezone {
+                match timezone.parse() {
+                    Ok(timezone) => self.timezone = timezone,
+                    Err(_) => errors.push(format!(
+                        "`diecast.timezone` must be a timezone such as `America/New_York`, not `{}`",
+                        timezone)),
+                }
+            }
+
+            if let Some(passthrough) = passthrough {
+                match Passthrough::parse(&passthrough) {
+                    Some(passthrough) => self.passthrough = passthrough,
+                    None => errors.push(format!(
+                        "`diecast.passthrough` must be `copy`, `hardlink`, `reflink`, or `symlink`, not `{}`",
+                        passthrough)),
+                }
+            }
+
+            if let Some(livereload) = livereload {
+                match Livereload::parse(&livereload) {
+                    Some(livereload) => self.livereload = livereload,
+                    None => errors.push(format!(
+                        "`diecast.livereload` must be `off`, `websocket`, or `sse`, not `{}`",
+                        livereload)),
+                }
+            }
+
+            if let Some(collisions) = collisions {
+                match Collisions::parse(&collisions) {
+                    Some(collisions) => self.collisions = collisions,
+                    None => errors.push(format!(
+                        "`diecast.collisions` must be `fail` or `warn`, not `{}`",
+                        collisions)),
+                }
+            }
+        }
+
+        if let Some(site) = get("site").cloned() {
+            if let Err(e) = site.try_into::<SiteMetadata>() {
+                errors.push(format!("invalid `[site]` table in Diecast.toml: {}", e));
+            }
+        }
+
+        if let Some(rules) = get("rule").cloned() {
+            if let Err(e) = rules.try_into::<Vec<RuleTable>>() {
+                errors.push(format!("invalid `[[rule]]` table in Diecast.toml: {}", e));
+            }
+        }
+
+        let mut flag = |key: &str| -> Option<bool> {
+            match get(key) {
+                Some(&toml::Value::Boolean(value)) => Some(value),
+                Some(_) => {
+                    errors.push(format!("`{}` must be a boolean", key));
+                    None
+                },
+                None => None,
+            }
+        };
+
+        let tombstones = flag("diecast.tombstones");
+        let output_manifest = flag("diecast.output_manifest");
+        let follow_links = flag("diecast.follow_links");
+        let deterministic = flag("diecast.deterministic");
+        let include_hidden = flag("diecast.include_hidden");
+        let gzip = flag("diecast.gzip");
+        let brotli = flag("diecast.brotli");
+
+        let precompress_threshold = match get("diecast.precompress_threshold") {
+            Some(&toml::Value::Integer(threshold)) if threshold >= 0 => Some(threshold as usize),
+            Some(_) => {
+                errors.push(String::from(
+                    "`diecast.precompress_threshold` must be a non-negative integer"));
+                None
+            },
+            None => None,
+        };
+
+        let languages = match get("diecast.languages") {
+            Some(&toml::Value::Array(ref values)) => {
+                let languages =
+                    values.iter()
+                    .filter_map(|value| value.as_str().map(String::from))
+                    .collect::<Vec<_>>();
+
+                if languages.len() != values.len() {
+                    errors.push(String::from("`diecast.languages` must be an array of strings"));
+                }
+
+                Some(languages)
+            },
+            Some(_) => {
+                errors.push(String::from("`diecast.languages` must be an array of strings"));
+                None
+            },
+            None => None,
+        };
+
+        let max_depth = match get("diecast.max_depth") {
+            Some(&toml::Value::Integer(depth)) if depth > 0 => Some(depth as usize),
+            Some(_) => {
+                errors.push(String::from("`diecast.max_depth` must be a positive integer"));
+                None
+            },
+            None => None,
+        };
+
+        let seed = match get("diecast.seed") {
+            Some(&toml::Value::Integer(seed)) if seed >= 0 => Some(seed as u64),
+            Some(_) => {
+                errors.push(String::from("`diecast.seed` must be a non-negative integer"));
+                None
+            },
+            None => None,
+        };
+
+        let watchdog = match get("diecast.watchdog") {
+            Some(&toml::Value::Integer(seconds)) if seconds > 0 => {
+                Some(Duration::from_secs(seconds as u64))
+            },
+            Some(_) => {
+                errors.push(String::from(
+                    "`diecast.watchdog` must be a positive number of seconds"));
+                None
+            },
+            None => None,
+        };
+
+        self.tombstones = tombstones.unwrap_or(self.tombstones);
+        self.output_manifest = output_manifest.unwrap_or(self.output_manifest);
+        self.deterministic = deterministic.unwrap_or(self.deterministic);
+        self.follow_links = follow_links.unwrap_or(self.follow_links);
+        self.include_hidden = include_hidden.unwrap_or(self.include_hidden);
+        self.gzip = gzip.unwrap_or(self.gzip);
+        self.brotli = brotli.unwrap_or(self.brotli);
+        self.precompress_threshold = precompress_threshold.unwrap_or(self.precompress_threshold);
+        self.seed = seed.unwrap_or(self.seed);
+
+        if max_depth.is_some() {
+            self.max_depth = max_depth;
+        }
+
+        if watchdog.is_some() {
+            self.watchdog = watchdog;
+        }
+
+        if let Some(languages) = languages {
+            self.languages = languages;
+        }
+
+        errors
+    }
+
+    /// Set the fields that are overridden by environment variables.
+    ///
+    /// Returns any values that are invalid.
+    fn apply_env(&mut self) -> Vec<String> {
+        fn var(name: &str) -> Option<String> {
+            env::var(name).ok().and_then(|value| {
+                if value.is_empty() { None } else { Some(value) }
+            })
+        }
+
+        let mut errors = vec![];
+
+        if let Some(input) = var("DIECAST_INPUT") {
+            self.input = self.rooted(&input);
+        }
+
+        if let Some(output) = var("DIECAST_OUTPUT") {
+            self.output = self.rooted(&output);
+        }
+
+        if let Some(cache) = var("DIECAST_CACHE") {
+            self.cache = self.rooted(&cache);
+        }
+
+        if let Some(data) = var("DIECAST_DATA") {
+            self.data = self.rooted(&data);
+        }
+
+        if let Some(manifest) = var("DIECAST_MANIFEST") {
+            self.manifest = Some(self.rooted(&manifest));
+        }
+
+        if let Some(base_url) = var("DIECAST_BASE_URL") {
+            self.base_url = Some(base_url);
+        }
+
+        if let Some(timezone) = var("DIECAST_TIMEZONE") {
+            match timezone.parse() {
+                Ok(timezone) => self.timezone = timezone,
+                Err(_) => errors.push(format!(
+                    "DIECAST_TIMEZONE must be a timezone such as `America/New_York`, not `{}`",
+                    timezone)),
+            }
         }
+
+        if let Some(languages) = var("DIECAST_LANGUAGES") {
+            self.languages =
+                languages.split(',')
+                .map(|language| String::from(language.trim()))
+                .filter(|language| !language.is_empty())
+                .collect();
+        }
+
+        if let Some(passthrough) = var("DIECAST_PASSTHROUGH") {
+            match Passthrough::parse(&passthrough) {
+                Some(passthrough) => self.passthrough = passthrough,
+                None => errors.push(format!(
+                    "DIECAST_PASSTHROUGH must be `copy`, `hardlink`, `reflink`, or `symlink`, not `{}`",
+                    passthrough)),
+            }
+        }
+
+        if let Some(livereload) = var("DIECAST_LIVERELOAD") {
+            match Livereload::parse(&livereload) {
+                Some(livereload) => self.livereload = livereload,
+                None => errors.push(format!(
+                    "DIECAST_LIVERELOAD must be `off`, `websocket`, or `sse`, not `{}`",
+                    livereload)),
+            }
+        }
+
+        if let Some(collisions) = var("DIECAST_COLLISIONS") {
+            match Collisions::parse(&collisions) {
+                Some(collisions) => self.collisions = collisions,
+                None => errors.push(format!(
+                    "DIECAST_COLLISIONS must be `fail` or `warn`, not `{}`",
+                    collisions)),
+            }
+        }
+
+        if let Some(depth) = var("DIECAST_MAX_DEPTH") {
+            match depth.parse() {
+                Ok(depth) if depth > 0 => self.max_depth = Some(depth),
+                _ => errors.push(format!(
+                    "DIECAST_MAX_DEPTH must be a positive integer, not `{}`", depth)),
+            }
+        }
+
+        if let Some(ignore) = var("DIECAST_IGNORE") {
+            match Regex::new(&ignore) {
+                Ok(r) => self.ignore = Some(Arc::new(r) as Arc<Pattern + Send + Sync>),
+                Err(e) => errors.push(format!("DIECAST_IGNORE is not a valid regex: {}", e)),
+            }
+        }
+
+        {
+            let mut flag = |name: &str| -> Option<bool> {
+                var(name).and_then(|value| {
+                    match &value.to_lowercase()[..] {
+                        "1" | "true" | "yes" | "on" => Some(true),
+                        "0" | "false" | "no" | "off" => Some(false),
+                        _ => {
+                            errors.push(format!("{} must be a boolean, not `{}`", name, value));
+                            None
+                        },
+                    }
+                })
+            };
+
+            if let Some(tombstones) = flag("DIECAST_TOMBSTONES") {
+                self.tombstones = tombstones;
+            }
+
+            if let Some(output_manifest) = flag("DIECAST_OUTPUT_MANIFEST") {
+                self.output_manifest = output_manifest;
+            }
+
+            if let Some(deterministic) = flag("DIECAST_DETERMINISTIC") {
+                self.deterministic = deterministic;
+            }
+
+            if let Some(follow_links) = flag("DIECAST_FOLLOW_LINKS") {
+                self.follow_links = follow_links;
+            }
+
+            if let Some(include_hidden) = flag("DIECAST_INCLUDE_HIDDEN") {
+                self.include_hidden = include_hidden;
+            }
+
+            if let Some(gzip) = flag("DIECAST_GZIP") {
+                self.gzip = gzip;
+            }
+
+            if let Some(brotli) = flag("DIECAST_BROTLI") {
+                self.brotli = brotli;
+            }
+        }
+
+        let mut number = |name: &str| -> Option<usize> {
+            var(name).and_then(|value| {
+                match value.parse() {
+                    Ok(number) => Some(number),
+                    Err(_) => {
+                        errors.push(format!("{} must be a number, not `{}`", name, value));
+                        None
+                    },
+                }
+            })
+        };
+
+        let threads = number("DIECAST_THREADS");
+        let threshold = number("DIECAST_PRECOMPRESS_THRESHOLD");
+        let seed = number("DIECAST_SEED");
+        let watchdog = number("DIECAST_WATCHDOG");
+
+        match threads {
+            Some(0) => errors.push(String::from("DIECAST_THREADS must be a positive number")),
+            Some(threads) => self.threads = threads,
+            None => (),
+        }
+
+        if let Some(threshold) = threshold {
+            self.precompress_threshold = threshold;
+        }
+
+        if let Some(seed) = seed {
+            self.seed = seed as u64;
+        }
+
+        match watchdog {
+            Some(0) => errors.push(String::from("DIECAST_WATCHDOG must be a positive number of seconds")),
+            Some(seconds) => self.watchdog = Some(Duration::from_secs(seconds as u64)),
+            None => (),
+        }
+
+        errors
+    }
+
+    /// Look up a value in `Diecast.toml` by its dotted key, e.g. `diecast.output`.
+    ///
+    /// This reflects the selected profile, if any.
+    pub fn get(&self, key: &str) -> Option<&toml::Value> {
+        lookup(&self.toml, key)
+    }
+
+    /// The default configuration with the given `Diecast.toml`.
+    #[cfg(test)]
+    pub fn with_toml(toml: &str) -> Configuration {
+        let toml: toml::Value = toml.parse().unwrap();
+
+        let mut configuration = Configuration::default();
+        configuration.base = toml.clone();
+        configuration.toml = toml;
+        configuration
+    }
+
+    /// The `[site]` table of `Diecast.toml`, with the effective `base_url`.
+    ///
+    /// This is empty if there's no such table.
+    pub fn site(&self) -> SiteMetadata {
+        let mut site: SiteMetadata =
+            self.get("site")
+            .cloned()
+            .and_then(|site| site.try_into().ok())
+            .unwrap_or_default();
+
+        site.base_url = self.base_url.clone();
+
+        site
+    }
+
+    /// The rules declared in the `[[rule]]` tables of `Diecast.toml`.
+    pub fn rules(&self) -> Vec<RuleTable> {
+        self.get("rule")
+            .cloned()
+            .and_then(|rules| rules.try_into().ok())
+            .unwrap_or_default()
+    }
+
+    /// Deserialize a section of `Diecast.toml` into a user-defined type.
+    ///
+    /// The name may be dotted to refer to a nested table. This reflects
+    /// the selected profile, if any.
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Blog {
+    ///     title: String,
+    ///     posts_per_page: usize,
+    /// }
+    ///
+    /// let blog: Blog = site.configuration().section("blog")?;
+    /// ```
+    pub fn section<T>(&self, name: &str) -> ::Result<T>
+    where T: DeserializeOwned {
+        let value =
+            self.get(name)
+            .cloned()
+            .ok_or_else(|| format!("there is no `[{}]` section in Diecast.toml", name))?;
+
+        value.try_into()
+            .map_err(|e| From::from(format!("invalid `[{}]` section in Diecast.toml: {}", name, e)))
+    }
+
+    /// Select a profile from `Diecast.toml`.
+    ///
+    /// The keys in the `[profile.<name>]` table override those of the
+    /// base configuration, e.g. `[profile.production.diecast]` overrides
+    /// `[diecast]`. Only the fields that the profile sets are changed,
+    /// so values set in code are kept unless the profile overrides them.
+    pub fn set_profile(&mut self, name: &str) -> ::Result<()> {
+        let overrides =
+            self.base.get("profile")
+            .and_then(|profiles| profiles.get(name))
+            .and_then(toml::Value::as_table)
+            .cloned()
+            .ok_or_else(|| format!("there is no `[profile.{}]` in Diecast.toml", name))?;
+
+        let mut merged = self.base.as_table().cloned().unwrap_or_else(BTreeMap::new);
+        support::merge(&mut merged, overrides.clone(), true);
+
+        self.toml = toml::Value::Table(merged);
+        self.profile = Some(String::from(name));
+
+        // only what the profile sets is overridden, so values set by
+        // the program since the configuration was loaded are kept
+        let mut errors = self.apply_toml(&toml::Value::Table(overrides));
+        errors.extend(self.apply_env());
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Box::new(Invalid {
+                errors: errors,
+            }))
+        }
+    }
+
+    /// Select a profile from `Diecast.toml`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the profile doesn't exist.
+    pub fn profile(mut self, name: &str) -> Configuration {
+        if let Err(e) = self.set_profile(name) {
+            panic!("{}", e);
+        }
+
+        self
     }
 
     pub fn input<P: ?Sized>(mut self, input: P) -> Configuration
@@ -123,6 +1167,132 @@ impl Configuration {
         self
     }
 
+    pub fn cache<P: ?Sized>(mut self, cache: P) -> Configuration
+    where P: Into<PathBuf> {
+        self.cache = cache.into();
+        self
+    }
+
+    pub fn base_url<S>(mut self, base_url: S) -> Configuration
+    where S: Into<String> {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The absolute URL of a site-relative URL, e.g. `/posts/hello/`.
+    ///
+    /// This is the URL itself if there's no `base_url`.
+    pub fn absolute_url(&self, url: &str) -> String {
+        match self.base_url {
+            Some(ref base) => format!("{}{}", base.trim_end_matches('/'), url),
+            None => String::from(url),
+        }
+    }
+
+    /// The path of the base URL without a trailing slash, e.g. `/blog`
+    /// for `https://example.com/blog/`.
+    ///
+    /// This is empty if there's no base URL or it's at the root of its host.
+    pub fn base_path(&self) -> &str {
+        let base = match self.base_url {
+            Some(ref base) => &base[..],
+            None => return "",
+        };
+
+        let rest = base.find("://").map_or(base, |scheme| &base[scheme + 3..]);
+        let path = rest.find('/').map_or("", |start| &rest[start..]);
+
+        path.trim_end_matches('/')
+    }
+
+    /// Use a different file system, e.g. `vfs::Memory` in tests.
+    pub fn vfs<V>(mut self, vfs: V) -> Configuration
+    where V: Vfs + Sync + Send + 'static {
+        self.vfs = Arc::new(vfs);
+        self
+    }
+
+    /// Confine handlers to the input, output, and cache directories.
+    pub fn strict(mut self, is_strict: bool) -> Configuration {
+        self.is_strict = is_strict;
+        self
+    }
+
+    /// A walk of the input directory, as deep and through the links
+    /// that are configured.
+    ///
+    /// This doesn't skip ignored paths; see `is_ignored`.
+    pub fn walk_input(&self) -> WalkDir {
+        let walk = WalkDir::new(&self.input).follow_links(self.follow_links);
+
+        match self.max_depth {
+            Some(depth) => walk.max_depth(depth),
+            None => walk,
+        }
+    }
+
+    /// Use the patterns of an ignore file in the syntax of `.gitignore`.
+    ///
+    /// A missing file is skipped.
+    pub fn load_ignore_file(&mut self, path: &Path) -> ::Result<()> {
+        let mut contents = String::new();
+
+        match File::open(path) {
+            Ok(mut file) => { file.read_to_string(&mut contents)?; },
+            Err(_) => return Ok(()),
+        }
+
+        self.ignore_file = Some(Arc::new(Gitignore::parse(&contents)?));
+
+        Ok(())
+    }
+
+    /// Whether a path relative to the input directory is excluded from
+    /// the input paths, by `include_hidden`, the `ignore` pattern, or
+    /// the `.diecastignore`.
+    pub fn is_ignored(&self, relative: &Path) -> bool {
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        if !self.include_hidden {
+            let hidden = relative.components().any(|component| {
+                component.as_os_str().to_str().map_or(false, |name| name.starts_with('.'))
+            });
+
+            if hidden {
+                return true;
+            }
+        }
+
+        if let Some(ref ignore_file) = self.ignore_file {
+            let is_dir = self.input.join(relative).is_dir();
+
+            if ignore_file.matches_path(relative, is_dir) {
+                return true;
+            }
+        }
+
+        self.ignore.as_ref().map_or(false, |ignore| ignore.matches(relative))
+    }
+
+    /// The file system as seen by handlers.
+    pub fn sandbox(&self) -> Sandbox {
+        Sandbox::new(self)
+    }
+
+    pub fn data<P: ?Sized>(mut self, data: P) -> Configuration
+    where P: Into<PathBuf> {
+        self.data = data.into();
+        self
+    }
+
+    pub fn manifest<P: ?Sized>(mut self, manifest: P) -> Configuration
+    where P: Into<PathBuf> {
+        self.manifest = Some(manifest.into());
+        self
+    }
+
     pub fn toml(&self) -> &toml::Value {
         &self.toml
     }
@@ -146,14 +1316,236 @@ impl Configuration {
         self
     }
 
+    pub fn follow_links(mut self, follow_links: bool) -> Configuration {
+        self.follow_links = follow_links;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Configuration {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn include_hidden(mut self, include_hidden: bool) -> Configuration {
+        self.include_hidden = include_hidden;
+        self
+    }
+
     pub fn ignore_hidden(mut self, ignore_hidden: bool) -> Configuration {
         self.ignore_hidden = ignore_hidden;
         self
     }
 
+    /// Enter preview mode.
+    ///
+    /// Unless a profile was already selected, this selects
+    /// the `development` profile if there is one, so that
+    /// e.g. a production base URL isn't used while previewing.
     pub fn preview(mut self, is_preview: bool) -> Configuration {
         self.is_preview = is_preview;
+
+        if is_preview && self.profile.is_none() {
+            let _ = self.set_profile("development");
+        }
+
+        self
+    }
+
+    /// Build the site as it would be on the given date.
+    pub fn as_of(mut self, date: DateTime) -> Configuration {
+        self.as_of = Some(date);
+        self
+    }
+
+    /// The time that publication is judged by.
+    ///
+    /// This is `as_of` if it's set, otherwise the current time.
+    pub fn now(&self) -> DateTime {
+        self.as_of.unwrap_or_else(|| dates::fixed(Utc::now()))
+    }
+
+    pub fn timezone(mut self, timezone: Tz) -> Configuration {
+        self.timezone = timezone;
+        self
+    }
+
+    /// The default format of dates, in the style of `strftime`.
+    pub fn date_format<S>(mut self, format: S) -> Configuration
+    where S: Into<String> {
+        self.date_format = format.into();
+        self
+    }
+
+    /// Write tombstone pages for the pages that were removed since the last build.
+    pub fn tombstones(mut self, tombstones: bool) -> Configuration {
+        self.tombstones = tombstones;
+        self
+    }
+
+    /// Write the build's manifest into the output directory, as
+    /// `.diecast-manifest.json`.
+    pub fn output_manifest(mut self, output_manifest: bool) -> Configuration {
+        self.output_manifest = output_manifest;
+        self
+    }
+
+    /// Make the order of items and of the manifest independent of the
+    /// file system and of the order that jobs finish in.
+    pub fn deterministic(mut self, deterministic: bool) -> Configuration {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Write `.gz` siblings of text outputs; requires the `precompress` feature.
+    pub fn gzip(mut self, gzip: bool) -> Configuration {
+        self.gzip = gzip;
+        self
+    }
+
+    /// Write `.br` siblings of text outputs; requires the `precompress` feature.
+    pub fn brotli(mut self, brotli: bool) -> Configuration {
+        self.brotli = brotli;
+        self
+    }
+
+    pub fn precompress_threshold(mut self, threshold: usize) -> Configuration {
+        self.precompress_threshold = threshold;
         self
     }
+
+    pub fn strict_unused(mut self, strict_unused: bool) -> Configuration {
+        self.strict_unused = strict_unused;
+        self
+    }
+
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Configuration {
+        self.deny_warnings = deny_warnings;
+        self
+    }
+
+    pub fn keep_going(mut self, keep_going: bool) -> Configuration {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// The codes of the site's languages, the default first.
+    pub fn languages<L, S>(mut self, languages: L) -> Configuration
+    where L: IntoIterator<Item = S>, S: Into<String> {
+        self.languages = languages.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Configuration {
+        self.seed = seed;
+        self
+    }
+
+    /// Report handlers that run for longer than the threshold.
+    pub fn watchdog(mut self, threshold: Duration) -> Configuration {
+        self.watchdog = Some(threshold);
+        self
+    }
+
+    pub fn passthrough(mut self, passthrough: Passthrough) -> Configuration {
+        self.passthrough = passthrough;
+        self
+    }
+
+    pub fn livereload(mut self, livereload: Livereload) -> Configuration {
+        self.livereload = livereload;
+        self
+    }
+
+    pub fn collisions(mut self, collisions: Collisions) -> Configuration {
+        self.collisions = collisions;
+        self
+    }
+
+    /// How previewed pages are told to reload in this build.
+    ///
+    /// This is always `Off` unless previewing, and in the `production`
+    /// profile, so that preview-only code can't be deployed.
+    pub fn livereload_injected(&self) -> Livereload {
+        let is_production = self.profile.as_ref().map_or(false, |profile| profile == "production");
+
+        if self.is_preview && !is_production {
+            self.livereload
+        } else {
+            Livereload::Off
+        }
+    }
+
+    pub fn reporter<R>(mut self, reporter: R) -> Configuration
+    where R: Reporter + Sync + Send + 'static {
+        self.reporter = Arc::new(reporter);
+        self
+    }
+
+    pub fn scratch(mut self, scratch: Scratch) -> Configuration {
+        self.scratch = scratch;
+        self
+    }
+
+    /// The directory under the cache that holds the rules' scratch directories.
+    pub fn scratch_root(&self) -> PathBuf {
+        self.cache.join("scratch")
+    }
 }
 
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use toml;
+
+    use super::Configuration;
+
+    #[test]
+    fn overlay_profiles() {
+        let toml: toml::Value =
+            "[diecast]\n\
+             output = \"output\"\n\
+             [profile.production.diecast]\n\
+             base_url = \"https://example.com\"\n".parse().unwrap();
+
+        let mut configuration = Configuration::default();
+        configuration.base = toml.clone();
+        configuration.toml = toml;
+        configuration.input = Path::new("content").to_path_buf();
+
+        configuration.set_profile("production").unwrap();
+
+        assert_eq!(configuration.input, Path::new("content"));
+        assert_eq!(configuration.base_url.as_ref().map(String::as_str), Some("https://example.com"));
+        assert_eq!(configuration.get("diecast.output").and_then(|v| v.as_str()), Some("output"));
+    }
+
+    #[test]
+    fn parse_rules() {
+        let configuration = Configuration::with_toml(
+            "[[rule]]\n\
+             name = \"statics\"\n\
+             pattern = \"images/**/*\"\n\
+             handler = \"copy\"\n\
+             quality = 80\n");
+
+        let rules = configuration.rules();
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].name, "statics");
+        assert_eq!(rules[0].pattern, "images/**/*");
+        assert!(rules[0].dependencies.is_empty());
+        assert_eq!(rules[0].handler, "copy");
+        assert_eq!(rules[0].options.get("quality").and_then(|v| v.as_integer()), Some(80));
+    }
+
+    #[test]
+    fn base_path() {
+        assert_eq!(Configuration::default().base_path(), "");
+        assert_eq!(Configuration::default().base_url("https://example.com").base_path(), "");
+        assert_eq!(Configuration::default().base_url("https://example.com/").base_path(), "");
+        assert_eq!(Configuration::default().base_url("https://example.com/blog").base_path(), "/blog");
+        assert_eq!(Configuration::default().base_url("https://example.com/blog/").base_path(), "/blog");
+        assert_eq!(Configuration::default().base_url("/docs/v1/").base_path(), "/docs/v1");
+    }
+}
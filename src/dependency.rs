@@ -92,7 +92,6 @@ where T: Ord + Clone + Hash {
     }
 
     /// Topological ordering from a specific set of source nodes.
-    #[allow(dead_code)]
     pub fn resolve(&self, nodes: Vec<T>) -> Result<Order<T>, CycleError<T>>
     where T: fmt::Debug + fmt::Display + Any {
         Topological::new(self).from(nodes)
@@ -217,7 +216,6 @@ where T: Ord + Clone + Hash {
     }
 
     /// ordering from select nodes
-    #[allow(dead_code)]
     pub fn from(mut self, nodes: Vec<T>) -> Result<Order<T>, CycleError<T>>
     where T: fmt::Display + fmt::Debug + Any {
         let mut order = VecDeque::new();
@@ -0,0 +1,114 @@
+//! Warnings about the site's content.
+//!
+//! Handlers report problems that don't need to stop the build, such as
+//! an image without alt text or a post without a title, with `warn`.
+//! The warnings of a build are collected per item and reported together
+//! once it finishes. With `Configuration::deny_warnings`, or the
+//! `--deny-warnings` option of `build`, the build fails if there were any.
+//!
+//! ```ignore
+//! fn check_title(item: &mut Item) -> diecast::Result<()> {
+//!     if item.title().is_none() {
+//!         diag::warn(item, "missing a title");
+//!     }
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use typemap;
+
+use bind;
+use item::Item;
+use job::Event;
+
+/// A warning about an item, or about a rule's bind as a whole.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub rule: String,
+    pub item: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.item {
+            Some(ref item) => write!(f, "{}: {}: {}", self.rule, item, self.message),
+            None => write!(f, "{}: {}", self.rule, self.message),
+        }
+    }
+}
+
+/// The warnings of a build.
+#[derive(Default)]
+pub struct Diagnostics {
+    warnings: Mutex<Vec<Diagnostic>>,
+}
+
+/// The warnings of the current build, as a bind extension.
+impl typemap::Key for Diagnostics {
+    type Value = Arc<Diagnostics>;
+}
+
+impl Diagnostics {
+    pub fn new() -> Arc<Diagnostics> {
+        Arc::new(Diagnostics::default())
+    }
+
+    pub fn push(&self, diagnostic: Diagnostic) {
+        self.warnings.lock().unwrap().push(diagnostic);
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The warnings, grouped by rule and item.
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        let mut warnings = self.warnings.lock().unwrap().clone();
+
+        // stable, so each item's warnings stay in the order they were made
+        warnings.sort_by(|a, b| (&a.rule, &a.item).cmp(&(&b.rule, &b.item)));
+        warnings
+    }
+}
+
+fn push(bind: &bind::Data, diagnostic: Diagnostic) {
+    match bind.extension::<Diagnostics>() {
+        Some(diagnostics) => diagnostics.push(diagnostic),
+        // outside of a build, e.g. in a test
+        None => {
+            let message = diagnostic.to_string();
+            bind.configuration.reporter.report(&Event::Warning { message: &message });
+        },
+    }
+}
+
+/// Warn about an item.
+pub fn warn<M>(item: &Item, message: M)
+where M: Into<String> {
+    let bind = item.bind();
+
+    push(bind, Diagnostic {
+        rule: bind.name.clone(),
+        item: Some(format!("{:?}", item)),
+        message: message.into(),
+    });
+}
+
+/// Warn about a rule's bind as a whole.
+pub fn warn_bind<M>(bind: &bind::Data, message: M)
+where M: Into<String> {
+    push(bind, Diagnostic {
+        rule: bind.name.clone(),
+        item: None,
+        message: message.into(),
+    });
+}
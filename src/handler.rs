@@ -1,5 +1,8 @@
+use std::pin::Pin;
 use std::sync::Arc;
 
+use futures::Future;
+
 // pub type Result = ::Result<()>;
 
 pub trait Handle<T> {
@@ -32,3 +35,22 @@ where F: Fn(&mut T) -> ::Result<()> {
     }
 }
 
+/// The future of an asynchronous handler, which gives the target back
+/// along with the result.
+pub type Handled<T> = Pin<Box<Future<Output = (T, ::Result<()>)> + Send>>;
+
+/// A handler that waits, e.g. on the network or another process,
+/// without holding a thread while it does.
+///
+/// It takes the target and gives it back when it's done, so that the
+/// future doesn't borrow it. See `util::handle::bind::each_async`.
+pub trait HandleAsync<T> {
+    fn handle(&self, target: T) -> Handled<T>;
+}
+
+impl<T, F> HandleAsync<T> for F
+where F: Fn(T) -> Handled<T> {
+    fn handle(&self, target: T) -> Handled<T> {
+        self(target)
+    }
+}
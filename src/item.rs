@@ -1,12 +1,20 @@
 //! Compilation unit for the `Generator`.
 
+use std::collections::BTreeMap;
 use std::fmt::{self, Debug};
 use std::sync::Arc;
 use std::path::{PathBuf, Path};
+use std::time::Duration;
 
-use typemap::{CloneAny, TypeMap};
+use serde::Serialize;
+use serde_json::{self, Map, Value};
+use typemap::{self, CloneAny, TypeMap};
+use toml;
 
 use bind;
+use support;
+use util::dates::{self, DateTime};
+use util::versions::Versions;
 
 /// The route of an `Item`.
 #[derive(Clone)]
@@ -91,6 +99,21 @@ impl Route {
             },
         };
     }
+
+    /// Apply a router to the path being written to.
+    ///
+    /// Unlike `route_with`, this transforms the current write path
+    /// rather than the read path. A `Read` is left as it is.
+    pub fn retarget_with<R>(&mut self, router: R)
+    where R: Fn(&Path) -> PathBuf {
+        match *self {
+            Route::Read(_) => (),
+            Route::Write(ref mut to) | Route::ReadWrite(_, ref mut to) => {
+                let target = router(to);
+                *to = target;
+            },
+        }
+    }
 }
 
 impl Debug for Route {
@@ -107,16 +130,139 @@ impl Debug for Route {
     }
 }
 
+/// The metadata parsed from an item's front matter.
+pub struct Metadata;
+
+impl typemap::Key for Metadata {
+    type Value = toml::Value;
+}
+
+/// The metadata that a rule gives each of its items, as a bind extension.
+///
+/// See `rule::Builder::default_metadata`.
+pub struct DefaultMetadata;
+
+impl typemap::Key for DefaultMetadata {
+    type Value = Arc<toml::value::Table>;
+}
+
+/// The date associated with an item.
+pub struct Date;
+
+impl typemap::Key for Date {
+    type Value = DateTime;
+}
+
+/// The name that an item's URL is derived from, e.g. `hello-world`.
+pub struct Slug;
+
+impl typemap::Key for Slug {
+    type Value = String;
+}
+
+/// How long the handlers of `bind::each` took with an item, in total.
+pub struct Elapsed;
+
+impl typemap::Key for Elapsed {
+    type Value = Duration;
+}
+
+/// The contents of an item, which are text unless they were read from
+/// a file that isn't valid UTF-8, such as an image or a font.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Content {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Content {
+    /// Text if the bytes are valid UTF-8, binary otherwise.
+    pub fn from_bytes(bytes: Vec<u8>) -> Content {
+        match String::from_utf8(bytes) {
+            Ok(text) => Content::Text(text),
+            Err(e) => Content::Binary(e.into_bytes()),
+        }
+    }
+
+    /// The text, if the contents aren't binary.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Content::Text(ref text) => Some(text),
+            Content::Binary(_) => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match *self {
+            Content::Text(ref text) => text.as_bytes(),
+            Content::Binary(ref bytes) => bytes,
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Content::Text(text) => text.into_bytes(),
+            Content::Binary(bytes) => bytes,
+        }
+    }
+
+    pub fn is_binary(&self) -> bool {
+        match *self {
+            Content::Text(_) => false,
+            Content::Binary(_) => true,
+        }
+    }
+
+    /// The size in bytes.
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for Content {
+    fn default() -> Content {
+        Content::Text(String::new())
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Content {
+        Content::Text(text)
+    }
+}
+
+impl<'a> From<&'a str> for Content {
+    fn from(text: &'a str) -> Content {
+        Content::Text(String::from(text))
+    }
+}
+
+impl From<Vec<u8>> for Content {
+    fn from(bytes: Vec<u8>) -> Content {
+        Content::Binary(bytes)
+    }
+}
+
 /// Represents a file to be processed.
 
 #[derive(Clone)]
 pub struct Item {
     /// The data that was read or that is to be written
-    pub body: String,
+    pub body: Content,
 
     /// Arbitrary additional data
     pub extensions: TypeMap<CloneAny + Sync + Send>,
 
+    /// Additional data keyed by name, which templates and exports see
+    ///
+    /// Unlike `extensions`, which holds typed data for other handlers,
+    /// this holds plain values, e.g. a reading time computed by a handler.
+    pub meta: Map<String, Value>,
+
     bind: Option<Arc<bind::Data>>,
 
     route: Route,
@@ -131,8 +277,9 @@ impl Item {
             bind: None,
             route: route,
 
-            body: String::new(),
+            body: Content::default(),
             extensions: TypeMap::custom(),
+            meta: Map::new(),
         }
     }
 
@@ -166,6 +313,12 @@ impl Item {
         self.route.route_with(router)
     }
 
+    /// Re-route the item's target with the given router.
+    pub fn retarget_with<R>(&mut self, router: R)
+    where R: Fn(&Path) -> PathBuf {
+        self.route.retarget_with(router)
+    }
+
     /// The path to the underlying file being read.
     pub fn source(&self) -> Option<PathBuf> {
         self.route.reading().map(|from| {
@@ -184,6 +337,39 @@ impl Item {
         })
     }
 
+    /// The path of the item's output relative to the site's output.
+    ///
+    /// This is the route's writing path, within the subdirectory of
+    /// the rule if it writes into one.
+    pub fn output_path(&self) -> Option<PathBuf> {
+        self.route.writing().map(|to| {
+            match self.bind.as_ref().and_then(|b| b.configuration.subdirectory.as_ref()) {
+                Some(subdirectory) => subdirectory.join(to),
+                None => to.to_path_buf(),
+            }
+        })
+    }
+
+    /// The site-relative URL of the item's output, e.g. `/posts/hello/`.
+    ///
+    /// `index.html` files are represented by their directory.
+    pub fn url(&self) -> Option<String> {
+        self.output_path().map(|path| support::url(&path))
+    }
+
+    /// The canonical absolute URL of the item's output.
+    ///
+    /// This is relative to `Configuration::base_url`, or the same as
+    /// `url` if there is no base URL or the item isn't attached to a bind.
+    pub fn permalink(&self) -> Option<String> {
+        self.url().map(|url| {
+            match self.bind {
+                Some(ref bind) => bind.configuration.absolute_url(&url),
+                None => url,
+            }
+        })
+    }
+
     /// Access the bind's data
     ///
     /// # Panics
@@ -192,6 +378,52 @@ impl Item {
     pub fn bind(&self) -> &bind::Data {
         self.bind.as_ref().unwrap()
     }
+
+    /// Set a value in `meta`, replacing any previous value.
+    pub fn set_meta<K, V: ?Sized>(&mut self, key: K, value: &V) -> ::Result<()>
+    where K: Into<String>, V: Serialize {
+        self.meta.insert(key.into(), serde_json::to_value(value)?);
+        Ok(())
+    }
+
+    /// The name of the source file without its extension.
+    fn stem(&self) -> Option<String> {
+        self.route.reading()
+            .and_then(Path::file_stem)
+            .map(|stem| stem.to_string_lossy().into_owned())
+    }
+
+    /// The default metadata of the rule the item belongs to, if any.
+    pub fn default_metadata(&self) -> Option<Arc<toml::value::Table>> {
+        self.bind.as_ref()
+            .and_then(|bind| bind.with_ext(|extensions| extensions.get::<DefaultMetadata>().cloned()))
+    }
+
+    /// Save a copy of the body under a name, e.g. the Markdown as it was
+    /// read before it's rendered.
+    ///
+    /// This replaces an earlier copy of the same name. See `util::versions`.
+    pub fn snapshot<N>(&mut self, name: N)
+    where N: Into<String> {
+        let body = self.body.clone();
+
+        self.extensions.entry::<Versions>()
+            .or_insert_with(BTreeMap::new)
+            .insert(name.into(), body);
+    }
+
+    /// The copy of the body saved under a name, if any.
+    pub fn version(&self, name: &str) -> Option<&Content> {
+        self.extensions.get::<Versions>().and_then(|versions| versions.get(name))
+    }
+
+    /// The body as text, for handlers that only make sense on text.
+    ///
+    /// This fails if the body is binary.
+    pub fn text(&self) -> ::Result<&str> {
+        self.body.as_str()
+            .ok_or_else(|| From::from(format!("{} has a binary body, but text was expected", self)))
+    }
 }
 
 impl fmt::Display for Item {
@@ -206,3 +438,71 @@ impl Debug for Item {
     }
 }
 
+/// Common metadata of an item, with fallbacks for when it isn't given.
+///
+/// This is in the `prelude`.
+pub trait ItemExt {
+    /// The `title` in the metadata, or the name of the source file
+    /// without its extension.
+    fn title(&self) -> Option<String>;
+
+    /// The parsed `Date`, or the `date` in the metadata.
+    fn date(&self) -> Option<DateTime>;
+
+    /// The `Slug`, the `slug` in the metadata, or the slugified name of
+    /// the source file without its extension.
+    fn slug(&self) -> Option<String>;
+
+    /// The `tags` in the metadata, as an array or a comma-separated string.
+    fn tags(&self) -> Vec<String>;
+}
+
+impl ItemExt for Item {
+    fn title(&self) -> Option<String> {
+        self.extensions.get::<Metadata>()
+            .and_then(|m| m.get("title"))
+            .and_then(toml::Value::as_str)
+            .map(String::from)
+            .or_else(|| self.stem())
+    }
+
+    fn date(&self) -> Option<DateTime> {
+        if let Some(date) = self.extensions.get::<Date>() {
+            return Some(*date);
+        }
+
+        match self.bind {
+            Some(_) => dates::metadata(self, "date").and_then(|date| date.ok()),
+            None => None,
+        }
+    }
+
+    fn slug(&self) -> Option<String> {
+        self.extensions.get::<Slug>().cloned()
+            .or_else(|| {
+                self.extensions.get::<Metadata>()
+                    .and_then(|m| m.get("slug"))
+                    .and_then(toml::Value::as_str)
+                    .map(String::from)
+            })
+            .or_else(|| self.stem().map(|stem| support::slugify(&stem)))
+    }
+
+    fn tags(&self) -> Vec<String> {
+        let tags = self.extensions.get::<Metadata>().and_then(|m| m.get("tags"));
+
+        match tags {
+            Some(&toml::Value::Array(ref tags)) => {
+                tags.iter().filter_map(toml::Value::as_str).map(String::from).collect()
+            },
+            Some(&toml::Value::String(ref tags)) => {
+                tags.split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect()
+            },
+            _ => vec![],
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Running the jobs that the scheduler finds ready.
+//!
+//! The scheduler decides when a job can run, and its `Evaluator`
+//! decides where: on a pool of threads by default, or on the
+//! scheduler's own thread, one job at a time, which is predictable
+//! enough for tests and debugging.
+
+use std::pin::Pin;
+
+use futures::{future, Future};
+use futures::executor::ThreadPool;
+use futures::task::SpawnExt;
+
+use bind::Bind;
+use super::Job;
+
+/// A job that was started, yielding the name of its bind alongside the
+/// error if it fails.
+pub type Pending = Pin<Box<Future<Output = Result<Bind, (String, ::Error)>> + Send>>;
+
+pub trait Evaluator {
+    /// Start processing a job.
+    fn evaluate(&self, job: Job) -> ::Result<Pending>;
+}
+
+fn process(job: Job) -> Result<Bind, (String, ::Error)> {
+    let name = job.bind.name.clone();
+
+    job.process().map_err(|e| (name, e))
+}
+
+/// Runs jobs on a pool of threads.
+pub struct Pool {
+    pool: ThreadPool,
+}
+
+impl Pool {
+    pub fn new(threads: usize) -> ::Result<Pool> {
+        let pool =
+            ThreadPool::builder()
+            .pool_size(threads)
+            .name_prefix("job-")
+            .create()?;
+
+        Ok(Pool { pool: pool })
+    }
+}
+
+impl Evaluator for Pool {
+    fn evaluate(&self, job: Job) -> ::Result<Pending> {
+        let spawned = self.pool.spawn_with_handle(future::lazy(move |_| process(job)))?;
+
+        Ok(Box::pin(spawned))
+    }
+}
+
+/// Runs each job as soon as it's ready, on the scheduler's thread.
+pub struct Inline;
+
+impl Evaluator for Inline {
+    fn evaluate(&self, job: Job) -> ::Result<Pending> {
+        Ok(Box::pin(future::ready(process(job))))
+    }
+}
@@ -1,21 +1,78 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::fmt;
+use std::time::{Duration, Instant};
 
-use time::PreciseTime;
+use futures::executor::ThreadPool;
+use typemap;
 
 use bind::{self, Bind};
 use handler::Handle;
+use util::handle::item::Unchanged;
+
+use self::watchdog::Watchdog;
 
 mod scheduler;
+pub mod evaluator;
+pub mod reporter;
+pub mod watchdog;
 
 pub use self::scheduler::Scheduler;
+pub use self::reporter::{Event, Reporter, BuildEvent};
+pub use self::evaluator::Evaluator;
 
 pub static STARTING: &'static str = "  Starting";
 pub static FINISHED: &'static str = "  Finished";
+pub static WARNING: &'static str = "   Warning";
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// The threads that the items of a bind are handled on, e.g. by `each`.
+///
+/// These are separate from the threads that run the jobs, so that
+/// handlers that wait on IO don't keep other jobs from starting.
+pub struct ItemPool;
+
+impl typemap::Key for ItemPool {
+    type Value = ThreadPool;
+}
+
+/// How long the handler of a bind's job took.
+pub struct Elapsed;
+
+impl typemap::Key for Elapsed {
+    type Value = Duration;
+}
+
+/// Stop the build in progress from starting any more jobs.
+///
+/// The jobs that are running are finished, then the build fails and
+/// the previous output stays live. The commands call this on Ctrl-C.
+pub fn cancel() {
+    CANCELLED.store(true, Ordering::SeqCst);
+}
+
+/// Whether the build in progress was cancelled.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Clears the cancellation when a build ends, however it ends, so that
+/// the next build in the process runs.
+struct Cancellation;
+
+impl Drop for Cancellation {
+    fn drop(&mut self) {
+        CANCELLED.store(false, Ordering::SeqCst);
+    }
+}
 
 pub struct Job {
     pub handler: Arc<Handle<Bind> + Sync + Send>,
     pub bind: bind::Data,
+
+    /// Whether a failure yields an empty bind instead of an error
+    pub allow_failure: bool,
 }
 
 impl fmt::Debug for Job {
@@ -32,38 +89,61 @@ impl Job {
         Job {
             handler: handler,
             bind: bind,
+            allow_failure: false,
         }
     }
 
     pub fn process(self) -> ::Result<Bind> {
-        use ansi_term::Colour::Green;
-        use ansi_term::Style;
+        let reporter = self.bind.configuration.reporter.clone();
 
         let mut bind = Bind::new(self.bind);
 
-        println!("{} {}",
-            Green.bold().paint(STARTING),
-            bind);
+        reporter.report(&Event::Started { rule: &bind.name });
 
-        let start = PreciseTime::now();
+        // report the bind if its handler takes too long
+        let guard =
+            bind.with_ext(|extensions| extensions.get::<Watchdog>().cloned())
+            .map(|watchdog| Watchdog::watch(&watchdog, bind.name.clone(), None, None));
+
+        let start = Instant::now();
         let res = self.handler.handle(&mut bind);
-        let end = PreciseTime::now();
+        let duration = start.elapsed();
+
+        drop(guard);
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Elapsed>(duration));
 
-        let duration = start.to(end);
+        let unchanged =
+            bind.with_ext(|extensions| extensions.get::<Unchanged>().cloned())
+            .unwrap_or(0);
 
-        println!("{} {} [{}] {}",
-            Style::default().bold().paint(FINISHED),
-            bind,
-            bind.items().len(),
-            duration);
+        reporter.report(&Event::Finished {
+            rule: &bind.name,
+            items: bind.items().len(),
+            unchanged: unchanged,
+            millis: duration.as_secs() * 1000 + u64::from(duration.subsec_millis()),
+        });
 
         match res {
             Ok(_) => Ok(bind),
-            Err(e) =>
-                Err(From::from(
-                    format!("\nthe following job encountered an error:\n  {:?}\n\n{}\n",
-                            bind.name,
-                            e))),
+            Err(e) => {
+                reporter.report(&Event::Failed {
+                    rule: &bind.name,
+                    error: &e.to_string(),
+                    allowed: self.allow_failure,
+                });
+
+                if self.allow_failure {
+                    bind.fail(e.to_string());
+
+                    Ok(bind)
+                } else {
+                    Err(From::from(
+                        format!("\nthe following job encountered an error:\n  {:?}\n\n{}\n",
+                                bind.name,
+                                e)))
+                }
+            },
         }
     }
 }
@@ -0,0 +1,268 @@
+//! Reporting the progress of a build.
+//!
+//! Jobs and the scheduler describe what they're doing as `Event`s
+//! instead of printing them, and the configured `Reporter` decides how
+//! to show them: as the usual status lines, only warnings, or as one
+//! JSON object per line for CI logs and editor integrations.
+//!
+//! Programs that embed diecast can also receive the events as they
+//! happen, from `Site::subscribe`.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use ansi_term::Colour::{Green, Yellow};
+use ansi_term::Style;
+use serde_json;
+
+use super::{STARTING, FINISHED, WARNING};
+
+/// Something that happened during a build.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// The site started building from its input directory.
+    Building { input: &'a Path },
+
+    /// A rule's job started.
+    Started { rule: &'a str },
+
+    /// A rule's job finished, with the number of items it produced.
+    Finished {
+        rule: &'a str,
+        items: usize,
+        unchanged: usize,
+        millis: u64,
+    },
+
+    /// A rule's job failed.
+    Failed {
+        rule: &'a str,
+        error: &'a str,
+        allowed: bool,
+    },
+
+    /// An item of a rule failed, failing the rule.
+    ItemFailed {
+        rule: &'a str,
+        item: &'a str,
+        error: &'a str,
+    },
+
+    /// A rule wasn't run because a rule it depends on failed.
+    Skipped { rule: &'a str, because: &'a str },
+
+    /// The chain of dependent rules that took the longest, in total.
+    CriticalPath { rules: &'a [String], millis: u64 },
+
+    /// Something that might be a mistake.
+    Warning { message: &'a str },
+
+    /// Anything else worth mentioning, e.g. the pages that were removed.
+    Note { message: &'a str },
+}
+
+pub trait Reporter {
+    fn report(&self, event: &Event);
+}
+
+/// The usual status lines.
+pub struct Human;
+
+impl Reporter for Human {
+    fn report(&self, event: &Event) {
+        match *event {
+            Event::Building { input } => {
+                println!("building from {:?}", input);
+            },
+            Event::Started { rule } => {
+                println!("{} {}", Green.bold().paint(STARTING), rule);
+            },
+            Event::Finished { rule, items, unchanged, millis } => {
+                if unchanged > 0 {
+                    println!("{} {} [{}, {} unchanged] {}ms",
+                        Style::default().bold().paint(FINISHED),
+                        rule, items, unchanged, millis);
+                } else {
+                    println!("{} {} [{}] {}ms",
+                        Style::default().bold().paint(FINISHED),
+                        rule, items, millis);
+                }
+            },
+            Event::Failed { rule, error, allowed: true } => {
+                println!("{} {} failed but is allowed to fail:\n  {}",
+                    Yellow.bold().paint(WARNING), rule, error);
+            },
+            // the error is returned from the build
+            Event::Failed { allowed: false, .. } => (),
+            Event::ItemFailed { item, error, .. } => {
+                println!("\nthe following item encountered an error:\n  {}\n\n{}\n",
+                    item, error);
+            },
+            Event::Skipped { rule, because } => {
+                println!("skipping `{}` because `{}` failed", rule, because);
+            },
+            Event::CriticalPath { rules, millis } => {
+                println!("critical path: {} [{}ms]", rules.join(" -> "), millis);
+            },
+            Event::Warning { message } => {
+                println!("{} {}", Yellow.bold().paint(WARNING), message);
+            },
+            Event::Note { message } => {
+                println!("{}", message);
+            },
+        }
+    }
+}
+
+/// Only warnings and allowed failures.
+pub struct Quiet;
+
+impl Reporter for Quiet {
+    fn report(&self, event: &Event) {
+        match *event {
+            Event::Warning { .. } | Event::Failed { allowed: true, .. } => Human.report(event),
+            _ => (),
+        }
+    }
+}
+
+/// One JSON object per event, per line, with the kind of event as `event`.
+pub struct Json;
+
+impl Reporter for Json {
+    fn report(&self, event: &Event) {
+        if let Ok(line) = serde_json::to_string(event) {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+
+            let _ = writeln!(stdout, "{}", line);
+        }
+    }
+}
+
+/// An event sent to the subscribers of a site.
+///
+/// Unlike `Event`, this owns its contents, so that it can be sent to
+/// another thread.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildEvent {
+    BindStarted { rule: String },
+    BindFinished { rule: String, items: usize, duration: Duration },
+    BindFailed { rule: String, error: String },
+    ItemFailed { rule: String, item: String, error: String },
+}
+
+impl BuildEvent {
+    /// The build event of a reported event, if it has one.
+    pub fn from_event(event: &Event) -> Option<BuildEvent> {
+        match *event {
+            Event::Started { rule } => {
+                Some(BuildEvent::BindStarted { rule: String::from(rule) })
+            },
+            Event::Finished { rule, items, millis, .. } => {
+                Some(BuildEvent::BindFinished {
+                    rule: String::from(rule),
+                    items: items,
+                    duration: Duration::from_millis(millis),
+                })
+            },
+            Event::Failed { rule, error, .. } => {
+                Some(BuildEvent::BindFailed {
+                    rule: String::from(rule),
+                    error: String::from(error),
+                })
+            },
+            Event::ItemFailed { rule, item, error } => {
+                Some(BuildEvent::ItemFailed {
+                    rule: String::from(rule),
+                    item: String::from(item),
+                    error: String::from(error),
+                })
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Sends build events to a channel, and reports every event to another
+/// reporter as well.
+///
+/// Events are no longer sent once the receiver is dropped.
+pub struct Subscriber {
+    sender: Mutex<Option<Sender<BuildEvent>>>,
+    inner: Arc<Reporter + Sync + Send>,
+}
+
+impl Subscriber {
+    pub fn new(sender: Sender<BuildEvent>, inner: Arc<Reporter + Sync + Send>) -> Subscriber {
+        Subscriber {
+            sender: Mutex::new(Some(sender)),
+            inner: inner,
+        }
+    }
+}
+
+impl Reporter for Subscriber {
+    fn report(&self, event: &Event) {
+        self.inner.report(event);
+
+        if let Some(build_event) = BuildEvent::from_event(event) {
+            let mut sender = self.sender.lock().unwrap();
+
+            let disconnected =
+                sender.as_ref().map_or(false, |sender| sender.send(build_event).is_err());
+
+            if disconnected {
+                *sender = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::mpsc;
+
+    use serde_json::{self, Value};
+
+    use super::{Event, BuildEvent, Reporter, Subscriber, Quiet};
+
+    #[test]
+    fn tag_events() {
+        let event = Event::Finished {
+            rule: "posts",
+            items: 3,
+            unchanged: 1,
+            millis: 12,
+        };
+
+        let expected: Value =
+            serde_json::from_str(
+                r#"{"event": "finished", "rule": "posts", "items": 3, "unchanged": 1, "millis": 12}"#)
+            .unwrap();
+
+        assert_eq!(serde_json::to_value(&event).unwrap(), expected);
+    }
+
+    #[test]
+    fn send_build_events() {
+        let (sender, receiver) = mpsc::channel();
+        let subscriber = Subscriber::new(sender, Arc::new(Quiet));
+
+        subscriber.report(&Event::Started { rule: "posts" });
+        subscriber.report(&Event::Note { message: "there is nothing to do" });
+
+        assert_eq!(receiver.try_recv().unwrap(),
+                   BuildEvent::BindStarted { rule: String::from("posts") });
+        assert!(receiver.try_recv().is_err());
+
+        drop(receiver);
+
+        subscriber.report(&Event::Started { rule: "pages" });
+    }
+}
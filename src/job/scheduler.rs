@@ -1,16 +1,24 @@
 use std::sync::Arc;
-use std::path::{PathBuf, Path};
+use std::path::PathBuf;
 use std::collections::{BTreeMap, VecDeque, HashMap};
-use std::mem;
+use std::fs;
+use std::{cmp, mem};
+use std::time::Duration;
 
-use futures::prelude::*;
-use futures::{self, future, Future};
+use futures::{self, future};
+use futures::executor::ThreadPool;
+use serde_json;
 
-use configuration::Configuration;
+use configuration::{Collisions, Configuration, Scratch};
 use dependency::Graph;
+use manifest::Manifest;
+use model::Model;
 use rule::Rule;
+use phase;
 use bind::{self, Bind};
-use super::Job;
+use item;
+use super::{Event, Job, ItemPool, Elapsed, Cancellation};
+use super::evaluator::{Evaluator, Pending, Pool};
 
 pub struct Scheduler {
     configuration: Arc<Configuration>,
@@ -22,20 +30,51 @@ pub struct Scheduler {
     /// Dependency count of each bind
     dependencies: BTreeMap<String, usize>,
 
+    /// The length of the longest chain of dependents of each bind,
+    /// counting itself
+    priorities: HashMap<String, usize>,
+
+    /// How long the job of each finished bind took
+    elapsed: HashMap<String, Duration>,
+
     /// List of jobs that haven't been processed yet
     waiting: Vec<Job>,
 
     /// List of jobs currently being processed
-    pending: Vec<Box<Future<Item = Bind, Error = ::Error>>>,
+    pending: Vec<Pending>,
+
+    /// Runs the jobs, on a pool of `threads` threads if unset
+    evaluator: Option<Arc<Evaluator + Sync + Send>>,
 
     /// Finished dependencies
     finished: BTreeMap<String, Arc<Bind>>,
 
+    /// Projections of the finished binds, keyed by dependent
+    projections: BTreeMap<String, BTreeMap<String, Arc<serde_json::Value>>>,
+
+    /// Files generated by the finished binds
+    manifest: Manifest,
+
+    /// The structure of the finished binds
+    model: Model,
+
+    /// Files written with the same contents as in the previous build
+    unchanged: usize,
+
     // TODO
     // feels weird to have this here, but it's in-line with making
     // matching Patterns first-class
     /// Paths being considered
     paths: Arc<Vec<PathBuf>>,
+
+    /// The site's cross-cutting passes
+    passes: Arc<phase::Handlers>,
+
+    /// The item that was written to each path, as `rule: item`
+    routes: HashMap<PathBuf, String>,
+
+    /// Paths that more than one item was written to
+    collisions: Vec<String>,
 }
 
 impl Scheduler {
@@ -45,13 +84,33 @@ impl Scheduler {
             rules: HashMap::new(),
             graph: Graph::new(),
             dependencies: BTreeMap::new(),
+            priorities: HashMap::new(),
+            elapsed: HashMap::new(),
             waiting: Vec::new(),
             pending: Vec::new(),
+            evaluator: None,
             finished: BTreeMap::new(),
+            projections: BTreeMap::new(),
+            manifest: Manifest::new(),
+            model: Model::new(),
+            unchanged: 0,
             paths: Arc::new(Vec::new()),
+            passes: Arc::new(phase::Handlers::new()),
+            routes: HashMap::new(),
+            collisions: Vec::new(),
         }
     }
 
+    pub fn set_passes(&mut self, passes: Arc<phase::Handlers>) {
+        self.passes = passes;
+    }
+
+    /// Run the jobs with the given evaluator instead of on a pool of threads.
+    pub fn set_evaluator<E>(&mut self, evaluator: E)
+    where E: Evaluator + Sync + Send + 'static {
+        self.evaluator = Some(Arc::new(evaluator));
+    }
+
     // TODO
     // it's probably beneficial to keep this stuff here
     // that way the files are only enumerated once and each handler
@@ -60,21 +119,16 @@ impl Scheduler {
 
     /// Re-enumerate the paths in the input directory
     pub fn update_paths(&mut self) {
-        use walkdir::WalkDir;
+        let configuration = &self.configuration;
 
-        let walked_paths =
-            WalkDir::new(&self.configuration.input)
+        let mut walked_paths: Vec<PathBuf> =
+            configuration.walk_input()
                 .into_iter()
                 .filter_entry(|entry| {
-                    if let Some(ref ignore) = self.configuration.ignore {
-                        let file_name = &Path::new(entry.path().file_name().unwrap());
-
-                        if ignore.matches(file_name) {
-                            return false;
-                        }
-                    }
+                    let relative = entry.path().strip_prefix(&configuration.input)
+                        .unwrap_or_else(|_| entry.path());
 
-                    true
+                    !configuration.is_ignored(relative)
                 })
                 .filter_map(|entry| entry.ok())
                 .filter_map(|entry| {
@@ -87,23 +141,44 @@ impl Scheduler {
                 })
                 .collect();
 
+        // the walk's order depends on the file system
+        if self.configuration.deterministic {
+            walked_paths.sort();
+        }
+
         self.paths = Arc::new(walked_paths);
     }
 
+    /// The paths in the input directory that aren't ignored.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
     pub fn add(&mut self, rule: Arc<Rule>) {
         // prepare bind-data with the name and configuration
-        let data = bind::Data::new(
-            String::from(rule.name()),
-            self.configuration.clone());
+        let configuration =
+            if rule.overlay().is_empty() { self.configuration.clone() }
+            else { Arc::new(rule.overlay().apply(&self.configuration)) };
+
+        let data = bind::Data::new(String::from(rule.name()), configuration);
         let name = data.name.clone();
 
+        if let Some(metadata) = rule.default_metadata() {
+            data.with_ext_mut(|extensions| {
+                extensions.insert::<item::DefaultMetadata>(metadata.clone())
+            });
+        }
+
         // TODO
         // instead of rule_count == 0,
         // check if self.waiting.is_empty()?
 
         // construct job from bind-data, rule kind, rule handler, and paths
         // push it to waiting queue
-        self.waiting.push(Job::new(data, rule.handler()));
+        let mut job = Job::new(data, rule.handler());
+        job.allow_failure = rule.allows_failure();
+
+        self.waiting.push(job);
 
         self.graph.add_node(name.clone());
 
@@ -120,38 +195,81 @@ impl Scheduler {
     // should send the finished bind to a result channel
     // this will enable decoupling of cli status messages
     // from the core library
-    fn satisfy(&mut self, current: Bind) {
+    fn satisfy(&mut self, current: Bind) -> ::Result<()> {
+        use util::handle::item::Unchanged;
+
         let bind_name = current.name.clone();
 
+        if let Some(elapsed) = current.with_ext(|extensions| extensions.get::<Elapsed>().cloned()) {
+            self.elapsed.insert(bind_name.clone(), elapsed);
+        }
+
+        self.manifest.record(&current);
+        self.check_routes(&current);
+        self.model.record(&current, self.rules[&bind_name].dependencies());
+
+        self.unchanged +=
+            current.with_ext(|extensions| extensions.get::<Unchanged>().cloned())
+            .unwrap_or(0);
+
+        // dependents that only need a projection get it now, and if
+        // none need the full bind then it doesn't need to be kept
+        let mut needed = true;
+
+        if let Some(dependents) = self.graph.dependents_of(&bind_name) {
+            needed = false;
+
+            for dependent in dependents {
+                match self.rules[dependent].projections().get(&bind_name) {
+                    Some(projector) => {
+                        self.projections.entry(dependent.clone())
+                            .or_insert_with(BTreeMap::new)
+                            .insert(bind_name.clone(), Arc::new(projector(&current)));
+                    },
+                    None => needed = true,
+                }
+            }
+        }
+
         // if they're done, move from staging to finished
-        self.finished.insert(bind_name.clone(), Arc::new(current));
+        if needed {
+            self.finished.insert(bind_name.clone(), Arc::new(current));
+        }
 
         if let Some(dependents) = self.graph.dependents_of(&bind_name) {
             let names = self.dependencies.keys().cloned().collect::<Vec<String>>();
 
             for name in names {
                 if dependents.contains(&name) {
-                    // FIXME this or_insert is incorrect, since using 0
-                    // and subtracting 1 will cause underflow
-                    if let Some(count) = self.dependencies.get_mut(&name) {
-                        *count -= 1;
-                    } else {
-                        panic!("dependency count for {} is not available!", name);
-                    }
+                    let count = self.dependencies.get_mut(&name).ok_or_else(|| {
+                        format!("dependency count for {} is not available!", name)
+                    })?;
+
+                    *count = count.checked_sub(1).ok_or_else(|| {
+                        format!("`{}` was satisfied more times than `{}` has dependencies",
+                                bind_name, name)
+                    })?;
                 }
             }
         }
+
+        Ok(())
     }
 
     fn ready(&mut self) -> Vec<Job> {
         let waiting = mem::replace(&mut self.waiting, Vec::new());
 
-        let (ready, waiting): (Vec<Job>, Vec<Job>) =
+        let (mut ready, waiting): (Vec<Job>, Vec<Job>) =
             waiting.into_iter()
                .partition(|job| self.dependencies[&job.bind.name] == 0);
 
         self.waiting = waiting;
 
+        // the binds with the longest chains of dependents hold up the most
+        // of the build, so they start first
+        let priorities = &self.priorities;
+        ready.sort_by(|a, b| priorities.get(&b.bind.name).cmp(&priorities.get(&a.bind.name)));
+
         ready
     }
 
@@ -159,6 +277,8 @@ impl Scheduler {
         assert!(self.waiting.len() == order.len(),
                 "`waiting` and `order` are not the same length");
 
+        self.priorities = chain_lengths(&self.graph, &order);
+
         let mut job_map =
             mem::replace(&mut self.waiting, Vec::new())
             .into_iter()
@@ -192,25 +312,58 @@ impl Scheduler {
 
     pub fn build(&mut self) -> ::Result<()> {
         use util::handle::bind::InputPaths;
+        use diag::Diagnostics;
+        use job::watchdog::Watchdog;
+        use util::data::Data;
 
         if self.waiting.is_empty() {
-            println!("there is nothing to do");
+            self.configuration.reporter.report(&Event::Note { message: "there is nothing to do" });
             return Ok(());
         }
 
+        let reporter = self.configuration.reporter.clone();
+        let watchdog = self.configuration.watchdog.map(|threshold| Watchdog::new(threshold, reporter));
+
+        let threads = cmp::max(self.configuration.threads, 1);
+
+        let evaluator: Arc<Evaluator + Sync + Send> = match self.evaluator {
+            Some(ref evaluator) => evaluator.clone(),
+            None => Arc::new(Pool::new(threads)?),
+        };
+
+        // jobs wait on their items, so they run on separate threads to
+        // avoid a job waiting on items that can't start
+        let items = ThreadPool::builder().pool_size(threads).name_prefix("item-").create()?;
+
+        let data = Arc::new(Data::load(&self.configuration)?);
+        let diagnostics = Diagnostics::new();
+
         for job in &mut self.waiting {
-            job.bind.extensions.write().unwrap()
-                .insert::<InputPaths>(self.paths.clone());
+            let paths = self.paths.clone();
+            let passes = self.passes.clone();
+
+            job.bind.with_ext_mut(|extensions| {
+                extensions.insert::<InputPaths>(paths);
+                extensions.insert::<Data>(data.clone());
+                extensions.insert::<phase::Passes>(passes);
+                extensions.insert::<Diagnostics>(diagnostics.clone());
+                extensions.insert::<ItemPool>(items.clone());
+
+                if let Some(ref watchdog) = watchdog {
+                    extensions.insert::<Watchdog>(watchdog.clone());
+                }
+            });
         }
 
+        // stops sampling when the build ends, however it ends
+        let _sampler = watchdog.as_ref().map(Watchdog::start);
+
         // NOTE
         //
-        // using futures_cpupool
+        // * For each ready job, start it with the evaluator and add it to a
+        // vector of pending jobs.
         //
-        // * For each ready job, spawn it on the cpupool and add it to a vector
-        // of pending jobs.
-        //
-        // * In the main loop perform a select_all().wait() on the vector to
+        // * In the main loop block on select_all() on the vector to
         // wait for the first available job. select_all() returns a triple of:
         //
         //   1. resolved value
@@ -220,49 +373,226 @@ impl Scheduler {
         // * When a future is resolve (i.e. job is ready), enqueue all ready
         // other ready jobs
 
+        let _cancellation = Cancellation;
+
+        self.remove_scratch()?;
+
         let order = self.graph.resolve_all()?;
 
-        self.sort_jobs(order);
-        self.schedule_ready();
+        self.sort_jobs(order.clone());
+        self.schedule_ready(&*evaluator)?;
+
+        let mut failures = Vec::new();
 
         while !self.pending.is_empty() {
             let pending = mem::replace(&mut self.pending, Vec::new());
 
-            match futures::executor::block_on(future::select_all(pending)) {
-                Ok((bind, _index, mut new_pending)) => {
-                    mem::swap(&mut new_pending, &mut self.pending);
+            let (result, _index, rest) =
+                futures::executor::block_on(future::select_all(pending));
+
+            self.pending = rest;
 
-                    self.satisfy(bind);
-                    self.schedule_ready();
+            match result {
+                Ok(bind) => {
+                    self.satisfy(bind)?;
+                    self.schedule_ready(&*evaluator)?;
                 }
-                Err((e, _index, _new_pending)) => {
-                    return Err(
-                        From::from(
-                            format!("a job panicked. stopping everything:\n{}", e)));
+                Err((name, e)) => {
+                    if !self.configuration.keep_going {
+                        if self.configuration.scratch == Scratch::Remove {
+                            self.remove_scratch()?;
+                        }
+
+                        return Err(
+                            From::from(
+                                format!("a job panicked. stopping everything:\n{}", e)));
+                    }
+
+                    // nothing that depends on the failed bind can run,
+                    // but independent branches of the graph still can
+                    for skipped in self.skip_dependents_of(&name) {
+                        self.configuration.reporter.report(&Event::Skipped {
+                            rule: &skipped,
+                            because: &name,
+                        });
+                    }
+
+                    failures.push(e);
+                    self.schedule_ready(&*evaluator)?;
                 }
             }
         }
 
+        let cancelled = super::is_cancelled();
+
+        let (path, duration) = critical_path(&self.graph, &order, &self.elapsed);
+
+        if !path.is_empty() {
+            self.configuration.reporter.report(&Event::CriticalPath {
+                rules: &path,
+                millis: duration.as_secs() * 1000 + u64::from(duration.subsec_millis()),
+            });
+        }
+
         // TODO
         // no longer necessary post-partial update purge?
         self.reset();
 
+        match self.configuration.scratch {
+            Scratch::Remove => self.remove_scratch()?,
+            Scratch::KeepOnFailure if failures.is_empty() || cancelled => self.remove_scratch()?,
+            _ => (),
+        }
+
+        if !diagnostics.is_empty() {
+            let warnings = diagnostics.warnings();
+            let summary = format!("{} warning(s):", warnings.len());
+
+            self.configuration.reporter.report(&Event::Note { message: &summary });
+
+            for warning in &warnings {
+                self.configuration.reporter.report(&Event::Warning { message: &warning.to_string() });
+            }
+        }
+
+        // report the failed jobs and the collisions together, so that
+        // neither hides the other
+        let mut problems = vec![];
+
+        if !failures.is_empty() {
+            let mut message =
+                format!("{} job(s) failed:\n", failures.len());
+
+            for failure in &failures {
+                message.push_str(&failure.to_string());
+            }
+
+            problems.push(message);
+        }
+
+        if !self.collisions.is_empty() && self.configuration.collisions == Collisions::Fail {
+            problems.push(format!(
+                "{} path(s) were written by more than one item:\n  {}",
+                self.collisions.len(), self.collisions.join("\n  ")));
+        }
+
+        if !problems.is_empty() {
+            return Err(From::from(problems.join("\n")));
+        }
+
+        if cancelled {
+            return Err(From::from("the build was interrupted"));
+        }
+
+        if self.configuration.deny_warnings && !diagnostics.is_empty() {
+            return Err(From::from(
+                format!("{} warning(s) were denied by --deny-warnings", diagnostics.len())));
+        }
+
         Ok(())
     }
 
+    /// Record the paths that the bind's items are written to, and any
+    /// that were already written to by other items.
+    fn check_routes(&mut self, bind: &Bind) {
+        for item in bind.items() {
+            let target = match item.output_path() {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let writer = format!("{}: {}", bind.name, item);
+
+            if let Some(previous) = self.routes.get(&target) {
+                let collision = format!("{} is written by both {} and {}",
+                                        target.display(), previous, writer);
+
+                if self.configuration.collisions == Collisions::Warn {
+                    self.configuration.reporter.report(&Event::Warning { message: &collision });
+                }
+
+                self.collisions.push(collision);
+            }
+
+            self.routes.insert(target, writer);
+        }
+    }
+
+    /// Remove every waiting job that transitively depends on the given bind.
+    ///
+    /// Returns the names of the jobs that were removed.
+    fn skip_dependents_of(&mut self, name: &str) -> Vec<String> {
+        // the ordering from the failed node reaches every one of its
+        // dependents, direct or otherwise
+        let affected = match self.graph.resolve(vec![String::from(name)]) {
+            Ok(order) => order,
+            Err(_) => return vec![],
+        };
+
+        let waiting = mem::replace(&mut self.waiting, Vec::new());
+
+        let (skipped, waiting): (Vec<Job>, Vec<Job>) =
+            waiting.into_iter()
+               .partition(|job| affected.contains(&job.bind.name));
+
+        self.waiting = waiting;
+
+        skipped.into_iter().map(|job| job.bind.name).collect()
+    }
+
+    /// Remove the rules' scratch directories.
+    fn remove_scratch(&self) -> ::Result<()> {
+        let root = self.configuration.scratch_root();
+
+        if root.exists() {
+            fs::remove_dir_all(&root)?;
+        }
+
+        Ok(())
+    }
+
+    /// The files generated so far.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// The structure of the binds finished so far.
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// The number of files written with the same contents as in the previous build.
+    pub fn unchanged(&self) -> usize {
+        self.unchanged
+    }
+
     // TODO: audit
     fn reset(&mut self) {
         self.graph = Graph::new();
         self.waiting.clear();
+        self.priorities.clear();
+        self.elapsed.clear();
     }
 
-    fn schedule_ready(&mut self) {
+    fn schedule_ready(&mut self, evaluator: &Evaluator) -> ::Result<()> {
+        if super::is_cancelled() {
+            return Ok(());
+        }
+
         for mut job in self.ready() {
             let name = job.bind.name.clone();
 
+            let mut projections =
+                self.projections.remove(&name).unwrap_or_else(BTreeMap::new);
+
             if let Some(deps) = self.graph.dependencies_of(&name) {
                 // insert each dependency
                 for dep in deps {
+                    if let Some(projection) = projections.remove(dep) {
+                        job.bind.projections.insert(dep.clone(), projection);
+                        continue;
+                    }
+
                     // mutation of the bind dependencies is what necessitates
                     // Job using a bind::Data and only building the
                     // actual Bind on-the-fly, instead of only dealing with
@@ -271,8 +601,216 @@ impl Scheduler {
                 }
             }
 
-            let spawned = futures::executor::block_on(futures::executor::spawn_with_handle(future::lazy(move |_| job.process()))).unwrap();
-            self.pending.push(Box::new(spawned));
+            self.pending.push(evaluator.evaluate(job)?);
+        }
+
+        Ok(())
+    }
+}
+
+/// The length of the longest chain of dependents of each node, counting
+/// itself, given the nodes in topological order.
+fn chain_lengths(graph: &Graph<String>, order: &VecDeque<String>) -> HashMap<String, usize> {
+    let mut lengths = HashMap::new();
+
+    for node in order.iter().rev() {
+        let longest =
+            graph.dependents_of(node)
+            .and_then(|dependents| {
+                dependents.iter().filter_map(|dependent| lengths.get(dependent)).max()
+            })
+            .cloned()
+            .unwrap_or(0);
+
+        lengths.insert(node.clone(), longest + 1);
+    }
+
+    lengths
+}
+
+/// The chain of dependent nodes whose elapsed times add up to the most,
+/// and that total, given the nodes in topological order.
+///
+/// Nodes without an elapsed time, e.g. ones that were skipped, aren't
+/// part of any chain.
+fn critical_path(
+    graph: &Graph<String>,
+    order: &VecDeque<String>,
+    elapsed: &HashMap<String, Duration>)
+-> (Vec<String>, Duration) {
+    // the total of the longest chain ending at each node, and the
+    // node before it in that chain
+    let mut totals: HashMap<&str, (Duration, Option<&str>)> = HashMap::new();
+
+    for node in order {
+        let own = match elapsed.get(node) {
+            Some(own) => *own,
+            None => continue,
+        };
+
+        let previous =
+            graph.dependencies_of(node)
+            .and_then(|dependencies| {
+                dependencies.iter()
+                    .filter_map(|dependency| {
+                        totals.get(&dependency[..]).map(|&(total, _)| (total, &dependency[..]))
+                    })
+                    .max()
+            });
+
+        let total = own + previous.map_or(Duration::from_secs(0), |(total, _)| total);
+
+        totals.insert(&node[..], (total, previous.map(|(_, dependency)| dependency)));
+    }
+
+    let last =
+        totals.iter()
+        .max_by_key(|&(node, &(total, _))| (total, *node))
+        .map(|(node, &(total, _))| (*node, total));
+
+    let (mut node, total) = match last {
+        Some(last) => last,
+        None => return (vec![], Duration::from_secs(0)),
+    };
+
+    let mut path = vec![String::from(node)];
+
+    while let Some(previous) = totals[node].1 {
+        path.push(String::from(previous));
+        node = previous;
+    }
+
+    path.reverse();
+
+    (path, total)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bind::{self, Bind};
+    use configuration::Configuration;
+    use dependency::Graph;
+    use job::evaluator::Inline;
+    use job::reporter::Quiet;
+    use rule::Rule;
+    use super::{Scheduler, critical_path};
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    fn scheduler(configuration: Configuration) -> Scheduler {
+        let mut scheduler = Scheduler::new(Arc::new(configuration.reporter(Quiet)));
+        scheduler.set_evaluator(Inline);
+        scheduler
+    }
+
+    fn record(log: &Log) -> impl Fn(&mut Bind) -> ::Result<()> + Sync + Send + 'static {
+        let log = log.clone();
+
+        move |bind: &mut Bind| {
+            log.lock().unwrap().push(bind.name.clone());
+            Ok(())
         }
     }
+
+    fn fail(_bind: &mut Bind) -> ::Result<()> {
+        Err(From::from("broken"))
+    }
+
+    #[test]
+    fn run_dependencies_first() {
+        let log = Log::default();
+        let mut scheduler = scheduler(Configuration::default());
+
+        scheduler.add(Arc::new(
+            Rule::named("index").depends_on("posts").handler(record(&log)).build()));
+        scheduler.add(Arc::new(Rule::named("posts").handler(record(&log)).build()));
+
+        scheduler.build().unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec!["posts", "index"]);
+    }
+
+    #[test]
+    fn stop_on_failure() {
+        let log = Log::default();
+        let mut scheduler = scheduler(Configuration::default());
+
+        scheduler.add(Arc::new(Rule::named("posts").handler(fail).build()));
+        scheduler.add(Arc::new(
+            Rule::named("index").depends_on("posts").handler(record(&log)).build()));
+
+        assert!(scheduler.build().is_err());
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn keep_going_past_failures() {
+        let log = Log::default();
+        let mut scheduler = scheduler(Configuration::default().keep_going(true));
+
+        scheduler.add(Arc::new(Rule::named("posts").handler(fail).build()));
+        scheduler.add(Arc::new(
+            Rule::named("index").depends_on("posts").handler(record(&log)).build()));
+        scheduler.add(Arc::new(Rule::named("about").handler(record(&log)).build()));
+
+        assert!(scheduler.build().is_err());
+        assert_eq!(*log.lock().unwrap(), vec!["about"]);
+    }
+
+    #[test]
+    fn start_longest_chains_first() {
+        let log = Log::default();
+        let mut scheduler = scheduler(Configuration::default());
+
+        scheduler.add(Arc::new(Rule::named("about").handler(record(&log)).build()));
+        scheduler.add(Arc::new(Rule::named("posts").handler(record(&log)).build()));
+        scheduler.add(Arc::new(
+            Rule::named("tags").depends_on("posts").handler(record(&log)).build()));
+
+        scheduler.build().unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec!["posts", "about", "tags"]);
+    }
+
+    #[test]
+    fn find_critical_path() {
+        let mut graph = Graph::new();
+
+        graph.add_node(String::from("about"));
+        graph.add_edge(String::from("posts"), String::from("tags"));
+        graph.add_edge(String::from("posts"), String::from("index"));
+
+        let elapsed =
+            vec![("about", 20), ("posts", 10), ("tags", 5), ("index", 30)]
+            .into_iter()
+            .map(|(name, millis)| (String::from(name), Duration::from_millis(millis)))
+            .collect::<HashMap<_, _>>();
+
+        let order = graph.resolve_all().unwrap();
+
+        assert_eq!(critical_path(&graph, &order, &elapsed),
+                   (vec![String::from("posts"), String::from("index")],
+                    Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn satisfy_once() {
+        let configuration = Arc::new(Configuration::default().reporter(Quiet));
+        let mut scheduler = Scheduler::new(configuration.clone());
+
+        scheduler.add(Arc::new(Rule::named("posts").build()));
+        scheduler.add(Arc::new(Rule::named("index").depends_on("posts").build()));
+
+        let order = scheduler.graph.resolve_all().unwrap();
+        scheduler.sort_jobs(order);
+
+        let posts = || Bind::new(bind::Data::new(String::from("posts"), configuration.clone()));
+
+        assert!(scheduler.satisfy(posts()).is_ok());
+        assert!(scheduler.satisfy(posts()).is_err());
+    }
 }
@@ -0,0 +1,159 @@
+//! Diagnostics for handlers that run for too long.
+//!
+//! A handler that never returns, such as a user closure stuck in a
+//! loop or waiting on a lock, otherwise just stalls the build without
+//! a word. When `Configuration::watchdog` is set, every bind and every
+//! item processed by `bind::each` is watched, and anything that runs
+//! for longer than the threshold is reported, along with the backtrace
+//! of where it was started if `RUST_BACKTRACE` is set.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use typemap;
+
+use super::{Event, Reporter};
+
+/// Something that's being processed.
+struct Activity {
+    rule: String,
+    item: Option<String>,
+    handler: Option<&'static str>,
+    started: Instant,
+    reports: u32,
+    backtrace: Backtrace,
+}
+
+/// Reports activities that run for longer than a threshold.
+pub struct Watchdog {
+    threshold: Duration,
+    reporter: Arc<Reporter + Sync + Send>,
+    activities: Mutex<BTreeMap<usize, Activity>>,
+    next: AtomicUsize,
+    stopped: AtomicBool,
+}
+
+/// The watchdog of the current build, as a bind extension.
+impl typemap::Key for Watchdog {
+    type Value = Arc<Watchdog>;
+}
+
+impl Watchdog {
+    pub fn new(threshold: Duration, reporter: Arc<Reporter + Sync + Send>) -> Arc<Watchdog> {
+        Arc::new(Watchdog {
+            threshold: threshold,
+            reporter: reporter,
+            activities: Mutex::new(BTreeMap::new()),
+            next: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+        })
+    }
+
+    /// Sample the activities on a separate thread until the returned
+    /// `Sampler` is dropped.
+    pub fn start(watchdog: &Arc<Watchdog>) -> Sampler {
+        let watchdog = watchdog.clone();
+
+        let interval = ::std::cmp::max(watchdog.threshold / 4, Duration::from_millis(100));
+
+        let sampler = Sampler {
+            watchdog: watchdog.clone(),
+        };
+
+        thread::spawn(move || {
+            while !watchdog.stopped.load(Ordering::SeqCst) {
+                thread::sleep(interval);
+                watchdog.sample();
+            }
+        });
+
+        sampler
+    }
+
+    /// Watch an activity until the returned guard is dropped.
+    pub fn watch<R>(watchdog: &Arc<Watchdog>,
+                    rule: R,
+                    item: Option<String>,
+                    handler: Option<&'static str>)
+    -> Guard
+    where R: Into<String> {
+        let id = watchdog.next.fetch_add(1, Ordering::SeqCst);
+
+        let activity = Activity {
+            rule: rule.into(),
+            item: item,
+            handler: handler,
+            started: Instant::now(),
+            reports: 0,
+            backtrace: Backtrace::capture(),
+        };
+
+        watchdog.activities.lock().unwrap().insert(id, activity);
+
+        Guard {
+            watchdog: watchdog.clone(),
+            id: id,
+        }
+    }
+
+    /// Report the activities that crossed another multiple of the threshold.
+    fn sample(&self) {
+        let mut activities = self.activities.lock().unwrap();
+
+        for activity in activities.values_mut() {
+            let elapsed = activity.started.elapsed();
+
+            if elapsed < self.threshold * (activity.reports + 1) {
+                continue;
+            }
+
+            let mut message = format!("`{}` has been running", activity.rule);
+
+            if let Some(handler) = activity.handler {
+                message.push_str(&format!(" `{}`", handler));
+            }
+
+            if let Some(ref item) = activity.item {
+                message.push_str(&format!(" on {}", item));
+            }
+
+            message.push_str(&format!(" for {}s", elapsed.as_secs()));
+
+            self.reporter.report(&Event::Warning { message: &message });
+
+            if activity.reports == 0 && activity.backtrace.status() == BacktraceStatus::Captured {
+                let started = format!("it was started at:\n{}", activity.backtrace);
+                self.reporter.report(&Event::Note { message: &started });
+            }
+
+            activity.reports += 1;
+        }
+    }
+}
+
+/// Stops the sampling thread when dropped.
+pub struct Sampler {
+    watchdog: Arc<Watchdog>,
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.watchdog.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Stops watching an activity when dropped.
+pub struct Guard {
+    watchdog: Arc<Watchdog>,
+    id: usize,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.watchdog.activities.lock().unwrap().remove(&self.id);
+    }
+}
@@ -14,8 +14,11 @@ extern crate toml;
 extern crate typemap;
 extern crate walkdir;
 extern crate time;
+extern crate chrono;
+extern crate chrono_tz;
 
 extern crate serde;
+extern crate serde_json;
 
 #[macro_use]
 extern crate serde_derive;
@@ -24,24 +27,57 @@ extern crate serde_derive;
 extern crate log;
 
 extern crate rustc_serialize;
-extern crate docopt;
 extern crate num_cpus;
+extern crate ctrlc;
 extern crate ansi_term;
 
 extern crate futures;
 
+#[cfg(feature = "tera")]
+extern crate tera;
+
+#[cfg(feature = "minify")]
+extern crate minifier;
+
+#[cfg(feature = "images")]
+extern crate image;
+
+#[cfg(feature = "sass")]
+extern crate grass;
+
+#[cfg(feature = "precompress")]
+extern crate flate2;
+
+#[cfg(feature = "precompress")]
+extern crate brotli;
+
+#[cfg(feature = "yaml")]
+extern crate serde_yaml;
+
+#[cfg(feature = "epub")]
+extern crate zip;
+
+#[cfg(feature = "check-external")]
+extern crate ureq;
+
+#[cfg(feature = "git")]
+extern crate git2;
+
+#[cfg(target_os = "linux")]
+extern crate libc;
+
 pub use pattern::Pattern;
 pub use site::Site;
 pub use rule::Rule;
 pub use configuration::Configuration;
 pub use item::Item;
 pub use bind::Bind;
-pub use handler::Handle;
+pub use handler::{Handle, HandleAsync};
 // TODO command hooks
 pub use command::Command;
 
 mod handler;
-mod job;
+pub mod job;
 mod dependency;
 
 #[macro_use]
@@ -55,6 +91,14 @@ pub mod command;
 pub mod configuration;
 pub mod util;
 pub mod support;
+pub mod templates;
+pub mod manifest;
+pub mod model;
+pub mod phase;
+pub mod vfs;
+pub mod vcs;
+pub mod prelude;
+pub mod diag;
 
 pub type Error = Box<::std::error::Error + Sync + Send>;
 pub type Result<T> = ::std::result::Result<T, Error>;
@@ -0,0 +1,245 @@
+//! Build manifests.
+//!
+//! A manifest records every file that a build generated, along with
+//! the file and rule it was generated from, how long that took, and a
+//! hash of its contents. Comparing the manifests of two builds shows
+//! which pages were added, removed, changed, or moved.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde_json;
+use typemap;
+
+use bind::Bind;
+use item::Elapsed;
+use support;
+
+/// The hash of an item's written contents, for items whose
+/// body isn't what was written, such as copied files.
+pub struct Checksum;
+
+impl typemap::Key for Checksum {
+    type Value = String;
+}
+
+/// A file generated by a build.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// The file that was read, relative to the input directory
+    pub source: Option<PathBuf>,
+
+    /// The file that was written, relative to the output directory
+    pub target: PathBuf,
+
+    /// The hash of the written contents
+    pub hash: String,
+
+    /// The URL that the file's tombstone links to, if it's
+    /// the tombstone of a page that moved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_to: Option<String>,
+
+    /// The rule that produced the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+
+    /// How long the handlers of `bind::each` took with its item, in milliseconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub millis: Option<u64>,
+}
+
+impl Entry {
+    /// The site-relative URL of the generated file.
+    pub fn url(&self) -> String {
+        support::url(&self.target)
+    }
+}
+
+/// The name of the manifest that's written into the output directory
+/// if `Configuration::output_manifest` is set.
+pub const OUTPUT_NAME: &'static str = ".diecast-manifest.json";
+
+/// The set of files generated by a build.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Manifest {
+    pub entries: Vec<Entry>,
+}
+
+impl Manifest {
+    pub fn new() -> Manifest {
+        Manifest {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn load<P>(path: P) -> ::Result<Manifest>
+    where P: AsRef<Path> {
+        let mut contents = String::new();
+
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    pub fn save<P>(&self, path: P) -> ::Result<()>
+    where P: AsRef<Path> {
+        let contents = serde_json::to_string_pretty(self)?;
+
+        File::create(path)?.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Record every item in the bind that writes a file.
+    pub fn record(&mut self, bind: &Bind) {
+        for item in bind.items() {
+            if let Some(target) = item.output_path() {
+                self.entries.push(Entry {
+                    source: item.route().reading().map(Path::to_path_buf),
+                    target: target,
+                    hash: item.extensions.get::<Checksum>().cloned()
+                        .unwrap_or_else(|| support::hash(item.body.as_bytes())),
+                    moved_to: None,
+                    rule: Some(bind.name.clone()),
+                    millis: item.extensions.get::<Elapsed>().map(|elapsed| {
+                        elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis())
+                    }),
+                });
+            }
+        }
+    }
+
+    /// Sort the entries by target, since binds finish in no particular order.
+    pub fn sort(&mut self) {
+        self.entries.sort_by(|a, b| a.target.cmp(&b.target));
+    }
+
+    /// Check the files in the output directory against the manifest.
+    ///
+    /// Returns the entries whose files are missing or whose contents
+    /// don't match their hash. Files are hashed as they're streamed.
+    pub fn verify<P>(&self, output: P) -> ::Result<Vec<Entry>>
+    where P: AsRef<Path> {
+        let output = output.as_ref();
+        let mut mismatched = vec![];
+
+        for entry in &self.entries {
+            let matches = match File::open(output.join(&entry.target)) {
+                Ok(file) => support::hash_reader(file)? == entry.hash,
+                Err(_) => false,
+            };
+
+            if !matches {
+                mismatched.push(entry.clone());
+            }
+        }
+
+        Ok(mismatched)
+    }
+
+    /// Compare this manifest against that of a newer build.
+    pub fn diff(&self, newer: &Manifest) -> Diff {
+        let old = by_target(&self.entries);
+        let new = by_target(&newer.entries);
+
+        let mut diff = Diff::default();
+
+        let mut removed = BTreeMap::new();
+
+        for (target, entry) in &old {
+            match new.get(target) {
+                Some(current) => {
+                    if current.hash != entry.hash {
+                        diff.changed.push((*current).clone());
+                    }
+                },
+                None => {
+                    removed.insert(*target, *entry);
+                },
+            }
+        }
+
+        let mut added = BTreeMap::new();
+
+        for (target, entry) in &new {
+            if !old.contains_key(target) {
+                added.insert(*target, *entry);
+            }
+        }
+
+        // an entry generated from the same source but written
+        // somewhere else has moved, which breaks inbound links
+        for (_, entry) in removed {
+            let moved =
+                entry.source.as_ref()
+                .and_then(|source| {
+                    added.iter()
+                        .find(|&(_, a)| a.source.as_ref() == Some(source))
+                        .map(|(t, _)| *t)
+                });
+
+            match moved {
+                Some(to) => {
+                    let current = added.remove(to).unwrap();
+                    diff.moved.push((entry.clone(), current.clone()));
+                },
+                None => {
+                    diff.removed.push(entry.clone());
+                },
+            }
+        }
+
+        diff.added = added.into_iter().map(|(_, e)| e.clone()).collect();
+
+        diff
+    }
+}
+
+fn by_target(entries: &[Entry]) -> BTreeMap<&Path, &Entry> {
+    entries.iter().map(|e| (e.target.as_path(), e)).collect()
+}
+
+/// The differences between two manifests.
+#[derive(Debug, Default)]
+pub struct Diff {
+    /// Files that only exist in the newer build
+    pub added: Vec<Entry>,
+
+    /// Files that only exist in the older build
+    pub removed: Vec<Entry>,
+
+    /// Files whose contents changed
+    pub changed: Vec<Entry>,
+
+    /// Files generated from the same source that were written
+    /// to a different location, as (old, new) pairs
+    pub moved: Vec<(Entry, Entry)>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.moved.is_empty()
+    }
+
+    /// A suggested redirect map for the moved files.
+    ///
+    /// Each line maps an old URL to its new URL, separated by a space.
+    pub fn redirects(&self) -> String {
+        let mut map = String::new();
+
+        for &(ref from, ref to) in &self.moved {
+            map.push_str(&from.url());
+            map.push(' ');
+            map.push_str(&to.url());
+            map.push('\n');
+        }
+
+        map
+    }
+}
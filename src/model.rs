@@ -0,0 +1,106 @@
+//! The resolved structure of a site.
+//!
+//! A model records every rule of a build along with its items, their
+//! routes, dates, and metadata, so that tools that aren't written in
+//! Rust, such as editors, search indexers, or CI checks, can inspect
+//! the site without running the generator themselves. It's exported
+//! by the `export` command.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+
+use bind::Bind;
+use item::{self, Item};
+
+/// An item as it was when its bind finished.
+#[derive(Serialize, Clone, Debug)]
+pub struct Entry {
+    /// The file that was read, relative to the input directory
+    pub source: Option<PathBuf>,
+
+    /// The file that was written, relative to the output directory
+    pub target: Option<PathBuf>,
+
+    /// The site-relative URL, if the item is written
+    pub url: Option<String>,
+
+    /// The `Date` as RFC 3339
+    pub date: Option<String>,
+
+    pub slug: Option<String>,
+
+    pub metadata: Option<serde_json::Value>,
+
+    /// The item's `meta`
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub meta: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Entry {
+    pub fn from_item(item: &Item) -> Entry {
+        Entry {
+            source: item.route().reading().map(Path::to_path_buf),
+            target: item.output_path(),
+            url: item.url(),
+            date: item.extensions.get::<item::Date>().map(|date| date.to_rfc3339()),
+            slug: item.extensions.get::<item::Slug>().cloned(),
+            metadata:
+                item.extensions.get::<item::Metadata>()
+                .and_then(|metadata| serde_json::to_value(metadata).ok()),
+            meta: item.meta.clone(),
+        }
+    }
+}
+
+/// A rule and the items it produced.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Rule {
+    /// The names of the rules it depends on, sorted
+    pub dependencies: Vec<String>,
+
+    /// The error it failed with, if it was allowed to fail
+    pub error: Option<String>,
+
+    pub items: Vec<Entry>,
+}
+
+/// The rules of a build, by name.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Model {
+    pub rules: BTreeMap<String, Rule>,
+}
+
+impl Model {
+    pub fn new() -> Model {
+        Model {
+            rules: BTreeMap::new(),
+        }
+    }
+
+    /// Record a finished bind and the dependencies of its rule.
+    pub fn record(&mut self, bind: &Bind, dependencies: &HashSet<String>) {
+        let mut dependencies = dependencies.iter().cloned().collect::<Vec<_>>();
+        dependencies.sort();
+
+        self.rules.insert(bind.name.clone(), Rule {
+            dependencies: dependencies,
+            error: bind.error().map(String::from),
+            items: bind.items().iter().map(Entry::from_item).collect(),
+        });
+    }
+
+    pub fn to_json(&self) -> ::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn save<P>(&self, path: P) -> ::Result<()>
+    where P: AsRef<Path> {
+        File::create(path)?.write_all(self.to_json()?.as_bytes())?;
+
+        Ok(())
+    }
+}
@@ -22,6 +22,7 @@ use glob;
 use regex::Regex;
 use std::path::{Path, PathBuf};
 use std::collections::HashSet;
+use std::sync::Arc;
 
 /// A kind of pattern that can be used for
 /// filtering the files in the input directory.
@@ -36,6 +37,12 @@ where P: Pattern {
     }
 }
 
+impl<P: ?Sized> Pattern for Arc<P> where P: Pattern {
+    fn matches(&self, path: &Path) -> bool {
+        (**self).matches(path)
+    }
+}
+
 impl<'a, P: ?Sized> Pattern for &'a P where P: Pattern {
     fn matches(&self, path: &Path) -> bool {
         (**self).matches(path)
@@ -61,21 +68,6 @@ where P: Pattern {
     }
 }
 
-// TODO
-// can create an And and Or that are not type parameterized
-// they would store trait objects in a vec
-//
-// And's implementation would be self.objs.iter().all(|p| p.matches(p))
-// Or's implementation would be self.objs.iter().any(|p| p.matches(p))
-//
-// Would be (with appropriate macro):
-//
-// Or::new().add(pat1).add(pat2)
-//
-// Or just introduce or! etc. macro which expands to
-//
-// Or::from(vec![Box::new(pat1) as Box<Pattern>, Box::new(pat2) as Box<Pattern>])
-
 /// This conjunction of two patterns.
 pub struct And<A, B>
 where A: Pattern, B: Pattern {
@@ -104,6 +96,164 @@ where A: Pattern, B: Pattern {
     }
 }
 
+/// The disjunction of any number of patterns.
+///
+/// Unlike `Or`, this can be built at runtime, e.g. from a list in
+/// `Diecast.toml`. It matches nothing if it's empty.
+pub struct AnyOf {
+    patterns: Vec<Box<Pattern + Sync + Send>>,
+}
+
+impl AnyOf {
+    pub fn new() -> AnyOf {
+        AnyOf {
+            patterns: vec![],
+        }
+    }
+
+    pub fn push<P>(&mut self, pattern: P)
+    where P: Pattern + Sync + Send + 'static {
+        self.patterns.push(Box::new(pattern));
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+impl From<Vec<Box<Pattern + Sync + Send>>> for AnyOf {
+    fn from(patterns: Vec<Box<Pattern + Sync + Send>>) -> AnyOf {
+        AnyOf {
+            patterns: patterns,
+        }
+    }
+}
+
+impl Pattern for AnyOf {
+    fn matches(&self, p: &Path) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(p))
+    }
+}
+
+/// The conjunction of any number of patterns.
+///
+/// Unlike `And`, this can be built at runtime. It matches everything
+/// if it's empty.
+pub struct AllOf {
+    patterns: Vec<Box<Pattern + Sync + Send>>,
+}
+
+impl AllOf {
+    pub fn new() -> AllOf {
+        AllOf {
+            patterns: vec![],
+        }
+    }
+
+    pub fn push<P>(&mut self, pattern: P)
+    where P: Pattern + Sync + Send + 'static {
+        self.patterns.push(Box::new(pattern));
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+impl From<Vec<Box<Pattern + Sync + Send>>> for AllOf {
+    fn from(patterns: Vec<Box<Pattern + Sync + Send>>) -> AllOf {
+        AllOf {
+            patterns: patterns,
+        }
+    }
+}
+
+impl Pattern for AllOf {
+    fn matches(&self, p: &Path) -> bool {
+        self.patterns.iter().all(|pattern| pattern.matches(p))
+    }
+}
+
+/// A closure used as a pattern.
+///
+/// Closures can't implement `Pattern` directly, since references to
+/// and boxes of closures are closures too, which would make the impls
+/// for `&P` and `Box<P>` overlap.
+pub struct FromFn<F>
+where F: Fn(&Path) -> bool {
+    function: F,
+}
+
+impl<F> Pattern for FromFn<F>
+where F: Fn(&Path) -> bool {
+    fn matches(&self, p: &Path) -> bool {
+        (self.function)(p)
+    }
+}
+
+/// Pattern that matches files with any of a set of extensions.
+pub struct Extensions {
+    extensions: Vec<String>,
+}
+
+impl Pattern for Extensions {
+    fn matches(&self, p: &Path) -> bool {
+        p.extension()
+            .and_then(|extension| extension.to_str())
+            .map_or(false, |extension| {
+                self.extensions.iter().any(|e| e.eq_ignore_ascii_case(extension))
+            })
+    }
+}
+
+/// Constructs a pattern that matches any of the extensions, ignoring case.
+///
+/// A leading dot is optional, e.g. `extensions(["md", ".markdown", "mkd"])`.
+pub fn extensions<I, S>(extensions: I) -> Extensions
+where I: IntoIterator<Item = S>, S: AsRef<str> {
+    Extensions {
+        extensions:
+            extensions.into_iter()
+            .map(|extension| String::from(extension.as_ref().trim_start_matches('.')))
+            .collect(),
+    }
+}
+
+/// A pattern that ignores the case of paths.
+pub struct CaseInsensitive<P>
+where P: Pattern {
+    pattern: P,
+}
+
+impl<P> Pattern for CaseInsensitive<P>
+where P: Pattern {
+    fn matches(&self, p: &Path) -> bool {
+        p.to_str()
+            .map_or(false, |s| self.pattern.matches(Path::new(&s.to_lowercase())))
+    }
+}
+
+/// Constructs a pattern that matches paths regardless of their case.
+///
+/// This matches the lowercased path, so the pattern must be written in
+/// lowercase, e.g. `case_insensitive(glob!("posts/*.md"))` matches
+/// `Posts/Foo.MD`. This is useful for sites authored on case-insensitive
+/// file systems and built on case-sensitive ones.
+pub fn case_insensitive<P>(pattern: P) -> CaseInsensitive<P>
+where P: Pattern {
+    CaseInsensitive {
+        pattern: pattern,
+    }
+}
+
 /// Pattern that matches everything.
 #[derive(Copy, Clone)]
 pub struct Everything;
@@ -159,9 +309,171 @@ impl Pattern for glob::Pattern {
     }
 }
 
+/// A rule of a `Gitignore`.
+struct IgnoreRule {
+    regex: Regex,
+    negated: bool,
+    directories: bool,
+}
+
+/// Patterns in the syntax of `.gitignore` files.
+///
+/// As in git, a later rule overrides an earlier one, a rule starting
+/// with `!` re-includes what an earlier one excluded, a rule ending in
+/// `/` only matches directories, and a rule with a `/` elsewhere is
+/// anchored to the root. Everything under a matched directory is matched,
+/// and can't be re-included.
+pub struct Gitignore {
+    rules: Vec<IgnoreRule>,
+}
+
+impl Gitignore {
+    /// Parse the lines of a `.gitignore` file.
+    pub fn parse(contents: &str) -> Result<Gitignore, ::regex::Error> {
+        let mut rules = vec![];
+
+        for line in contents.lines() {
+            let line = line.trim_end();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, line) =
+                if line.starts_with('!') { (true, &line[1..]) }
+                else { (false, line) };
+
+            let (directories, line) =
+                if line.ends_with('/') { (true, &line[.. line.len() - 1]) }
+                else { (false, line) };
+
+            let anchored = line.contains('/');
+            let line = line.trim_start_matches('/');
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+            let regex = Regex::new(&format!("{}{}$", prefix, glob_to_regex(line)))?;
+
+            rules.push(IgnoreRule {
+                regex: regex,
+                negated: negated,
+                directories: directories,
+            });
+        }
+
+        Ok(Gitignore {
+            rules: rules,
+        })
+    }
+
+    /// Whether a relative path is matched, given whether it's a directory.
+    pub fn matches_path(&self, path: &Path, is_dir: bool) -> bool {
+        let mut prefix = PathBuf::new();
+        let mut components = path.components().peekable();
+
+        while let Some(component) = components.next() {
+            prefix.push(component);
+
+            let is_last = components.peek().is_none();
+
+            if self.decide(&prefix, !is_last || is_dir) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Whether the last rule that matches the path excludes it.
+    fn decide(&self, path: &Path, is_dir: bool) -> bool {
+        let path = match path.to_str() {
+            Some(path) => path,
+            None => return false,
+        };
+
+        self.rules.iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.directories) && rule.regex.is_match(path))
+            .map_or(false, |rule| !rule.negated)
+    }
+}
+
+/// Paths are taken to be files, though their ancestors are directories.
+impl Pattern for Gitignore {
+    fn matches(&self, p: &Path) -> bool {
+        self.matches_path(p, false)
+    }
+}
+
+/// Translate a `.gitignore` glob to a regular expression.
+fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+    let mut at_start = true;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else if at_start || regex.ends_with('/') {
+                    regex.push_str(".*");
+                } else {
+                    regex.push_str("[^/]*");
+                }
+            },
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+
+                while let Some(c) = chars.next() {
+                    if c == ']' && !class.is_empty() {
+                        closed = true;
+                        break;
+                    }
+
+                    class.push(c);
+                }
+
+                if closed {
+                    if class.starts_with('!') {
+                        class = format!("^{}", &class[1..]);
+                    }
+
+                    regex.push('[');
+                    regex.push_str(&class.replace("\\", "\\\\"));
+                    regex.push(']');
+                } else {
+                    regex.push_str(&::regex::escape(&format!("[{}", class)));
+                }
+            },
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    regex.push_str(&::regex::escape(&c.to_string()));
+                }
+            },
+            c => regex.push_str(&::regex::escape(&c.to_string())),
+        }
+
+        at_start = false;
+    }
+
+    regex
+}
+
 /// Contains the DSL items for easily constructing complex patterns.
 pub mod dsl {
-    use super::{Pattern, Not, And, Or};
+    use std::path::Path;
+
+    use super::{Pattern, Not, And, Or, AnyOf, AllOf, FromFn};
 
     /// Constructs the negation of a pattern.
     pub fn not<P>(p: P) -> Not<P>
@@ -188,6 +500,28 @@ pub mod dsl {
             right: b
         }
     }
+
+    /// Constructs a pattern out of a closure, for one-off matching logic.
+    ///
+    /// ```ignore
+    /// bind::select(pattern!(from_fn(|path| path.components().count() == 1)))
+    /// ```
+    pub fn from_fn<F>(function: F) -> FromFn<F>
+    where F: Fn(&Path) -> bool {
+        FromFn {
+            function: function
+        }
+    }
+
+    /// Constructs the disjunction of a list of patterns.
+    pub fn any_of(patterns: Vec<Box<Pattern + Sync + Send>>) -> AnyOf {
+        AnyOf::from(patterns)
+    }
+
+    /// Constructs the conjunction of a list of patterns.
+    pub fn all_of(patterns: Vec<Box<Pattern + Sync + Send>>) -> AllOf {
+        AllOf::from(patterns)
+    }
 }
 
 #[cfg(test)]
@@ -267,6 +601,98 @@ mod test {
         assert!(Pattern::matches("pages/about.md", &about_page));
     }
 
+    #[test]
+    fn match_lists() {
+        use super::{AnyOf, AllOf};
+
+        let intro_to_rust = Path::new("posts/long/introduction-to-rust.md");
+        let about_page = Path::new("pages/about.md");
+
+        let mut any = AnyOf::new();
+        assert!(!any.matches(&about_page));
+
+        any.push("pages/about.md");
+        any.push(Regex::new(r"^drafts/").unwrap());
+        assert!(any.matches(&about_page));
+        assert!(!any.matches(&intro_to_rust));
+
+        let mut all = AllOf::new();
+        assert!(all.matches(&about_page));
+
+        all.push(Regex::new(r"^posts/").unwrap());
+        all.push(Regex::new(r"\.md$").unwrap());
+        assert!(all.matches(&intro_to_rust));
+        assert!(!all.matches(&about_page));
+    }
+
+    #[test]
+    fn match_closures() {
+        let intro_to_rust = Path::new("posts/long/introduction-to-rust.md");
+        let about_page = Path::new("pages/about.md");
+
+        let shallow = pattern!(from_fn(|path: &Path| path.components().count() <= 2));
+
+        assert!(shallow.matches(&about_page));
+        assert!(!shallow.matches(&intro_to_rust));
+        assert!(and!(shallow, not!("pages/about.md")).matches(&Path::new("index.md")));
+    }
+
+    #[test]
+    fn match_extensions() {
+        use super::extensions;
+
+        let markdown = extensions(["md", ".markdown", "mkd"]);
+
+        assert!(markdown.matches(&Path::new("posts/intro.md")));
+        assert!(markdown.matches(&Path::new("posts/intro.MARKDOWN")));
+        assert!(!markdown.matches(&Path::new("posts/intro.html")));
+        assert!(!markdown.matches(&Path::new("posts/md")));
+    }
+
+    #[test]
+    fn match_ignoring_case() {
+        use glob;
+        use super::case_insensitive;
+
+        let posts = case_insensitive(glob::Pattern::new("posts/*.md").unwrap());
+
+        assert!(posts.matches(&Path::new("Posts/Foo.MD")));
+        assert!(posts.matches(&Path::new("posts/foo.md")));
+        assert!(!posts.matches(&Path::new("pages/foo.md")));
+    }
+
+    #[test]
+    fn match_gitignore() {
+        use super::Gitignore;
+
+        let ignore = Gitignore::parse("\
+# drafts and editor files
+*.swp
+/drafts/
+notes/**/*.txt
+build/
+!build/keep.css
+*.log
+!important.log
+").unwrap();
+
+        assert!(ignore.matches(Path::new("posts/post.md.swp")));
+        assert!(ignore.matches(Path::new("drafts/post.md")));
+        assert!(!ignore.matches(Path::new("posts/drafts/post.md")));
+        assert!(ignore.matches(Path::new("notes/a/b/todo.txt")));
+        assert!(ignore.matches(Path::new("notes/todo.txt")));
+        assert!(!ignore.matches(Path::new("notes/todo.md")));
+
+        // a directory's contents can't be re-included
+        assert!(ignore.matches(Path::new("build/keep.css")));
+        assert!(!ignore.matches_path(Path::new("build"), false));
+        assert!(ignore.matches_path(Path::new("build"), true));
+
+        assert!(ignore.matches(Path::new("debug.log")));
+        assert!(!ignore.matches(Path::new("important.log")));
+        assert!(!ignore.matches(Path::new("posts/post.md")));
+    }
+
     #[test]
     fn use_macros() {
         use glob;
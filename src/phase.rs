@@ -0,0 +1,205 @@
+//! The phases of a rule.
+//!
+//! Processing an item goes through phases in a fixed order: its source
+//! is read, its body is transformed, it's routed, rendered, written, and
+//! post-processed.
+//!
+//! A rule can be built from handlers declared per phase, which run in
+//! phase order:
+//!
+//! ```ignore
+//! let posts =
+//!     Rule::named("posts")
+//!     .phase(Phase::Source, bind::select(glob!("posts/*.md")))
+//!     .phase(Phase::Transform, bind::each(chain![item::read, markdown]))
+//!     .phase(Phase::Route, bind::each(route::pretty))
+//!     .phase(Phase::Render, bind::each(tera::render_template("post", context::item)))
+//!     .phase(Phase::Write, bind::each(item::write))
+//!     .build();
+//! ```
+//!
+//! Such rules run the site's cross-cutting passes, added with
+//! `Site::pass`, after their own handlers of the same phase, e.g. to
+//! validate every rendered page. In them, the built-in handlers record
+//! the phase that each item has reached, so an ordering mistake, such as
+//! rendering an item after writing it, fails with an explanation instead
+//! of silently producing the wrong output. Rules with a single handler
+//! aren't checked.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use typemap;
+
+use bind::Bind;
+use handler::Handle;
+use item::Item;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Selecting and reading items.
+    Source,
+
+    /// Changing their bodies and metadata.
+    Transform,
+
+    /// Deciding where they're written.
+    Route,
+
+    /// Rendering them with templates.
+    Render,
+
+    /// Writing them.
+    Write,
+
+    /// Anything that needs the written output.
+    Post,
+}
+
+impl Phase {
+    pub fn all() -> [Phase; 6] {
+        [Phase::Source, Phase::Transform, Phase::Route, Phase::Render, Phase::Write, Phase::Post]
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Phase::Source => "source",
+            Phase::Transform => "transform",
+            Phase::Route => "route",
+            Phase::Render => "render",
+            Phase::Write => "write",
+            Phase::Post => "post",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// The latest phase that an item has reached, along with the rule
+/// that it reached it in.
+pub struct Reached;
+
+impl typemap::Key for Reached {
+    type Value = (String, Phase);
+}
+
+/// The phase whose handlers a bind is running, if its rule declares phases.
+pub struct Current;
+
+impl typemap::Key for Current {
+    type Value = Phase;
+}
+
+/// Record that the item is entering a phase.
+///
+/// In a rule that declares phases, this fails if the item has already
+/// reached a later phase in it, or if the bind is running the handlers
+/// of an earlier phase than this one. Otherwise it does nothing.
+pub fn enter(item: &mut Item, phase: Phase) -> ::Result<()> {
+    let current = item.bind().with_ext(|extensions| extensions.get::<Current>().cloned());
+
+    let current = match current {
+        Some(current) => current,
+        None => return Ok(()),
+    };
+
+    let name = item.bind().name.clone();
+
+    // an item from a dependency reached its phases in another rule
+    if let Some(&(ref rule, reached)) = item.extensions.get::<Reached>() {
+        if *rule == name && reached > phase {
+            return Err(From::from(format!(
+                "{} entered the {} phase after the {} phase; \
+                 check the order of the handlers of `{}`",
+                item, phase, reached, name)));
+        }
+    }
+
+    if phase > current {
+        return Err(From::from(format!(
+            "{} entered the {} phase from a handler of the {} phase of `{}`",
+            item, phase, current, name)));
+    }
+
+    item.extensions.insert::<Reached>((name, phase));
+
+    Ok(())
+}
+
+/// The latest phase that the item has reached in the given rule, if it
+/// was recorded.
+pub fn reached(item: &Item, rule: &str) -> Option<Phase> {
+    match item.extensions.get::<Reached>() {
+        Some(&(ref name, phase)) if name == rule => Some(phase),
+        _ => None,
+    }
+}
+
+pub struct At<H>
+where H: Handle<Item> {
+    phase: Phase,
+    handler: H,
+}
+
+impl<H> Handle<Item> for At<H>
+where H: Handle<Item> {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        enter(item, self.phase)?;
+        self.handler.handle(item)
+    }
+}
+
+/// Handle<Item> that enters the phase before running the handler.
+///
+/// This declares the phase of a handler that isn't built-in, so that
+/// it's checked like the built-in ones.
+#[inline]
+pub fn at<H>(phase: Phase, handler: H) -> At<H>
+where H: Handle<Item> {
+    At {
+        phase: phase,
+        handler: handler,
+    }
+}
+
+pub type Handlers = BTreeMap<Phase, Vec<Arc<Handle<Bind> + Sync + Send>>>;
+
+/// The site's cross-cutting passes, by phase.
+pub struct Passes;
+
+impl typemap::Key for Passes {
+    type Value = Arc<Handlers>;
+}
+
+/// The handler of a rule that declares its phases.
+pub struct Phased {
+    pub handlers: Handlers,
+}
+
+impl Handle<Bind> for Phased {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let passes = bind.with_ext(|extensions| extensions.get::<Passes>().cloned());
+
+        for phase in Phase::all().iter() {
+            bind.with_ext_mut(|extensions| extensions.insert::<Current>(*phase));
+
+            let own = self.handlers.get(phase).into_iter().flat_map(|handlers| handlers.iter());
+
+            let site =
+                passes.iter()
+                .flat_map(|passes| passes.get(phase))
+                .flat_map(|handlers| handlers.iter());
+
+            for handler in own.chain(site) {
+                handler.handle(bind)?;
+            }
+        }
+
+        bind.with_ext_mut(|extensions| extensions.remove::<Current>());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! The types and traits that most sites use.
+//!
+//! ```ignore
+//! use diecast::prelude::*;
+//! ```
+
+pub use bind::Bind;
+pub use configuration::Configuration;
+pub use handler::{Handle, HandleAsync};
+pub use item::{Item, ItemExt};
+pub use pattern::Pattern;
+pub use rule::Rule;
+pub use site::Site;
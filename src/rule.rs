@@ -1,31 +1,191 @@
 use std::sync::Arc;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::Into;
+use std::path::PathBuf;
+
+use glob;
+use serde_json;
+use toml;
 
 use bind::Bind;
+use configuration::{Configuration, RuleTable};
 use util;
+use util::route;
+use util::handle::{bind, item};
 use handler::Handle;
+use pattern::{Pattern, AnyOf};
+use phase::{self, Phase};
+
+/// Overrides of the site's configuration for a single rule.
+///
+/// The rule's handlers see the overridden configuration through
+/// `bind.configuration`.
+#[derive(Clone, Default)]
+pub struct Overlay {
+    /// A subdirectory of the output to write into
+    pub output: Option<PathBuf>,
+
+    /// The most items that `bind::each` handles at once
+    pub threads: Option<usize>,
+
+    /// A pattern of input paths that `bind::select` skips, in addition
+    /// to those that the site ignores
+    pub ignore: Option<Arc<Pattern + Sync + Send>>,
+}
+
+impl Overlay {
+    pub fn is_empty(&self) -> bool {
+        self.output.is_none() && self.threads.is_none() && self.ignore.is_none()
+    }
+
+    /// The configuration with the overrides applied.
+    pub fn apply(&self, configuration: &Configuration) -> Configuration {
+        let mut configuration = configuration.clone();
+
+        if let Some(ref output) = self.output {
+            configuration.output = configuration.output.join(output);
+            configuration.previous = configuration.previous.map(|previous| previous.join(output));
+            configuration.subdirectory = Some(output.clone());
+        }
+
+        if let Some(threads) = self.threads {
+            configuration.threads = threads;
+        }
+
+        if let Some(ref ignore) = self.ignore {
+            let mut patterns = AnyOf::new();
+
+            if let Some(site) = configuration.ignore.take() {
+                patterns.push(site);
+            }
+
+            patterns.push(ignore.clone());
+            configuration.ignore = Some(Arc::new(patterns));
+        }
+
+        configuration
+    }
+}
+
+/// Extracts the data that a dependent needs from a dependency's bind.
+pub type Projector = Arc<Fn(&Bind) -> serde_json::Value + Sync + Send>;
 
 #[must_use]
 pub struct Builder {
     name: String,
-    handler: Arc<Handle<Bind> + Sync + Send>,
+    handler: Option<Arc<Handle<Bind> + Sync + Send>>,
     dependencies: HashSet<String>,
+    projections: HashMap<String, Projector>,
+    pattern: Option<Arc<Pattern + Sync + Send>>,
+    allow_failure: bool,
+    phases: phase::Handlers,
+    overlay: Overlay,
+    before: Vec<Arc<Handle<Bind> + Sync + Send>>,
+    after: Vec<Arc<Handle<Bind> + Sync + Send>>,
+    default_metadata: Option<Arc<toml::value::Table>>,
 }
 
 impl Builder {
     fn new(name: String) -> Builder {
         Builder {
             name: name,
-            handler: Arc::new(util::handle::bind::missing),
+            handler: None,
             dependencies: HashSet::new(),
+            projections: HashMap::new(),
+            pattern: None,
+            allow_failure: false,
+            phases: phase::Handlers::new(),
+            overlay: Overlay::default(),
+            before: vec![],
+            after: vec![],
+            default_metadata: None,
         }
     }
 
     /// Associate a handler with this rule.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rule declares phases.
     pub fn handler<H>(mut self, handler: H) -> Builder
     where H: Handle<Bind> + Sync + Send + 'static {
-        self.handler = Arc::new(handler);
+        if !self.phases.is_empty() {
+            panic!("`{}` declares phases, so it can't also have a handler", self.name);
+        }
+
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Add a handler to one of the rule's phases.
+    ///
+    /// The handlers of each phase run in the order they're added, and
+    /// the phases in their order, followed by the site's passes of the
+    /// same phase. The order of the phases that items reach is checked
+    /// only for rules that declare phases.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rule has a `handler`.
+    pub fn phase<H>(mut self, phase: Phase, handler: H) -> Builder
+    where H: Handle<Bind> + Sync + Send + 'static {
+        if self.handler.is_some() {
+            panic!("`{}` has a handler, so it can't also declare phases", self.name);
+        }
+
+        self.phases.entry(phase).or_insert_with(Vec::new).push(Arc::new(handler));
+        self
+    }
+
+    /// Run a handler right before the rule's handler, e.g. to set up a
+    /// resource that its handlers share through the bind's extensions.
+    ///
+    /// These run in the order they're added, and neither the rest of
+    /// them nor the rule's handler run if one fails, though the `after`
+    /// handlers still do.
+    pub fn before<H>(mut self, handler: H) -> Builder
+    where H: Handle<Bind> + Sync + Send + 'static {
+        self.before.push(Arc::new(handler));
+        self
+    }
+
+    /// Run a handler right after the rule's handler, e.g. to release a
+    /// resource or record metrics about the rule.
+    ///
+    /// These run in the order they're added, even if a `before` handler,
+    /// the rule's handler, or another `after` handler fails, in which
+    /// case the first error is the one that's returned.
+    pub fn after<H>(mut self, handler: H) -> Builder
+    where H: Handle<Bind> + Sync + Send + 'static {
+        self.after.push(Arc::new(handler));
+        self
+    }
+
+    /// Give each of the rule's items metadata that its front matter can
+    /// override, e.g. a `layout`.
+    ///
+    /// This is merged under the metadata that `item::parse_metadata`
+    /// parses, including for items without front matter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the metadata isn't a table.
+    pub fn default_metadata(mut self, metadata: toml::Value) -> Builder {
+        match metadata {
+            toml::Value::Table(table) => self.default_metadata = Some(Arc::new(table)),
+            _ => panic!("the default metadata of `{}` must be a table", self.name),
+        }
+
+        self
+    }
+
+    /// Declare the pattern of input paths that this rule is meant to match.
+    ///
+    /// This doesn't select anything by itself; it lets the build warn
+    /// when the rule matches no files, e.g. due to a typo.
+    pub fn pattern<P>(mut self, pattern: P) -> Builder
+    where P: Pattern + Sync + Send + 'static {
+        self.pattern = Some(Arc::new(pattern));
         self
     }
 
@@ -36,12 +196,110 @@ impl Builder {
         self
     }
 
+    /// Register a dependency on a projection of another rule's bind.
+    ///
+    /// Instead of the dependency's full bind, this rule's bind only
+    /// receives what the extractor returns, through `Bind::projection`.
+    /// This is useful when only aggregate data is needed, such as counts
+    /// or titles, since a bind that's only projected by its dependents
+    /// can be freed as soon as it's finished.
+    pub fn depends_on_projection<D, F>(mut self, dependency: D, extractor: F) -> Builder
+    where D: Into<String>,
+          F: Fn(&Bind) -> serde_json::Value + Sync + Send + 'static {
+        let dependency = dependency.into();
+
+        self.dependencies.insert(dependency.clone());
+        self.projections.insert(dependency, Arc::new(extractor));
+        self
+    }
+
+    /// Mark this rule as non-fatal.
+    ///
+    /// If the rule's handler fails, a warning is printed and dependents
+    /// receive an empty bind instead of the build being aborted.
+    /// Dependents can check `Bind::succeeded` to tell the difference.
+    pub fn allow_failure(mut self) -> Builder {
+        self.allow_failure = true;
+        self
+    }
+
+    /// Write into a subdirectory of the output, relative to which the
+    /// rule's items are routed.
+    pub fn output<P>(mut self, subdirectory: P) -> Builder
+    where P: Into<PathBuf> {
+        self.overlay.output = Some(subdirectory.into());
+        self
+    }
+
+    /// Limit how many items the rule's `bind::each` handles at once.
+    pub fn threads(mut self, threads: usize) -> Builder {
+        self.overlay.threads = Some(threads);
+        self
+    }
+
+    /// Skip input paths that match the pattern in the rule's `bind::select`.
+    pub fn ignore<P>(mut self, pattern: P) -> Builder
+    where P: Pattern + Sync + Send + 'static {
+        self.overlay.ignore = Some(Arc::new(pattern));
+        self
+    }
+
     pub fn build(self) -> Rule {
+        let handler: Arc<Handle<Bind> + Sync + Send> =
+            if !self.phases.is_empty() { Arc::new(phase::Phased { handlers: self.phases }) }
+            else { self.handler.unwrap_or_else(|| Arc::new(util::handle::bind::missing)) };
+
+        let handler: Arc<Handle<Bind> + Sync + Send> =
+            if self.before.is_empty() && self.after.is_empty() { handler }
+            else {
+                Arc::new(Around {
+                    before: self.before,
+                    handler: handler,
+                    after: self.after,
+                })
+            };
+
         Rule {
             name: self.name,
-            handler: self.handler,
+            handler: handler,
             dependencies: self.dependencies,
+            projections: self.projections,
+            pattern: self.pattern,
+            allow_failure: self.allow_failure,
+            overlay: self.overlay,
+            default_metadata: self.default_metadata,
+        }
+    }
+}
+
+/// The handler of a rule with `before` or `after` handlers.
+struct Around {
+    before: Vec<Arc<Handle<Bind> + Sync + Send>>,
+    handler: Arc<Handle<Bind> + Sync + Send>,
+    after: Vec<Arc<Handle<Bind> + Sync + Send>>,
+}
+
+impl Handle<Bind> for Around {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut result =
+            self.before.iter()
+            .map(|handler| handler.handle(bind))
+            .find(|result| result.is_err())
+            .unwrap_or(Ok(()));
+
+        if result.is_ok() {
+            result = self.handler.handle(bind);
+        }
+
+        for handler in &self.after {
+            let after = handler.handle(bind);
+
+            if result.is_ok() {
+                result = after;
+            }
         }
+
+        result
     }
 }
 
@@ -53,6 +311,11 @@ pub struct Rule {
     name: String,
     handler: Arc<Handle<Bind> + Sync + Send>,
     dependencies: HashSet<String>,
+    projections: HashMap<String, Projector>,
+    pattern: Option<Arc<Pattern + Sync + Send>>,
+    allow_failure: bool,
+    overlay: Overlay,
+    default_metadata: Option<Arc<toml::value::Table>>,
 }
 
 impl Rule {
@@ -72,6 +335,99 @@ impl Rule {
     pub fn dependencies(&self) -> &HashSet<String> {
         &self.dependencies
     }
+
+    /// The pattern of input paths that this rule is meant to match, if declared.
+    pub fn pattern(&self) -> Option<&(Pattern + Sync + Send)> {
+        self.pattern.as_ref().map(|pattern| &**pattern)
+    }
+
+    /// The dependencies that this rule only needs a projection of.
+    pub fn projections(&self) -> &HashMap<String, Projector> {
+        &self.projections
+    }
+
+    /// The metadata that the rule's items have unless they set it.
+    pub fn default_metadata(&self) -> Option<&Arc<toml::value::Table>> {
+        self.default_metadata.as_ref()
+    }
+
+    /// The rule's overrides of the site's configuration.
+    pub fn overlay(&self) -> &Overlay {
+        &self.overlay
+    }
+
+    /// Whether a failure of this rule is tolerated.
+    pub fn allows_failure(&self) -> bool {
+        self.allow_failure
+    }
+}
+
+/// Completes the builder of a rule declared in `Diecast.toml`.
+///
+/// The builder already has the rule's name, pattern, and dependencies,
+/// and the table has the preset's options.
+pub type Preset = Arc<Fn(Builder, &RuleTable) -> ::Result<Builder> + Sync + Send>;
+
+/// The presets that rules declared in `Diecast.toml` can use, by name.
+#[derive(Clone)]
+pub struct Presets {
+    presets: HashMap<String, Preset>,
+}
+
+impl Presets {
+    /// The built-in presets.
+    ///
+    /// * `copy`: copy the matched files verbatim
+    pub fn new() -> Presets {
+        let mut presets = Presets {
+            presets: HashMap::new(),
+        };
+
+        presets.insert("copy", |builder: Builder, table: &RuleTable| -> ::Result<Builder> {
+            let pattern = glob::Pattern::new(&table.pattern)?;
+
+            Ok(builder.handler(chain![
+                bind::select(pattern),
+                bind::each(chain![route::identity, item::copy])]))
+        });
+
+        presets
+    }
+
+    /// Add a preset, replacing any with the same name.
+    pub fn insert<N, F>(&mut self, name: N, preset: F)
+    where N: Into<String>,
+          F: Fn(Builder, &RuleTable) -> ::Result<Builder> + Sync + Send + 'static {
+        self.presets.insert(name.into(), Arc::new(preset));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.presets.get(name)
+    }
+
+    /// Build a declared rule with its preset.
+    pub fn build(&self, table: &RuleTable) -> ::Result<Rule> {
+        let preset = match self.get(&table.handler) {
+            Some(preset) => preset,
+            None => {
+                return Err(From::from(format!(
+                    "the rule `{}` in Diecast.toml uses the unknown handler `{}`",
+                    table.name, table.handler)));
+            },
+        };
+
+        let pattern = glob::Pattern::new(&table.pattern).map_err(|e| {
+            format!("the rule `{}` in Diecast.toml has an invalid pattern: {}", table.name, e)
+        })?;
+
+        let mut builder = Rule::named(table.name.clone()).pattern(pattern);
+
+        for dependency in &table.dependencies {
+            builder = builder.depends_on(dependency.clone());
+        }
+
+        Ok(preset(builder, table)?.build())
+    }
 }
 
 impl<'a> Into<String> for &'a Rule {
@@ -79,3 +435,36 @@ impl<'a> Into<String> for &'a Rule {
         self.name.clone()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use configuration::RuleTable;
+    use super::Presets;
+
+    fn table(handler: &str) -> RuleTable {
+        RuleTable {
+            name: String::from("statics"),
+            pattern: String::from("images/**/*"),
+            dependencies: vec![],
+            handler: String::from(handler),
+            options: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn build_with_preset() {
+        let rule = Presets::new().build(&table("copy")).unwrap();
+
+        assert_eq!(rule.name(), "statics");
+        assert!(rule.pattern().is_some());
+    }
+
+    #[test]
+    fn unknown_preset() {
+        let error = Presets::new().build(&table("minify")).err().unwrap();
+
+        assert!(error.to_string().contains("unknown handler `minify`"));
+    }
+}
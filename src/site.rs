@@ -1,13 +1,70 @@
 //! Site generation.
 
 use std::sync::Arc;
+use std::sync::mpsc;
 use std::collections::HashSet;
 use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
+use dependency;
 use job;
-use configuration::Configuration;
-use rule::Rule;
+use configuration::{Configuration, RuleTable};
+use handler::Handle;
+use bind::Bind;
+use manifest::{self, Manifest};
+use model::Model;
+use phase::{self, Phase};
+use rule::{self, Rule};
 use support;
+use util::tombstone;
+
+/// The rules of a site and their dependencies.
+///
+/// Edges point from a dependency to its dependent, and the order is
+/// one in which every rule comes after its dependencies.
+#[derive(Clone, Debug)]
+pub struct Graph {
+    nodes: Vec<String>,
+    edges: Vec<(String, String)>,
+    order: Vec<String>,
+}
+
+impl Graph {
+    /// The names of the rules, sorted.
+    pub fn nodes(&self) -> &[String] {
+        &self.nodes
+    }
+
+    /// The pairs of a dependency and its dependent.
+    pub fn edges(&self) -> &[(String, String)] {
+        &self.edges
+    }
+
+    /// The rules in an order that respects their dependencies.
+    pub fn order(&self) -> &[String] {
+        &self.order
+    }
+
+    /// The rules that a rule depends on.
+    pub fn dependencies_of(&self, name: &str) -> Vec<&str> {
+        self.edges.iter()
+            .filter(|&&(_, ref dependent)| dependent == name)
+            .map(|&(ref dependency, _)| &dependency[..])
+            .collect()
+    }
+
+    /// The rules that depend on a rule.
+    pub fn dependents_of(&self, name: &str) -> Vec<&str> {
+        self.edges.iter()
+            .filter(|&&(ref dependency, _)| dependency == name)
+            .map(|&(_, ref dependent)| &dependent[..])
+            .collect()
+    }
+}
+
+/// A function that runs before or after the site is built.
+pub type Hook = Arc<Fn(&Site) -> ::Result<()> + Sync + Send>;
 
 /// A Site scans the input path to find
 /// files that match the given pattern. It then
@@ -16,15 +73,29 @@ use support;
 pub struct Site {
     configuration: Configuration,
     rules: Vec<Arc<Rule>>,
+    manifest: Manifest,
+    model: Model,
+    passes: phase::Handlers,
+    presets: rule::Presets,
+    before_build: Vec<Hook>,
+    after_build: Vec<Hook>,
 }
 
 impl Site {
-    pub fn new(rules: Vec<Rule>) -> Site {
+    /// Create a site from its rules and `Diecast.toml`.
+    ///
+    /// Fails if the configuration is invalid, or if a rule depends on
+    /// one that doesn't exist.
+    pub fn new(rules: Vec<Rule>) -> ::Result<Site> {
+        let configuration = Configuration::new()?;
+
         let mut site_rules = vec![];
 
+        // rules may depend on those declared in Diecast.toml
         let names =
             rules.iter()
             .map(|r| String::from(r.name()))
+            .chain(configuration.rules().into_iter().map(|table| table.name))
             .collect::<HashSet<_>>();
 
         for rule in rules {
@@ -33,44 +104,422 @@ impl Site {
                     rule.dependencies().difference(&names).collect();
 
                 if !diff.is_empty() {
-                    println!("`{}` depends on unregistered rule(s) `{:?}`", rule.name(), diff);
-                    ::std::process::exit(1);
+                    return Err(From::from(
+                        format!("`{}` depends on unregistered rule(s) `{:?}`", rule.name(), diff)));
                 }
             }
 
             site_rules.push(Arc::new(rule));
         }
 
-        Site {
-            configuration: Configuration::new(),
+        Ok(Site {
+            configuration: configuration,
             rules: site_rules,
-        }
+            manifest: Manifest::new(),
+            model: Model::new(),
+            passes: phase::Handlers::new(),
+            presets: rule::Presets::new(),
+            before_build: vec![],
+            after_build: vec![],
+        })
+    }
+
+    /// Run a function before every build, e.g. to generate a file in the
+    /// input directory or start a helper process.
+    ///
+    /// The build is stopped if it fails. Hooks run in the order they're added.
+    pub fn before_build<F>(&mut self, hook: F)
+    where F: Fn(&Site) -> ::Result<()> + Sync + Send + 'static {
+        self.before_build.push(Arc::new(hook));
+    }
+
+    /// Run a function after every successful build, e.g. to ping search
+    /// engines.
+    ///
+    /// The site's manifest and model are those of the build that just
+    /// finished. Hooks run in the order they're added.
+    pub fn after_build<F>(&mut self, hook: F)
+    where F: Fn(&Site) -> ::Result<()> + Sync + Send + 'static {
+        self.after_build.push(Arc::new(hook));
     }
 
     pub fn build(&mut self) -> ::Result<()> {
-        self.clean()?;
+        for hook in &self.before_build {
+            hook(self)?;
+        }
+
+        self.generate()?;
 
-        let mut scheduler = job::Scheduler::new(Arc::new(self.configuration.clone()));
+        for hook in &self.after_build {
+            hook(self)?;
+        }
+
+        Ok(())
+    }
 
-        println!("building from {:?}", self.configuration.input);
+    fn generate(&mut self) -> ::Result<()> {
+        self.configuration.reporter.report(&job::Event::Building { input: &self.configuration.input });
 
         if !&self.configuration.input.exists() {
-            println!("the input directory `{:?}` does not exist!",
-                     self.configuration.input);
-            ::std::process::exit(1);
+            return Err(From::from(
+                format!("the input directory `{:?}` does not exist!",
+                        self.configuration.input)));
+        }
+
+        let output = self.configuration.output.clone();
+        let staging = Site::sibling(&output, "staging");
+
+        // fail before doing any work if the output can't be written
+        Site::probe(&staging)?;
+
+        // build into the staging directory, reusing unchanged
+        // files from the live output
+        let mut configuration = self.configuration.clone();
+        configuration.output = staging.clone();
+
+        if output.exists() {
+            configuration.previous = Some(output.clone());
         }
 
+        let configuration = Arc::new(configuration);
+        let mut scheduler = job::Scheduler::new(configuration.clone());
+
         scheduler.update_paths();
 
-        for rule in &self.rules {
+        let rules = match self.declared_rules() {
+            Ok(declared) => self.rules.iter().cloned().chain(declared).collect::<Vec<_>>(),
+            Err(e) => {
+                fs::remove_dir_all(&staging)?;
+                return Err(e);
+            },
+        };
+
+        if let Err(e) = self.check_unused(&rules, scheduler.paths()) {
+            fs::remove_dir_all(&staging)?;
+            return Err(e);
+        }
+
+        scheduler.set_passes(Arc::new(self.passes.clone()));
+
+        for rule in &rules {
            // FIXME: this just seems weird re: strings
            scheduler.add(rule.clone());
         }
 
-        // create the output directory
-        support::mkdir_p(&self.configuration.output).unwrap();
+        if let Err(e) = scheduler.build() {
+            // the previous output stays live
+            fs::remove_dir_all(&staging)?;
+            return Err(e);
+        }
+
+        let mut manifest = scheduler.manifest().clone();
+
+        if let Err(e) = self.report_removed(&configuration, &mut manifest) {
+            fs::remove_dir_all(&staging)?;
+            return Err(e);
+        }
+
+        if configuration.deterministic {
+            manifest.sort();
+        }
+
+        if configuration.output_manifest {
+            if let Err(e) = manifest.save(staging.join(manifest::OUTPUT_NAME)) {
+                fs::remove_dir_all(&staging)?;
+                return Err(e);
+            }
+        }
+
+        self.swap(&staging)?;
+
+        let unchanged = scheduler.unchanged();
+
+        if unchanged > 0 {
+            let message = format!("{} file(s) unchanged", unchanged);
+            self.configuration.reporter.report(&job::Event::Note { message: &message });
+        }
+
+        self.manifest = manifest;
+        self.model = scheduler.model().clone();
+
+        if let Some(ref path) = self.configuration.manifest {
+            self.manifest.save(path)?;
+        }
 
-        scheduler.build()
+        Ok(())
+    }
+
+    /// List the pages that the previous build's manifest has but this
+    /// build doesn't, and write their tombstones if configured to.
+    fn report_removed(&self, configuration: &Configuration, manifest: &mut Manifest) -> ::Result<()> {
+        let previous = match self.configuration.manifest {
+            Some(ref path) if path.exists() => Manifest::load(path)?,
+            _ => return Ok(()),
+        };
+
+        let diff = previous.diff(manifest);
+
+        // tombstones are removed every build, as they're written afterwards
+        for entry in diff.removed.iter().filter(|e| !tombstone::is_tombstone(configuration, e)) {
+            let message = format!("removed {}", entry.url());
+            configuration.reporter.report(&job::Event::Note { message: &message });
+        }
+
+        for &(ref from, ref to) in &diff.moved {
+            let message = format!("moved {} to {}", from.url(), to.url());
+            configuration.reporter.report(&job::Event::Note { message: &message });
+        }
+
+        if configuration.tombstones {
+            let buried = tombstone::bury(configuration, &diff, manifest)?;
+
+            if buried > 0 {
+                let message = format!("wrote {} tombstone(s)", buried);
+                configuration.reporter.report(&job::Event::Note { message: &message });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add a cross-cutting pass to a phase of every rule that declares its phases.
+    ///
+    /// The pass runs after each rule's own handlers of the phase, e.g.
+    /// `site.pass(Phase::Render, validate_html)`.
+    pub fn pass<H>(&mut self, phase: Phase, handler: H)
+    where H: Handle<Bind> + Sync + Send + 'static {
+        self.passes.entry(phase).or_insert_with(Vec::new).push(Arc::new(handler));
+    }
+
+    /// Receive the events of the site's builds, e.g. to show progress in an editor.
+    ///
+    /// Events are sent as they happen, from the threads that process
+    /// the rules, and are still reported by the configured reporter.
+    /// Each call adds another subscriber.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<job::BuildEvent> {
+        let (sender, receiver) = mpsc::channel();
+        let inner = self.configuration.reporter.clone();
+
+        self.configuration.reporter = Arc::new(job::reporter::Subscriber::new(sender, inner));
+
+        receiver
+    }
+
+    /// The graph of the site's rules, including those declared in `Diecast.toml`.
+    ///
+    /// This fails if the rules' dependencies form a cycle.
+    pub fn graph(&self) -> ::Result<Graph> {
+        let declared = self.declared_rules()?;
+        let mut graph = dependency::Graph::new();
+        let mut edges = vec![];
+
+        for rule in self.rules.iter().chain(declared.iter()) {
+            graph.add_node(String::from(rule.name()));
+
+            for dependency in rule.dependencies() {
+                graph.add_edge(dependency.clone(), String::from(rule.name()));
+                edges.push((dependency.clone(), String::from(rule.name())));
+            }
+        }
+
+        edges.sort();
+
+        let order = graph.resolve_all()?.into_iter().collect();
+
+        Ok(Graph {
+            nodes: graph.nodes().cloned().collect(),
+            edges: edges,
+            order: order,
+        })
+    }
+
+    /// Add a preset that rules declared in `Diecast.toml` can use.
+    ///
+    /// This replaces a built-in preset of the same name.
+    pub fn preset<N, F>(&mut self, name: N, preset: F)
+    where N: Into<String>,
+          F: Fn(rule::Builder, &RuleTable) -> ::Result<rule::Builder> + Sync + Send + 'static {
+        self.presets.insert(name, preset);
+    }
+
+    /// Build the rules declared in `Diecast.toml`.
+    ///
+    /// A rule given to `Site::new` overrides a declared rule of the same name.
+    fn declared_rules(&self) -> ::Result<Vec<Arc<Rule>>> {
+        let tables = self.configuration.rules();
+
+        let names =
+            self.rules.iter()
+            .map(|r| String::from(r.name()))
+            .chain(tables.iter().map(|table| table.name.clone()))
+            .collect::<HashSet<_>>();
+
+        let mut declared = vec![];
+
+        for table in tables {
+            if self.rules.iter().any(|rule| rule.name() == table.name) {
+                continue;
+            }
+
+            let rule = self.presets.build(&table)?;
+
+            let missing =
+                rule.dependencies().iter()
+                .filter(|dependency| !names.contains(*dependency))
+                .collect::<Vec<_>>();
+
+            if !missing.is_empty() {
+                return Err(From::from(format!(
+                    "`{}` depends on unregistered rule(s) `{:?}`", rule.name(), missing)));
+            }
+
+            declared.push(Arc::new(rule));
+        }
+
+        Ok(declared)
+    }
+
+    /// The files generated by the last build.
+    ///
+    /// Deploy procedures can compare this against the manifest
+    /// of what was last deployed to only upload what changed.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// The rules and items of the last build.
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Report rules whose declared patterns match none of the input paths.
+    ///
+    /// A pattern that only matches ignored paths is reported as shadowed
+    /// by the ignore configuration. This is an error if `strict_unused`
+    /// is set.
+    fn check_unused(&self, rules: &[Arc<Rule>], paths: &[PathBuf]) -> ::Result<()> {
+        let input = &self.configuration.input;
+
+        let relative = |path: &Path| -> PathBuf {
+            path.strip_prefix(input).map(Path::to_path_buf).unwrap_or_else(|_| path.to_path_buf())
+        };
+
+        let mut unused = vec![];
+
+        for rule in rules {
+            let pattern = match rule.pattern() {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+
+            if paths.iter().any(|path| pattern.matches(&relative(path))) {
+                continue;
+            }
+
+            let shadowed =
+                self.configuration.walk_input()
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .any(|entry| pattern.matches(&relative(entry.path())));
+
+            let message =
+                if shadowed {
+                    format!("the pattern of `{}` only matches ignored files", rule.name())
+                } else {
+                    format!("the pattern of `{}` matches no files", rule.name())
+                };
+
+            self.configuration.reporter.report(&job::Event::Warning { message: &message });
+
+            unused.push(message);
+        }
+
+        if self.configuration.strict_unused && !unused.is_empty() {
+            return Err(From::from(
+                format!("{} rule(s) are unused:\n  {}", unused.len(), unused.join("\n  "))));
+        }
+
+        Ok(())
+    }
+
+    /// Prepare the staging directory and make sure that it's writable.
+    ///
+    /// Read-only mounts and missing permissions would otherwise only
+    /// surface as a failure to write some item in the middle of the build.
+    fn probe(staging: &Path) -> ::Result<()> {
+        let unwritable = |e: io::Error| -> ::Error {
+            From::from(format!(
+                "cannot write to {}: {}\n\
+                 make sure that the output directory isn't on a read-only file system \
+                 and that you're allowed to write to it and its parent, or choose \
+                 another output directory with `diecast.output` or DIECAST_OUTPUT",
+                staging.display(), e))
+        };
+
+        // left behind by an interrupted build
+        if staging.exists() {
+            fs::remove_dir_all(staging).map_err(&unwritable)?;
+        }
+
+        support::mkdir_p(staging).map_err(&unwritable)?;
+
+        let probe = staging.join(".diecast-probe");
+
+        fs::write(&probe, b"").map_err(&unwritable)?;
+        fs::remove_file(&probe).map_err(&unwritable)?;
+
+        Ok(())
+    }
+
+    /// A hidden sibling of the output directory, e.g. `.output.staging`.
+    fn sibling(output: &Path, purpose: &str) -> PathBuf {
+        let name =
+            output.file_name()
+            .map_or_else(|| String::from("output"), |n| n.to_string_lossy().into_owned());
+
+        output.with_file_name(format!(".{}.{}", name, purpose))
+    }
+
+    /// Replace the output directory with the successfully built staging directory.
+    ///
+    /// Hidden top-level entries of the previous output, such as a `.git`
+    /// directory, are carried over if `ignore_hidden` is set.
+    fn swap(&self, staging: &Path) -> ::Result<()> {
+        let output = &self.configuration.output;
+
+        if !output.exists() {
+            fs::rename(staging, output)?;
+            return Ok(());
+        }
+
+        if self.configuration.ignore_hidden {
+            for entry in fs::read_dir(output)? {
+                let entry = entry?;
+
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    fs::rename(entry.path(), staging.join(entry.file_name()))?;
+                }
+            }
+        }
+
+        let previous = Site::sibling(output, "previous");
+
+        if previous.exists() {
+            fs::remove_dir_all(&previous)?;
+        }
+
+        fs::rename(output, &previous)?;
+
+        // put the live output back rather than leave the site without one
+        if let Err(e) = fs::rename(staging, output) {
+            fs::rename(&previous, output)?;
+            return Err(From::from(e));
+        }
+
+        fs::remove_dir_all(&previous)?;
+
+        Ok(())
     }
 
     pub fn configuration(&self) -> &Configuration {
@@ -92,3 +541,68 @@ impl Site {
             .map_err(|e| Box::new(e) as ::Error)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use configuration::Configuration;
+    use manifest::Manifest;
+    use model::Model;
+    use phase;
+    use rule::{self, Rule};
+    use super::Site;
+
+    fn site(rules: Vec<Rule>, toml: &str) -> Site {
+        Site {
+            configuration: Configuration::with_toml(toml),
+            rules: rules.into_iter().map(Arc::new).collect(),
+            manifest: Manifest::new(),
+            model: Model::new(),
+            passes: phase::Handlers::new(),
+            presets: rule::Presets::new(),
+            before_build: vec![],
+            after_build: vec![],
+        }
+    }
+
+    static RULES: &'static str =
+        "[[rule]]\n\
+         name = \"statics\"\n\
+         pattern = \"images/**/*\"\n\
+         handler = \"copy\"\n\
+         [[rule]]\n\
+         name = \"fonts\"\n\
+         pattern = \"fonts/*\"\n\
+         handler = \"copy\"\n\
+         dependencies = [\"statics\"]\n";
+
+    #[test]
+    fn build_declared_rules() {
+        let declared = site(vec![], RULES).declared_rules().unwrap();
+        let names = declared.iter().map(|rule| rule.name()).collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["statics", "fonts"]);
+    }
+
+    #[test]
+    fn override_declared_rules() {
+        let site = site(vec![Rule::named("statics").build()], RULES);
+        let declared = site.declared_rules().unwrap();
+        let names = declared.iter().map(|rule| rule.name()).collect::<Vec<_>>();
+
+        assert_eq!(names, vec!["fonts"]);
+    }
+
+    #[test]
+    fn declared_rule_with_unknown_dependency() {
+        let toml =
+            "[[rule]]\n\
+             name = \"fonts\"\n\
+             pattern = \"fonts/*\"\n\
+             handler = \"copy\"\n\
+             dependencies = [\"statics\"]\n";
+
+        assert!(site(vec![], toml).declared_rules().is_err());
+    }
+}
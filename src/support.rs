@@ -1,6 +1,11 @@
+use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
-use std::io;
+use std::path::{Path, Component};
+use std::io::{self, Read};
+use std::process;
+use std::time::Duration;
+
+use toml;
 
 // TODO
 // remove this and use create_dir_all?
@@ -36,3 +41,330 @@ pub fn slugify(s: &str) -> String {
     })
     .collect()
 }
+
+/// A stable 64-bit FNV-1a hash of the given bytes, as hex.
+///
+/// This isn't cryptographic; it's only meant to tell contents apart
+/// between builds, so it must not change across platforms or releases.
+pub fn hash(bytes: &[u8]) -> String {
+    format!("{:016x}", hash_u64(bytes))
+}
+
+/// The same hash as `hash`, as a number.
+pub fn hash_u64(bytes: &[u8]) -> u64 {
+    fnv(FNV_OFFSET, bytes)
+}
+
+/// The same hash as `hash`, streamed from a reader.
+///
+/// This avoids loading large files into memory.
+pub fn hash_reader<R>(mut reader: R) -> io::Result<String>
+where R: Read {
+    let mut hash = FNV_OFFSET;
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => hash = fnv(hash, &buffer[..read]),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(format!("{:016x}", hash))
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+fn fnv(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash
+}
+
+/// The site-relative URL of a path in the output directory.
+///
+/// `index.html` files are represented by their directory,
+/// e.g. `posts/hello/index.html` becomes `/posts/hello/`.
+pub fn url(target: &Path) -> String {
+    let mut url = String::new();
+
+    for component in target.components() {
+        if let Component::Normal(part) = component {
+            url.push('/');
+            url.push_str(&part.to_string_lossy());
+        }
+    }
+
+    if url.ends_with("/index.html") {
+        let len = url.len() - "index.html".len();
+        url.truncate(len);
+    }
+
+    if url.is_empty() {
+        url.push('/');
+    }
+
+    url
+}
+
+/// Merge one TOML table into another.
+///
+/// Nested tables are merged recursively. Otherwise, a key present in
+/// both tables keeps the value from `base` unless `overwrite` is set.
+pub fn merge(base: &mut toml::value::Table, overlay: toml::value::Table, overwrite: bool) {
+    for (key, value) in overlay {
+        let merged = match (base.get_mut(&key), value) {
+            (Some(&mut toml::Value::Table(ref mut existing)), toml::Value::Table(nested)) => {
+                merge(existing, nested, overwrite);
+                None
+            },
+            (Some(_), value) => if overwrite { Some(value) } else { None },
+            (None, value) => Some(value),
+        };
+
+        if let Some(value) = merged {
+            base.insert(key, value);
+        }
+    }
+}
+
+/// How a service tells that it's ready to be used.
+enum Readiness {
+    /// As soon as it's started.
+    Immediately,
+
+    /// Once it accepts connections on a local port.
+    Port(u16),
+
+    /// Once the check returns true, e.g. after a request to it succeeds.
+    Check(Box<Fn() -> bool + Sync + Send>),
+}
+
+impl Readiness {
+    fn is_ready(&self) -> bool {
+        use std::net::TcpStream;
+
+        match *self {
+            Readiness::Immediately => true,
+            Readiness::Port(port) => TcpStream::connect(("127.0.0.1", port)).is_ok(),
+            Readiness::Check(ref check) => check(),
+        }
+    }
+}
+
+/// Starts a `Service`.
+#[must_use]
+pub struct ServiceBuilder {
+    name: String,
+    command: process::Command,
+    readiness: Readiness,
+    timeout: Duration,
+}
+
+impl ServiceBuilder {
+    pub fn arg<S>(mut self, arg: S) -> ServiceBuilder
+    where S: AsRef<OsStr> {
+        self.command.arg(arg);
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> ServiceBuilder
+    where I: IntoIterator<Item = S>, S: AsRef<OsStr> {
+        self.command.args(args);
+        self
+    }
+
+    pub fn env<K, V>(mut self, key: K, value: V) -> ServiceBuilder
+    where K: AsRef<OsStr>, V: AsRef<OsStr> {
+        self.command.env(key, value);
+        self
+    }
+
+    pub fn current_dir<P>(mut self, directory: P) -> ServiceBuilder
+    where P: AsRef<Path> {
+        self.command.current_dir(directory);
+        self
+    }
+
+    /// Wait until the service accepts connections on a local port.
+    pub fn ready_on_port(mut self, port: u16) -> ServiceBuilder {
+        self.readiness = Readiness::Port(port);
+        self
+    }
+
+    /// Wait until the check returns true.
+    pub fn ready_when<F>(mut self, check: F) -> ServiceBuilder
+    where F: Fn() -> bool + Sync + Send + 'static {
+        self.readiness = Readiness::Check(Box::new(check));
+        self
+    }
+
+    /// How long to wait for the service to be ready. Ten seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> ServiceBuilder {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Start the service and wait until it's ready.
+    ///
+    /// This fails if the service can't be started, exits before it's
+    /// ready, or isn't ready in time, in which case it's stopped.
+    pub fn start(mut self) -> ::Result<Service> {
+        use std::thread;
+        use std::time::Instant;
+
+        let child =
+            self.command.spawn()
+            .map_err(|e| format!("could not start the service `{}`: {}", self.name, e))?;
+
+        let mut service = Service {
+            name: self.name,
+            child: child,
+        };
+
+        let started = Instant::now();
+
+        while !self.readiness.is_ready() {
+            if let Some(status) = service.child.try_wait()? {
+                return Err(From::from(
+                    format!("the service `{}` exited before it was ready: {}", service.name, status)));
+            }
+
+            if started.elapsed() > self.timeout {
+                return Err(From::from(
+                    format!("the service `{}` wasn't ready after {:?}",
+                            service.name, self.timeout)));
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+
+        Ok(service)
+    }
+}
+
+/// A helper process, such as a server that handlers make requests to.
+///
+/// The process is stopped when the `Service` is dropped, including when
+/// the build fails or panics, so it doesn't outlive the build, e.g.
+///
+/// ```ignore
+/// let _server =
+///     Service::new("python3")
+///     .args(&["-m", "http.server", "8000"])
+///     .ready_on_port(8000)
+///     .start()?;
+/// ```
+///
+/// To share it with handlers, keep it in an `Arc` in a bind's extensions,
+/// e.g. from a rule's `before` handler.
+pub struct Service {
+    name: String,
+    child: process::Child,
+}
+
+impl Service {
+    pub fn new<S>(program: S) -> ServiceBuilder
+    where S: AsRef<OsStr> {
+        let name = program.as_ref().to_string_lossy().into_owned();
+
+        ServiceBuilder {
+            name: name,
+            command: process::Command::new(program),
+            readiness: Readiness::Immediately,
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The process's id.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Stop the service now, rather than when it's dropped.
+    pub fn stop(mut self) -> ::Result<()> {
+        self.kill()
+    }
+
+    fn kill(&mut self) -> ::Result<()> {
+        // it may have already exited
+        if self.child.try_wait()?.is_none() {
+            self.child.kill()?;
+            self.child.wait()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        let _ = self.kill();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::process;
+    use std::time::Duration;
+
+    use super::Service;
+
+    fn is_running(id: u32) -> bool {
+        process::Command::new("kill")
+            .arg("-0")
+            .arg(id.to_string())
+            .stderr(process::Stdio::null())
+            .status()
+            .unwrap()
+            .success()
+    }
+
+    #[test]
+    fn stop_when_dropped() {
+        let service = Service::new("sleep").arg("5").start().unwrap();
+        let id = service.id();
+
+        assert_eq!(service.name(), "sleep");
+        assert!(is_running(id));
+
+        drop(service);
+
+        assert!(!is_running(id));
+    }
+
+    #[test]
+    fn fail_to_start() {
+        let error = Service::new("diecast-missing-program").start().err().unwrap();
+
+        assert!(error.to_string().contains("could not start the service"));
+    }
+
+    #[test]
+    fn fail_when_exited_before_ready() {
+        let error = Service::new("true").ready_when(|| false).start().err().unwrap();
+
+        assert!(error.to_string().contains("exited before it was ready"));
+    }
+
+    #[test]
+    fn fail_when_not_ready_in_time() {
+        let error =
+            Service::new("sleep").arg("5")
+            .ready_when(|| false)
+            .timeout(Duration::from_millis(100))
+            .start()
+            .err().unwrap();
+
+        assert!(error.to_string().contains("wasn't ready after 100ms"));
+    }
+}
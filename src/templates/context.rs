@@ -0,0 +1,127 @@
+//! Template contexts.
+//!
+//! A `Context` is built from anything that implements `Serialize`,
+//! so template engines don't each need their own value type.
+//!
+//! ```ignore
+//! fn post_template(item: &Item) -> diecast::Result<Context> {
+//!     Context::from_item(item)
+//!         .insert("tags", &tags_of(item))
+//! }
+//! ```
+
+use serde::{Serialize, Serializer};
+use serde_json::{self, Map, Value};
+
+use item::{self, Item};
+use util::{data, dates, i18n};
+
+/// The data made available to a template.
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+    values: Map<String, Value>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            values: Map::new(),
+        }
+    }
+
+    /// A context populated from the item.
+    ///
+    /// This contains the `site` table of `Diecast.toml`, the `body` if it's
+    /// text, the `url` and `permalink` if the item is written, the `date` as RFC 3339
+    /// and the `formatted_date` with the site's `date_format` if the item
+    /// has a `Date`, the `lang` and the URLs of its `translations` by
+    /// language if it has a `Language`, the `data` files, the
+    /// `metadata` if the item has `Metadata`, and the item's and its
+    /// bind's values as `meta` and `rule_meta`.
+    pub fn from_item(item: &Item) -> Context {
+        let mut context = Context::new();
+
+        if let Ok(site) = serde_json::to_value(item.bind().configuration.site()) {
+            context.values.insert(String::from("site"), site);
+        }
+
+        if let Some(body) = item.body.as_str() {
+            context.values.insert(String::from("body"), Value::String(String::from(body)));
+        }
+
+        if let Some(url) = item.url() {
+            context.values.insert(String::from("url"), Value::String(url));
+        }
+
+        if let Some(permalink) = item.permalink() {
+            context.values.insert(String::from("permalink"), Value::String(permalink));
+        }
+
+        if let Some(date) = item.extensions.get::<item::Date>() {
+            context.values.insert(
+                String::from("date"),
+                Value::String(date.to_rfc3339()));
+
+            context.values.insert(
+                String::from("formatted_date"),
+                Value::String(dates::format(date, &item.bind().configuration)));
+        }
+
+        if let Some(language) = item.extensions.get::<i18n::Language>() {
+            context.values.insert(String::from("lang"), Value::String(language.clone()));
+
+            if let Some(translations) = item.extensions.get::<i18n::Translations>() {
+                if let Ok(translations) = serde_json::to_value(translations) {
+                    context.values.insert(String::from("translations"), translations);
+                }
+            }
+        }
+
+        let files = item.bind().with_ext(|extensions| extensions.get::<data::Data>().cloned());
+
+        if let Some(files) = files {
+            context.values.insert(String::from("data"), files.tree().clone());
+        }
+
+        if let Some(metadata) = item.extensions.get::<item::Metadata>() {
+            if let Ok(metadata) = serde_json::to_value(metadata) {
+                context.values.insert(String::from("metadata"), metadata);
+            }
+        }
+
+        context.values.insert(String::from("meta"), Value::Object(item.meta.clone()));
+        context.values.insert(String::from("rule_meta"), Value::Object(item.bind().meta()));
+
+        context
+    }
+
+    /// Insert a value into the context, replacing any previous value.
+    pub fn insert<K, V: ?Sized>(mut self, key: K, value: &V) -> ::Result<Context>
+    where K: Into<String>, V: Serialize {
+        self.values.insert(key.into(), serde_json::to_value(value)?);
+        Ok(self)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.values.get(key)
+    }
+
+    pub fn into_value(self) -> Value {
+        Value::Object(self.values)
+    }
+}
+
+impl Serialize for Context {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        self.values.serialize(serializer)
+    }
+}
+
+/// A context populated from the item.
+///
+/// This can be passed directly to template handlers that take
+/// a context function.
+pub fn item(item: &Item) -> ::Result<Context> {
+    Ok(Context::from_item(item))
+}
@@ -0,0 +1,10 @@
+//! Template engine support.
+//!
+//! Each engine lives behind a cargo feature of the same name.
+
+pub use self::context::Context;
+
+pub mod context;
+
+#[cfg(feature = "tera")]
+pub mod tera;
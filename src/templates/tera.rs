@@ -0,0 +1,194 @@
+//! Tera templating.
+//!
+//! Templates are loaded from the items of a dependency rule,
+//! which must have read them, and are registered under their
+//! file stem, e.g. `layouts/post.html` becomes `post`.
+//!
+//! ```ignore
+//! let posts =
+//!     Rule::named("posts")
+//!     .depends_on(&templates)
+//!     .handler(chain![
+//!         bind::select(glob!("posts/*.md")),
+//!         tera::register(&templates),
+//!         bind::each(chain![
+//!             item::read,
+//!             route::pretty,
+//!             tera::render_template("post", context::item),
+//!             item::write])])
+//!     .build();
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tera::{self, Tera};
+use toml;
+use typemap;
+
+use bind::Bind;
+use item::{self, Item};
+use handler::Handle;
+use phase::{self, Phase};
+use templates::Context;
+use util::dates;
+use util::i18n::Strings;
+use util::links::Routes;
+
+/// The template registry available to a bind's items.
+pub struct Templates;
+
+impl typemap::Key for Templates {
+    type Value = Arc<Tera>;
+}
+
+pub struct Register {
+    dependency: String,
+}
+
+impl Handle<Bind> for Register {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut tera = Tera::default();
+
+        {
+            let templates = bind.dependency(&self.dependency)?;
+
+            for item in templates.items() {
+                let name =
+                    item.route().reading()
+                    .and_then(|path| path.file_stem())
+                    .and_then(|stem| stem.to_str());
+
+                if let Some(name) = name {
+                    tera.add_raw_template(name, item.text()?)?;
+                }
+            }
+        }
+
+        let routes = bind.with_ext(|extensions| extensions.get::<Routes>().cloned());
+
+        if let Some(routes) = routes {
+            tera.register_function("link", move |args: &HashMap<String, tera::Value>| {
+                let path =
+                    args.get("path")
+                    .and_then(tera::Value::as_str)
+                    .ok_or_else(|| tera::Error::msg("`link` requires a `path` argument"))?;
+
+                routes.resolve(path)
+                    .map(tera::Value::String)
+                    .map_err(|e| tera::Error::msg(e.to_string()))
+            });
+        }
+
+        let strings = bind.with_ext(|extensions| extensions.get::<Strings>().cloned());
+
+        if let Some(strings) = strings {
+            tera.register_function("t", move |args: &HashMap<String, tera::Value>| {
+                let key =
+                    args.get("key")
+                    .and_then(tera::Value::as_str)
+                    .ok_or_else(|| tera::Error::msg("`t` requires a `key` argument"))?;
+
+                let language =
+                    args.get("lang")
+                    .and_then(tera::Value::as_str)
+                    .ok_or_else(|| tera::Error::msg("`t` requires a `lang` argument"))?;
+
+                strings.translate(language, key)
+                    .map(|string| tera::Value::String(String::from(string)))
+                    .ok_or_else(|| {
+                        tera::Error::msg(format!("there is no `{}` string for `{}`", key, language))
+                    })
+            });
+        }
+
+        let configuration = bind.configuration.clone();
+
+        tera.register_filter("format_date", move |value: &tera::Value,
+                                                  args: &HashMap<String, tera::Value>| {
+            let date =
+                value.as_str()
+                .ok_or_else(|| tera::Error::msg("`format_date` requires a date string"))
+                .and_then(|date| {
+                    dates::parse(date, &configuration.timezone)
+                        .map_err(|e| tera::Error::msg(e.to_string()))
+                })?;
+
+            let formatted = match args.get("format").and_then(tera::Value::as_str) {
+                Some(format) => dates::format_with(&date, &configuration, format),
+                None => dates::format(&date, &configuration),
+            };
+
+            Ok(tera::Value::String(formatted))
+        });
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Templates>(Arc::new(tera)));
+
+        Ok(())
+    }
+}
+
+/// Load the templates of the given dependency into the bind.
+///
+/// If the bind's links were indexed beforehand, templates can
+/// call `link(path="posts/hello.md")`, and if its translated strings
+/// were loaded, `t(key="read_more", lang=lang)`. Dates can be formatted
+/// with the `format_date` filter, which takes an optional `format`.
+#[inline]
+pub fn register<D>(dependency: D) -> Register
+where D: Into<String> {
+    Register {
+        dependency: dependency.into(),
+    }
+}
+
+pub struct RenderTemplate<C>
+where C: Fn(&Item) -> ::Result<Context>, C: Sync + Send + 'static {
+    name: String,
+    context: C,
+}
+
+impl<C> Handle<Item> for RenderTemplate<C>
+where C: Fn(&Item) -> ::Result<Context>, C: Sync + Send + 'static {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        phase::enter(item, Phase::Render)?;
+
+        let tera = item.bind().with_ext(|extensions| extensions.get::<Templates>().cloned());
+
+        let tera = match tera {
+            Some(tera) => tera,
+            None => {
+                return Err(From::from(
+                    format!("no templates were registered for `{}`",
+                            item.bind().name)));
+            },
+        };
+
+        let context = tera::Context::from_serialize((self.context)(item)?)?;
+
+        let name =
+            item.extensions.get::<item::Metadata>()
+            .and_then(|metadata| metadata.get("template"))
+            .and_then(toml::Value::as_str)
+            .map_or_else(|| self.name.clone(), String::from);
+
+        item.body = tera.render(&name, &context)?.into();
+
+        Ok(())
+    }
+}
+
+/// Render the item with the named template.
+///
+/// An item's `template` metadata names a different template for it.
+/// The closure builds the template context from the item;
+/// `templates::context::item` provides the default one.
+#[inline]
+pub fn render_template<N, C>(name: N, context: C) -> RenderTemplate<C>
+where N: Into<String>,
+      C: Fn(&Item) -> ::Result<Context>, C: Sync + Send + 'static {
+    RenderTemplate {
+        name: name.into(),
+        context: context,
+    }
+}
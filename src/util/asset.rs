@@ -0,0 +1,96 @@
+//! Asset fingerprinting.
+//!
+//! Fingerprinting adds a hash of an asset's contents to its file name,
+//! so that it can be cached indefinitely and still be busted whenever
+//! it changes.
+//!
+//! ```ignore
+//! let styles =
+//!     Rule::named("styles")
+//!     .handler(chain![
+//!         bind::select(glob!("css/*.css")),
+//!         bind::each(chain![
+//!             item::read,
+//!             route::identity,
+//!             asset::fingerprint,
+//!             item::write])])
+//!     .build();
+//!
+//! // in a dependent's handler
+//! let screen = asset::asset_url(item.bind().dependency("styles")?, "css/screen.css");
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use typemap;
+
+use bind::Bind;
+use item::Item;
+use support;
+
+/// The fingerprinted URL of each asset in a bind, keyed by its logical URL.
+pub struct Fingerprints;
+
+impl typemap::Key for Fingerprints {
+    type Value = BTreeMap<String, String>;
+}
+
+/// Insert the hash into the path's file name, before the extension.
+fn fingerprinted(path: &Path, hash: &str) -> PathBuf {
+    let stem =
+        path.file_stem()
+        .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, hash, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, hash),
+    };
+
+    path.with_file_name(name)
+}
+
+/// Handle<Item> that adds a hash of the body to the target's file name.
+///
+/// For example, `css/screen.css` becomes `css/screen.0123abcd.css`.
+/// The mapping is recorded in the bind's `Fingerprints`, so this must
+/// run after the item has been read and routed, and before it's written.
+pub fn fingerprint(item: &mut Item) -> ::Result<()> {
+    let original = match item.output_path() {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+
+    let hash = support::hash(item.body.as_bytes());
+
+    item.retarget_with(|target| fingerprinted(target, &hash[..8]));
+
+    let routed = item.output_path().unwrap();
+
+    let (original, routed) = (support::url(&original), support::url(&routed));
+
+    item.bind().with_ext_mut(|extensions| {
+        extensions.entry::<Fingerprints>()
+            .or_insert_with(BTreeMap::new)
+            .insert(original, routed);
+    });
+
+    Ok(())
+}
+
+/// Resolve an asset's logical name to its fingerprinted URL.
+///
+/// The name is relative to the site's output directory, including the
+/// asset rule's output subdirectory if it has one, e.g. `css/screen.css`.
+/// If the asset wasn't fingerprinted by the bind, its unmodified URL
+/// is returned.
+pub fn asset_url(bind: &Bind, name: &str) -> String {
+    let logical = support::url(Path::new(name));
+
+    let fingerprinted = bind.with_ext(|extensions| {
+        extensions.get::<Fingerprints>()
+            .and_then(|fingerprints| fingerprints.get(&logical).cloned())
+    });
+
+    fingerprinted.unwrap_or(logical)
+}
@@ -0,0 +1,230 @@
+//! Authors and their profiles.
+//!
+//! Profiles are kept in a TOML data file in the input directory,
+//! keyed by each author's slug:
+//!
+//! ```toml
+//! [alice]
+//! name = "Alice Liddell"
+//! url = "https://example.com/alice"
+//! ```
+//!
+//! Items name their authors in their metadata, either with
+//! `authors = ["alice", "bob"]` or `author = "alice"`.
+//!
+//! ```ignore
+//! let posts =
+//!     Rule::named("posts")
+//!     .handler(chain![
+//!         authors::profiles("authors.toml"),
+//!         bind::select(glob!("posts/*.md")),
+//!         bind::each(chain![item::read, item::parse_metadata, authors::attach])])
+//!     .build();
+//!
+//! let author_pages =
+//!     Rule::named("author pages")
+//!     .depends_on(&posts)
+//!     .handler(chain![
+//!         authors::archives(&posts, |slug| PathBuf::from(format!("authors/{}/index.html", slug))),
+//!         bind::each(chain![render_author, item::write])])
+//!     .build();
+//! ```
+//!
+//! Per-author feeds are created the same way, with a second `archives`
+//! whose pages are rendered as feeds.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use typemap;
+use toml;
+
+use bind::Bind;
+use handler::Handle;
+use item::{self, Item};
+
+/// An author's profile.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Profile {
+    /// The key of the profile in the data file.
+    #[serde(default)]
+    pub slug: String,
+
+    pub name: String,
+
+    #[serde(default)]
+    pub bio: Option<String>,
+
+    #[serde(default)]
+    pub url: Option<String>,
+
+    #[serde(default)]
+    pub email: Option<String>,
+
+    #[serde(default)]
+    pub avatar: Option<String>,
+}
+
+/// The profiles of every author, keyed by slug.
+pub struct Profiles;
+
+impl typemap::Key for Profiles {
+    type Value = Arc<BTreeMap<String, Profile>>;
+}
+
+/// The authors of an item, in the order they're named.
+pub struct Author;
+
+impl typemap::Key for Author {
+    type Value = Vec<Profile>;
+}
+
+/// An author's archive page.
+#[derive(Clone)]
+pub struct Archive {
+    pub author: Profile,
+
+    /// The author's items, in the order of the dependency.
+    pub items: Vec<Item>,
+}
+
+impl typemap::Key for Archive {
+    type Value = Archive;
+}
+
+pub struct LoadProfiles {
+    path: PathBuf,
+}
+
+impl Handle<Bind> for LoadProfiles {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let path = bind.configuration.input.join(&self.path);
+
+        let contents = bind.configuration.sandbox().read_to_string(&path)?;
+
+        let mut profiles: BTreeMap<String, Profile> =
+            toml::from_str(&contents)
+            .map_err(|e| format!("could not parse {}: {}", path.display(), e))?;
+
+        for (slug, profile) in profiles.iter_mut() {
+            profile.slug = slug.clone();
+        }
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Profiles>(Arc::new(profiles)));
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that loads author profiles from a data file.
+///
+/// The path is relative to the input directory. The profiles are
+/// stored as the bind's `Profiles`.
+#[inline]
+pub fn profiles<P>(path: P) -> LoadProfiles
+where P: Into<PathBuf> {
+    LoadProfiles {
+        path: path.into(),
+    }
+}
+
+/// The slugs of the authors named in the item's metadata.
+fn slugs(item: &Item) -> Vec<String> {
+    let metadata = match item.extensions.get::<item::Metadata>() {
+        Some(metadata) => metadata,
+        None => return vec![],
+    };
+
+    if let Some(authors) = metadata.get("authors").and_then(toml::Value::as_array) {
+        return authors.iter()
+            .filter_map(toml::Value::as_str)
+            .map(String::from)
+            .collect();
+    }
+
+    metadata.get("author")
+        .and_then(toml::Value::as_str)
+        .map_or_else(Vec::new, |author| vec![String::from(author)])
+}
+
+/// Handle<Item> that attaches the profiles of the item's authors.
+///
+/// The profiles must have been loaded into the item's bind with
+/// `profiles`, and this must run after `parse_metadata`. Naming an
+/// author without a profile is an error.
+pub fn attach(item: &mut Item) -> ::Result<()> {
+    let slugs = slugs(item);
+
+    if slugs.is_empty() {
+        return Ok(());
+    }
+
+    let profiles =
+        item.bind().with_ext(|extensions| extensions.get::<Profiles>().cloned())
+        .ok_or_else(|| format!("no author profiles were loaded for {:?}", item))?;
+
+    let mut authors = Vec::new();
+
+    for slug in slugs {
+        match profiles.get(&slug) {
+            Some(profile) => authors.push(profile.clone()),
+            None => return Err(From::from(format!("{:?} names unknown author `{}`", item, slug))),
+        }
+    }
+
+    item.extensions.insert::<Author>(authors);
+
+    Ok(())
+}
+
+pub struct Archives<R>
+where R: Fn(&str) -> PathBuf, R: Sync + Send + 'static {
+    dependency: String,
+    router: R,
+}
+
+impl<R> Handle<Bind> for Archives<R>
+where R: Fn(&str) -> PathBuf, R: Sync + Send + 'static {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut archives: BTreeMap<String, Archive> = BTreeMap::new();
+
+        {
+            let dependency = bind.dependency(&self.dependency)?;
+
+            for item in dependency.items() {
+                if let Some(authors) = item.extensions.get::<Author>() {
+                    for author in authors {
+                        archives.entry(author.slug.clone())
+                            .or_insert_with(|| Archive {
+                                author: author.clone(),
+                                items: Vec::new(),
+                            })
+                            .items.push(item.clone());
+                    }
+                }
+            }
+        }
+
+        for (slug, archive) in archives {
+            let mut page = Item::writing((self.router)(&slug));
+            page.extensions.insert::<Archive>(archive);
+            bind.attach(page);
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that creates a page for each author of the dependency's items.
+///
+/// The router maps an author's slug to the page's output path.
+/// Each page has an `Archive` with the author's profile and items.
+#[inline]
+pub fn archives<D, R>(dependency: D, router: R) -> Archives<R>
+where D: Into<String>, R: Fn(&str) -> PathBuf, R: Sync + Send + 'static {
+    Archives {
+        dependency: dependency.into(),
+        router: router,
+    }
+}
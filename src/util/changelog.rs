@@ -0,0 +1,157 @@
+//! A page of recent changes.
+//!
+//! Items are annotated with the `Change` that last modified them,
+//! usually by a version control handler, though it can be inserted
+//! by any handler. The `changelog` handler then gathers the most recent
+//! changes from its dependencies into a single page.
+//!
+//! ```ignore
+//! let changes =
+//!     Rule::named("changes")
+//!     .depends_on(&docs)
+//!     .handler(chain![
+//!         changelog::changelog("changes/index.html").from(&docs).limit(25),
+//!         bind::each(item::write)])
+//!     .build();
+//! ```
+
+use std::cmp;
+use std::path::PathBuf;
+
+use typemap;
+
+use bind::Bind;
+use handler::Handle;
+use item::{Item, ItemExt};
+use util::dates::DateTime;
+use util::html::escape;
+
+/// The change that last modified an item.
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub date: DateTime,
+    pub message: String,
+    pub commit: String,
+}
+
+impl typemap::Key for Change {
+    type Value = Change;
+}
+
+/// An entry of the changelog, as made available to templates.
+#[derive(Clone, Debug, Serialize)]
+pub struct Entry {
+    pub url: Option<String>,
+    pub title: String,
+    pub date: String,
+    pub message: String,
+    pub commit: String,
+}
+
+/// The entries of a changelog page, most recent first.
+pub struct Changes;
+
+impl typemap::Key for Changes {
+    type Value = Vec<Entry>;
+}
+
+fn title_of(item: &Item) -> String {
+    item.title().unwrap_or_else(String::new)
+}
+
+pub struct Changelog {
+    target: PathBuf,
+    sources: Vec<String>,
+    limit: usize,
+}
+
+impl Changelog {
+    /// Include the changes of a dependency's items.
+    pub fn from<D>(mut self, dependency: D) -> Changelog
+    where D: Into<String> {
+        self.sources.push(dependency.into());
+        self
+    }
+
+    /// The maximum number of changes to list.
+    pub fn limit(mut self, limit: usize) -> Changelog {
+        self.limit = limit;
+        self
+    }
+}
+
+impl Handle<Bind> for Changelog {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut changed: Vec<(DateTime, Entry)> = Vec::new();
+
+        for source in &self.sources {
+            let dependency = bind.dependency(source)?;
+
+            for item in dependency.items() {
+                if let Some(change) = item.extensions.get::<Change>() {
+                    changed.push((change.date, Entry {
+                        url: item.url(),
+                        title: title_of(item),
+                        date: change.date.to_rfc3339(),
+                        message: change.message.clone(),
+                        commit: change.commit.clone(),
+                    }));
+                }
+            }
+        }
+
+        changed.sort_by(|a, b| -> cmp::Ordering {
+            b.0.cmp(&a.0)
+        });
+
+        let entries: Vec<Entry> =
+            changed.into_iter()
+            .take(self.limit)
+            .map(|(_, entry)| entry)
+            .collect();
+
+        let mut page = Item::writing(self.target.clone());
+
+        page.body = render(&entries).into();
+        page.extensions.insert::<Changes>(entries);
+
+        bind.attach(page);
+
+        Ok(())
+    }
+}
+
+/// A plain HTML list of the entries.
+///
+/// This is the page's body until it's rendered with a template.
+fn render(entries: &[Entry]) -> String {
+    let mut html = String::from("<ul class=\"changelog\">\n");
+
+    for entry in entries {
+        let title = match entry.url {
+            Some(ref url) => format!("<a href=\"{}\">{}</a>", escape(url), escape(&entry.title)),
+            None => escape(&entry.title),
+        };
+
+        html.push_str(&format!(
+            "<li><time datetime=\"{0}\">{0}</time> {1}: {2}</li>\n",
+            escape(&entry.date), title, escape(&entry.message)));
+    }
+
+    html.push_str("</ul>\n");
+    html
+}
+
+/// Create a changelog page at the given output path.
+///
+/// The page lists the 20 most recent changes unless a different
+/// `limit` is given.
+#[inline]
+pub fn changelog<P>(target: P) -> Changelog
+where P: Into<PathBuf> {
+    Changelog {
+        target: target.into(),
+        sources: Vec::new(),
+        limit: 20,
+    }
+}
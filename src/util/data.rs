@@ -0,0 +1,188 @@
+//! Data files.
+//!
+//! Structured data that isn't content, such as navigation menus or
+//! lists of authors, can live in files in the `Configuration::data`
+//! directory rather than in code. Every TOML, JSON, or, with the `yaml`
+//! feature, YAML file in it is parsed at the start of the build and
+//! made available to every bind as the `Data` extension, keyed by its
+//! path without the extension, e.g. `authors` or `menus/main`.
+//!
+//! ```ignore
+//! #[derive(Deserialize)]
+//! struct Author {
+//!     name: String,
+//!     email: String,
+//! }
+//!
+//! let data = bind.with_ext(|extensions| extensions.get::<data::Data>().cloned()).unwrap();
+//! let authors: Vec<Author> = data.get_as("authors")?;
+//! ```
+//!
+//! Templates see the same data as `data`, e.g. `data.menus.main`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json;
+use toml;
+use typemap;
+use walkdir::WalkDir;
+
+use configuration::Configuration;
+
+/// The parsed data files, by path without the extension.
+#[derive(Clone, Debug, Default)]
+pub struct Data {
+    files: BTreeMap<String, serde_json::Value>,
+
+    /// The files as nested objects, for templates
+    tree: serde_json::Value,
+}
+
+impl typemap::Key for Data {
+    type Value = Arc<Data>;
+}
+
+impl Data {
+    /// Parse every data file in the configured directory.
+    ///
+    /// There's no data if the directory doesn't exist.
+    pub fn load(configuration: &Configuration) -> ::Result<Data> {
+        let mut data = Data::default();
+
+        let root = &configuration.data;
+        let sandbox = configuration.sandbox();
+
+        if !sandbox.exists(root) {
+            return Ok(data);
+        }
+
+        for entry in WalkDir::new(root) {
+            let entry = entry?;
+            let path = entry.path();
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative = path.strip_prefix(root)?;
+
+            let ignored =
+                configuration.ignore.as_ref()
+                .map_or(false, |ignore| ignore.matches(relative));
+
+            let extension =
+                path.extension()
+                .and_then(|e| e.to_str())
+                .map_or_else(String::new, |e| e.to_ascii_lowercase());
+
+            if ignored || !["toml", "json", "yaml", "yml"].contains(&&extension[..]) {
+                continue;
+            }
+
+            let contents = sandbox.read_to_string(path)?;
+
+            let value =
+                Data::parse(&contents, &extension)
+                .map_err(|e| format!("could not parse the data file {:?}: {}", path, e))?;
+
+            let key =
+                path.strip_prefix(root)?.with_extension("")
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+
+            data.files.insert(key, value);
+        }
+
+        data.tree = data.nest()?;
+
+        Ok(data)
+    }
+
+    fn parse(contents: &str, extension: &str) -> ::Result<serde_json::Value> {
+        match extension {
+            "toml" => Ok(serde_json::to_value(contents.parse::<toml::Value>()?)?),
+            "json" => Ok(serde_json::from_str(contents)?),
+            _ => Data::parse_yaml(contents),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    fn parse_yaml(contents: &str) -> ::Result<serde_json::Value> {
+        use serde_yaml;
+
+        Ok(serde_yaml::from_str(contents)?)
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    fn parse_yaml(_contents: &str) -> ::Result<serde_json::Value> {
+        Err(From::from("YAML data files require the `yaml` feature"))
+    }
+
+    /// The contents of a data file, e.g. `authors` or `menus/main`.
+    pub fn get(&self, name: &str) -> Option<&serde_json::Value> {
+        self.files.get(name)
+    }
+
+    /// The contents of a data file, deserialized into a user-defined type.
+    pub fn get_as<T>(&self, name: &str) -> ::Result<T>
+    where T: DeserializeOwned {
+        let value =
+            self.get(name)
+            .cloned()
+            .ok_or_else(|| format!("there is no data file named `{}`", name))?;
+
+        serde_json::from_value(value)
+            .map_err(|e| From::from(format!("invalid data file `{}`: {}", name, e)))
+    }
+
+    /// The names of the data files.
+    pub fn names(&self) -> Vec<&str> {
+        self.files.keys().map(|name| &name[..]).collect()
+    }
+
+    /// All of the data as nested objects, e.g. `menus/main` as `{"menus": {"main": ...}}`.
+    pub fn tree(&self) -> &serde_json::Value {
+        &self.tree
+    }
+
+    /// Fails if a file has the same name as a directory, e.g. both
+    /// `menus.toml` and `menus/`, since one would hide the other.
+    fn nest(&self) -> ::Result<serde_json::Value> {
+        let mut root = serde_json::Map::new();
+
+        for (name, value) in &self.files {
+            let mut parts = name.split('/').collect::<Vec<_>>();
+            let last = parts.pop().unwrap();
+
+            let mut table = &mut root;
+            let mut prefix = String::new();
+
+            for part in parts {
+                if !prefix.is_empty() {
+                    prefix.push('/');
+                }
+
+                prefix.push_str(part);
+
+                if self.files.contains_key(&prefix) {
+                    return Err(From::from(format!(
+                        "`{}` in the data directory is both a file and a directory", prefix)));
+                }
+
+                let entry =
+                    table.entry(String::from(part))
+                    .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+
+                table = entry.as_object_mut().unwrap();
+            }
+
+            table.insert(String::from(last), value.clone());
+        }
+
+        Ok(serde_json::Value::Object(root))
+    }
+}
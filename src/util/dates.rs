@@ -0,0 +1,138 @@
+//! Dates and times.
+//!
+//! Dates are parsed from metadata in any of the common formats, such as
+//! `2015-03-21`, `2015-03-21 14:30`, RFC 3339, or RFC 2822. Dates without
+//! an offset are taken to be in the site's `Configuration::timezone`, and
+//! are formatted with its `Configuration::date_format` unless another
+//! format is given.
+//!
+//! ```ignore
+//! let posts =
+//!     Rule::named("posts")
+//!     .handler(chain![
+//!         bind::select(glob!("posts/*.md")),
+//!         bind::each(chain![item::read, item::parse_metadata, dates::date]),
+//!         bind::retain(bind::is_published)])
+//!     .build();
+//! ```
+//!
+//! With the `tera` feature, templates can format dates with the
+//! `format_date` filter, e.g. `{{ date | format_date }}` or
+//! `{{ date | format_date(format="%Y") }}`.
+
+use chrono::{self, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use toml;
+
+use configuration::Configuration;
+use item::{self, Item};
+
+/// A date and time with the offset it was given in.
+pub type DateTime = chrono::DateTime<FixedOffset>;
+
+/// The formats of dates and times without an offset.
+static LOCAL_FORMATS: [&'static str; 4] = [
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// The date with its offset fixed, whatever its timezone.
+pub fn fixed<T>(date: chrono::DateTime<T>) -> DateTime
+where T: TimeZone {
+    let offset = date.offset().fix();
+    date.with_timezone(&offset)
+}
+
+/// Attach the offset of the timezone to a local date and time.
+fn localize(local: NaiveDateTime, timezone: &Tz) -> Option<DateTime> {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(date) => Some(fixed(date)),
+        // the earlier of the two, when the clocks go back
+        LocalResult::Ambiguous(date, _) => Some(fixed(date)),
+        // skipped when the clocks go forward
+        LocalResult::None => None,
+    }
+}
+
+/// Parse a date in any of the supported formats.
+///
+/// Dates and times without an offset are taken to be in the given
+/// timezone, and dates without a time are taken to be at midnight.
+pub fn parse(text: &str, timezone: &Tz) -> ::Result<DateTime> {
+    let text = text.trim();
+
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(text) {
+        return Ok(date);
+    }
+
+    if let Ok(date) = chrono::DateTime::parse_from_rfc2822(text) {
+        return Ok(date);
+    }
+
+    let local =
+        LOCAL_FORMATS.iter()
+        .filter_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .next()
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        });
+
+    match local {
+        Some(local) => {
+            localize(local, timezone).ok_or_else(|| {
+                From::from(format!("`{}` doesn't exist in the timezone {}", text, timezone.name()))
+            })
+        },
+        None => Err(From::from(format!("`{}` isn't a recognized date", text))),
+    }
+}
+
+/// Parse a date from a metadata value, which may be a string or a TOML datetime.
+pub fn from_toml(value: &toml::Value, timezone: &Tz) -> ::Result<DateTime> {
+    match *value {
+        toml::Value::String(ref text) => parse(text, timezone),
+        toml::Value::Datetime(ref date) => parse(&date.to_string(), timezone),
+        _ => Err(From::from(format!("`{}` isn't a date", value))),
+    }
+}
+
+/// The date in the site's timezone, whatever offset it was given in.
+pub fn in_timezone(date: &DateTime, configuration: &Configuration) -> chrono::DateTime<Tz> {
+    date.with_timezone(&configuration.timezone)
+}
+
+/// The date in the site's timezone, with the site's date format.
+pub fn format(date: &DateTime, configuration: &Configuration) -> String {
+    format_with(date, configuration, &configuration.date_format)
+}
+
+/// The date in the site's timezone, with the given `strftime`-style format.
+pub fn format_with(date: &DateTime, configuration: &Configuration, format: &str) -> String {
+    in_timezone(date, configuration).format(format).to_string()
+}
+
+/// The date in the item's metadata field, if it has one.
+pub fn metadata(item: &Item, key: &str) -> Option<::Result<DateTime>> {
+    let timezone = &item.bind().configuration.timezone;
+
+    item.extensions.get::<item::Metadata>()
+        .and_then(|m| m.get(key))
+        .map(|value| {
+            from_toml(value, timezone)
+                .map_err(|e| From::from(format!("the `{}` of {}: {}", key, item, e)))
+        })
+}
+
+/// Handle<Item> that parses the `date` metadata field.
+///
+/// The date is stored as the `Date` extension.
+pub fn date(item: &mut Item) -> ::Result<()> {
+    if let Some(date) = metadata(item, "date") {
+        item.extensions.insert::<item::Date>(date?);
+    }
+
+    Ok(())
+}
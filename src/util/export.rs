@@ -0,0 +1,297 @@
+//! Exporting content into a single document.
+//!
+//! An export assembles the items of one or more dependency rules,
+//! in order, into a single PDF or EPUB document in the output directory.
+//!
+//! ```ignore
+//! let book =
+//!     Rule::named("book")
+//!     .depends_on(&posts)
+//!     .handler(export::pdf("book.pdf").title("Collected Posts").from(&posts))
+//!     .build();
+//! ```
+//!
+//! PDFs are rendered from a combined HTML document by an external
+//! command, `weasyprint` by default, which is invoked as
+//! `command <input.html> <output.pdf>`. EPUBs require the `epub` feature.
+//!
+//! Each item contributes its `Rendered` body by default, so that
+//! layouts aren't repeated for every chapter; see `Export::body`.
+//!
+//! The exported document is attached to the bind as an item that
+//! has already been written; it shouldn't be written again.
+
+use std::path::{Path, PathBuf};
+use std::process;
+
+use toml;
+
+use bind::Bind;
+use handler::Handle;
+use item::{self, Item};
+use support;
+use util::html::escape;
+use util::versions::Body;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Pdf,
+    Epub,
+}
+
+/// A section of the exported document.
+struct Chapter {
+    title: String,
+    body: String,
+}
+
+impl Chapter {
+    fn from_item(item: &Item, body: &Body) -> ::Result<Chapter> {
+        let title =
+            item.extensions.get::<item::Metadata>()
+            .and_then(|m| m.get("title"))
+            .and_then(toml::Value::as_str)
+            .map(String::from)
+            .or_else(|| {
+                item.route().reading()
+                    .and_then(Path::file_stem)
+                    .map(|s| s.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(String::new);
+
+        Ok(Chapter {
+            title: title,
+            body: body.of(item)?.to_string(),
+        })
+    }
+}
+
+pub struct Export {
+    format: Format,
+    target: PathBuf,
+    title: String,
+    sources: Vec<String>,
+    body: Body,
+    command: String,
+}
+
+impl Export {
+    fn new(format: Format, target: PathBuf) -> Export {
+        Export {
+            format: format,
+            target: target,
+            title: String::new(),
+            sources: Vec::new(),
+            body: Body::Rendered,
+            command: String::from("weasyprint"),
+        }
+    }
+
+    /// Include the items of a dependency, after those already included.
+    pub fn from<D>(mut self, dependency: D) -> Export
+    where D: Into<String> {
+        self.sources.push(dependency.into());
+        self
+    }
+
+    /// The title of the document.
+    pub fn title<T>(mut self, title: T) -> Export
+    where T: Into<String> {
+        self.title = title.into();
+        self
+    }
+
+    /// The version of each item's body to include.
+    pub fn body(mut self, body: Body) -> Export {
+        self.body = body;
+        self
+    }
+
+    /// The command used to render PDFs.
+    pub fn command<C>(mut self, command: C) -> Export
+    where C: Into<String> {
+        self.command = command.into();
+        self
+    }
+
+    fn chapters(&self, bind: &Bind) -> ::Result<Vec<Chapter>> {
+        let mut chapters = Vec::new();
+
+        for source in &self.sources {
+            let dependency = bind.dependency(source)?;
+
+            for item in dependency.items() {
+                chapters.push(Chapter::from_item(item, &self.body)?);
+            }
+        }
+
+        Ok(chapters)
+    }
+
+    fn html(&self, chapters: &[Chapter]) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{}</title>\n</head>\n<body>\n",
+            escape(&self.title));
+
+        for chapter in chapters {
+            html.push_str(&format!("<section>\n<h1>{}</h1>\n{}\n</section>\n",
+                                   escape(&chapter.title), chapter.body));
+        }
+
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    fn render_pdf(&self, bind: &Bind, chapters: &[Chapter], target: &Path) -> ::Result<()> {
+        let sandbox = bind.configuration.sandbox();
+
+        let scratch = bind.scratch_dir()?;
+
+        let name = self.target.with_extension("html");
+
+        let name = name.file_name().ok_or_else(|| {
+            format!("the export target {:?} doesn't name a file", self.target)
+        })?;
+
+        let html = scratch.join(name);
+        sandbox.write(&html, self.html(chapters).as_bytes())?;
+
+        let status =
+            process::Command::new(&self.command)
+            .arg(&html)
+            .arg(target)
+            .status()
+            .map_err(|e| format!("could not run `{}`: {}", self.command, e))?;
+
+        if !status.success() {
+            return Err(From::from(
+                format!("`{}` failed to render {}: {}",
+                        self.command, target.display(), status)));
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "epub")]
+    fn render_epub(&self, bind: &Bind, chapters: &[Chapter], target: &Path) -> ::Result<()> {
+        use std::io::{Cursor, Write};
+
+        use zip::{ZipWriter, CompressionMethod};
+        use zip::write::SimpleFileOptions;
+
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = SimpleFileOptions::default();
+
+        // built in memory, since the sandbox only writes whole files
+        let mut epub = ZipWriter::new(Cursor::new(Vec::new()));
+
+        // the mimetype must come first and be stored uncompressed
+        epub.start_file("mimetype", stored)?;
+        epub.write_all(b"application/epub+zip")?;
+
+        epub.start_file("META-INF/container.xml", deflated)?;
+        epub.write_all(
+            b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+              <container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n\
+              <rootfiles>\n\
+              <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n\
+              </rootfiles>\n\
+              </container>\n")?;
+
+        let title = escape(&self.title);
+
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        let mut nav = String::new();
+
+        for (index, chapter) in chapters.iter().enumerate() {
+            let id = format!("chapter-{}", index + 1);
+            let file = format!("{}.xhtml", id);
+
+            manifest.push_str(&format!(
+                "<item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>\n", id, file));
+            spine.push_str(&format!("<itemref idref=\"{}\"/>\n", id));
+            nav.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n",
+                                  file, escape(&chapter.title)));
+
+            epub.start_file(format!("OEBPS/{}", file), deflated)?;
+            epub.write_all(format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <html xmlns=\"http://www.w3.org/1999/xhtml\">\n\
+                 <head><title>{0}</title></head>\n\
+                 <body>\n<h1>{0}</h1>\n{1}\n</body>\n</html>\n",
+                escape(&chapter.title), chapter.body).as_bytes())?;
+        }
+
+        epub.start_file("OEBPS/nav.xhtml", deflated)?;
+        epub.write_all(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+             <head><title>{}</title></head>\n\
+             <body>\n<nav epub:type=\"toc\">\n<ol>\n{}</ol>\n</nav>\n</body>\n</html>\n",
+            title, nav).as_bytes())?;
+
+        epub.start_file("OEBPS/content.opf", deflated)?;
+        epub.write_all(format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\">\n\
+             <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+             <dc:identifier id=\"id\">urn:diecast:{}</dc:identifier>\n\
+             <dc:title>{}</dc:title>\n\
+             <dc:language>en</dc:language>\n\
+             </metadata>\n\
+             <manifest>\n\
+             <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+             {}</manifest>\n\
+             <spine>\n{}</spine>\n\
+             </package>\n",
+            support::hash(self.title.as_bytes()), title, manifest, spine).as_bytes())?;
+
+        let contents = epub.finish()?.into_inner();
+
+        bind.configuration.sandbox().write(target, &contents)?;
+
+        Ok(())
+    }
+
+    #[cfg(not(feature = "epub"))]
+    fn render_epub(&self, _bind: &Bind, _chapters: &[Chapter], _target: &Path) -> ::Result<()> {
+        Err(From::from("exporting EPUB requires the `epub` feature"))
+    }
+}
+
+impl Handle<Bind> for Export {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let chapters = self.chapters(bind)?;
+
+        let target = bind.configuration.output.join(&self.target);
+
+        if let Some(parent) = target.parent() {
+            bind.configuration.sandbox().create_dir_all(parent)?;
+        }
+
+        match self.format {
+            Format::Pdf => self.render_pdf(bind, &chapters, &target)?,
+            Format::Epub => self.render_epub(bind, &chapters, &target)?,
+        }
+
+        bind.attach(Item::writing(self.target.clone()));
+
+        Ok(())
+    }
+}
+
+/// Export the included items into a PDF at the given output path.
+#[inline]
+pub fn pdf<P>(target: P) -> Export
+where P: Into<PathBuf> {
+    Export::new(Format::Pdf, target.into())
+}
+
+/// Export the included items into an EPUB at the given output path.
+#[inline]
+pub fn epub<P>(target: P) -> Export
+where P: Into<PathBuf> {
+    Export::new(Format::Epub, target.into())
+}
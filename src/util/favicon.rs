@@ -0,0 +1,310 @@
+//! Favicons and web app manifests.
+//!
+//! The `icons` handler generates every size of favicon that browsers
+//! and platforms look for from a single square source image, along with
+//! a `site.webmanifest` and the `<link>` and `<meta>` tags that refer to
+//! them. The `inject` handler then adds those tags to the `<head>` of
+//! the HTML items of dependent rules.
+//!
+//! ```ignore
+//! let icons =
+//!     Rule::named("icons")
+//!     .handler(favicon::icons("images/icon.png").theme_color("#3366cc"))
+//!     .build();
+//!
+//! let pages =
+//!     Rule::named("pages")
+//!     .depends_on(&icons)
+//!     .handler(chain![
+//!         bind::select(glob!("*.html")),
+//!         bind::each(chain![item::read, route::identity, favicon::inject(&icons), item::write])])
+//!     .build();
+//! ```
+//!
+//! The name and colors default to the `[favicon]` table of `Diecast.toml`:
+//!
+//! ```toml
+//! [favicon]
+//! name = "My Site"
+//! short_name = "Site"
+//! theme_color = "#3366cc"
+//! background_color = "#ffffff"
+//! ```
+//!
+//! Every file is written by `icons` itself, and attached to the bind
+//! so that it's recorded in the manifest; they shouldn't be written again.
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::{self, DynamicImage, GenericImageView, ImageFormat};
+use image::imageops::FilterType;
+use serde_json;
+use toml;
+use typemap;
+
+use bind::Bind;
+use configuration::Configuration;
+use handler::Handle;
+use item::Item;
+use manifest::Checksum;
+use support;
+use util::html;
+
+/// The `<link>` and `<meta>` tags that refer to a bind's icons.
+pub struct Tags;
+
+impl typemap::Key for Tags {
+    type Value = String;
+}
+
+/// The generated PNG icons: their file names, sizes, and link relations.
+static SIZES: [(&'static str, u32, Option<&'static str>); 5] = [
+    ("favicon-16x16.png", 16, Some("icon")),
+    ("favicon-32x32.png", 32, Some("icon")),
+    ("apple-touch-icon.png", 180, Some("apple-touch-icon")),
+    ("android-chrome-192x192.png", 192, None),
+    ("android-chrome-512x512.png", 512, None),
+];
+
+#[derive(Serialize)]
+struct WebManifest<'a> {
+    name: &'a str,
+    short_name: &'a str,
+    icons: Vec<ManifestIcon>,
+    theme_color: &'a str,
+    background_color: &'a str,
+    display: &'static str,
+}
+
+#[derive(Serialize)]
+struct ManifestIcon {
+    src: String,
+    sizes: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+}
+
+pub struct Icons {
+    source: PathBuf,
+    name: Option<String>,
+    short_name: Option<String>,
+    theme_color: Option<String>,
+    background_color: Option<String>,
+}
+
+impl Icons {
+    /// The name of the site in the web manifest.
+    pub fn name<S>(mut self, name: S) -> Icons
+    where S: Into<String> {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The name used where there's little space, e.g. under a home screen icon.
+    ///
+    /// Defaults to the name.
+    pub fn short_name<S>(mut self, short_name: S) -> Icons
+    where S: Into<String> {
+        self.short_name = Some(short_name.into());
+        self
+    }
+
+    /// The color of the browser's interface around the site. Defaults to white.
+    pub fn theme_color<S>(mut self, color: S) -> Icons
+    where S: Into<String> {
+        self.theme_color = Some(color.into());
+        self
+    }
+
+    /// The color of the splash screen while the site loads. Defaults to white.
+    pub fn background_color<S>(mut self, color: S) -> Icons
+    where S: Into<String> {
+        self.background_color = Some(color.into());
+        self
+    }
+
+    /// The value that was set, or that of the `[favicon]` table.
+    fn setting(value: &Option<String>, configuration: &Configuration, key: &str) -> Option<String> {
+        value.clone().or_else(|| {
+            configuration.get(&format!("favicon.{}", key))
+                .and_then(toml::Value::as_str)
+                .map(String::from)
+        })
+    }
+
+    /// Write a file to the output and attach it to the bind.
+    fn write(bind: &mut Bind, target: &str, contents: &[u8]) -> ::Result<()> {
+        bind.configuration.sandbox().write(&bind.configuration.output.join(target), contents)?;
+
+        let mut item = Item::writing(target);
+        item.extensions.insert::<Checksum>(support::hash(contents));
+
+        bind.attach(item);
+
+        Ok(())
+    }
+
+    /// The URL of a file written by `write`, within the rule's output.
+    fn url(bind: &Bind, target: &str) -> String {
+        match bind.configuration.subdirectory {
+            Some(ref subdirectory) => support::url(&subdirectory.join(target)),
+            None => support::url(Path::new(target)),
+        }
+    }
+
+    fn encode(icon: &DynamicImage, format: ImageFormat, target: &str) -> ::Result<Vec<u8>> {
+        let mut encoded = Cursor::new(Vec::new());
+
+        icon.write_to(&mut encoded, format)
+            .map_err(|e| format!("could not encode `{}`: {}", target, e))?;
+
+        Ok(encoded.into_inner())
+    }
+}
+
+impl Handle<Bind> for Icons {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let configuration = bind.configuration.clone();
+
+        let source = configuration.input.join(&self.source);
+
+        let original =
+            image::load_from_memory(&configuration.sandbox().read(&source)?)
+            .map_err(|e| format!("could not decode the icon {:?}: {}", self.source, e))?;
+
+        if original.width() != original.height() {
+            return Err(From::from(format!(
+                "the icon {:?} must be square, but it's {}x{}",
+                self.source, original.width(), original.height())));
+        }
+
+        configuration.sandbox().create_dir_all(&configuration.output)?;
+
+        let name =
+            Icons::setting(&self.name, &configuration, "name")
+            .ok_or_else(|| {
+                String::from("the icons need a name, from `name` or `favicon.name` in Diecast.toml")
+            })?;
+
+        let short_name =
+            Icons::setting(&self.short_name, &configuration, "short_name")
+            .unwrap_or_else(|| name.clone());
+
+        let theme_color =
+            Icons::setting(&self.theme_color, &configuration, "theme_color")
+            .unwrap_or_else(|| String::from("#ffffff"));
+
+        let background_color =
+            Icons::setting(&self.background_color, &configuration, "background_color")
+            .unwrap_or_else(|| String::from("#ffffff"));
+
+        let mut tags = String::new();
+        let mut icons = vec![];
+
+        for &(target, size, rel) in SIZES.iter() {
+            let icon = original.resize_exact(size, size, FilterType::Lanczos3);
+
+            Icons::write(bind, target, &Icons::encode(&icon, ImageFormat::Png, target)?)?;
+
+            let url = Icons::url(bind, target);
+            let sizes = format!("{}x{}", size, size);
+
+            match rel {
+                Some(rel) => {
+                    tags.push_str(&format!(
+                        "<link rel=\"{}\" type=\"image/png\" sizes=\"{}\" href=\"{}\">\n",
+                        rel, sizes, html::escape(&url)));
+                },
+                None => {
+                    icons.push(ManifestIcon {
+                        src: url,
+                        sizes: sizes,
+                        kind: "image/png",
+                    });
+                },
+            }
+        }
+
+        // browsers still request it at the root regardless of the tags
+        let ico = original.resize_exact(32, 32, FilterType::Lanczos3);
+        Icons::write(bind, "favicon.ico", &Icons::encode(&ico, ImageFormat::Ico, "favicon.ico")?)?;
+
+        let manifest = WebManifest {
+            name: &name,
+            short_name: &short_name,
+            icons: icons,
+            theme_color: &theme_color,
+            background_color: &background_color,
+            display: "standalone",
+        };
+
+        Icons::write(bind, "site.webmanifest", serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+
+        tags.push_str(&format!(
+            "<link rel=\"manifest\" href=\"{}\">\n", Icons::url(bind, "site.webmanifest")));
+        tags.push_str(&format!(
+            "<meta name=\"theme-color\" content=\"{}\">\n", html::escape(&theme_color)));
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Tags>(tags));
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that generates favicons and a web manifest from a square image.
+///
+/// The source is relative to the input directory, and should be at
+/// least 512 pixels wide. The outputs are written to the root of the
+/// rule's output directory.
+#[inline]
+pub fn icons<P>(source: P) -> Icons
+where P: Into<PathBuf> {
+    Icons {
+        source: source.into(),
+        name: None,
+        short_name: None,
+        theme_color: None,
+        background_color: None,
+    }
+}
+
+pub struct Inject {
+    dependency: String,
+}
+
+impl Handle<Item> for Inject {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let is_html =
+            item.route().writing()
+            .and_then(|target| target.extension())
+            .map_or(false, |extension| extension == "html" || extension == "htm");
+
+        if !is_html {
+            return Ok(());
+        }
+
+        let tags = match item.bind().dependency(&self.dependency)?.extension::<Tags>() {
+            Some(tags) => tags,
+            None => {
+                return Err(From::from(
+                    format!("`{}` didn't generate any icons", self.dependency)));
+            },
+        };
+
+        item.body = html::inject_head(item.text()?, &tags).into();
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that adds the tags of a dependency's icons to the `<head>`.
+///
+/// Items that aren't written as HTML are left alone.
+#[inline]
+pub fn inject<D>(dependency: D) -> Inject
+where D: Into<String> {
+    Inject {
+        dependency: dependency.into(),
+    }
+}
@@ -0,0 +1,416 @@
+//! Syndication feeds.
+//!
+//! A feed lists the most recent items of one or more dependency rules.
+//! Each item is mapped to an `Entry`, by `entry` unless another mapping
+//! is given, and the feed is attached to the bind as an item to be written.
+//!
+//! ```ignore
+//! let channel =
+//!     feed::Channel::new("Blog", "Posts about things")
+//!     .language("en");
+//!
+//! let feed =
+//!     Rule::named("feed")
+//!     .depends_on(&posts)
+//!     .handler(chain![
+//!         feed::rss("rss.xml", channel.clone()).from(&posts).limit(10),
+//!         feed::json_feed("feed.json", channel).from(&posts).limit(10),
+//!         bind::each(item::write)])
+//!     .build();
+//! ```
+//!
+//! Links in feeds must be absolute, so `Configuration::base_url`
+//! should be set.
+
+use std::cmp;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+use toml;
+
+use bind::Bind;
+use handler::Handle;
+use item::{self, Item};
+use util::dates::DateTime;
+use util::html;
+use util::versions::Body;
+
+/// Escape text for XML, dropping the control characters that XML doesn't allow.
+fn escape(text: &str) -> String {
+    let allowed =
+        text.chars()
+        .filter(|&c| c == '\t' || c == '\n' || c == '\r' || c >= ' ')
+        .filter(|&c| c != '\u{fffe}' && c != '\u{ffff}')
+        .collect::<String>();
+
+    html::escape(&allowed)
+}
+
+/// The metadata of a feed.
+#[derive(Clone, Debug)]
+pub struct Channel {
+    title: String,
+    description: String,
+    link: Option<String>,
+    language: Option<String>,
+}
+
+impl Channel {
+    pub fn new<T, D>(title: T, description: D) -> Channel
+    where T: Into<String>, D: Into<String> {
+        Channel {
+            title: title.into(),
+            description: description.into(),
+            link: None,
+            language: None,
+        }
+    }
+
+    /// The URL of the site the feed belongs to. Defaults to the base URL.
+    pub fn link<L>(mut self, link: L) -> Channel
+    where L: Into<String> {
+        self.link = Some(link.into());
+        self
+    }
+
+    /// The language of the feed, e.g. `en-us`.
+    pub fn language<L>(mut self, language: L) -> Channel
+    where L: Into<String> {
+        self.language = Some(language.into());
+        self
+    }
+}
+
+/// An item of a feed.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub title: String,
+
+    /// The absolute URL of the item.
+    pub url: String,
+
+    pub date: Option<DateTime>,
+
+    /// A summary of the content, as HTML.
+    pub summary: Option<String>,
+
+    /// The full content, as HTML with absolute links.
+    pub content: String,
+}
+
+/// The entry for an item.
+///
+/// The title is the `title` metadata field, or the file stem of the
+/// source, and the summary is the `summary` metadata field. The content
+/// is the `Rendered` version of the body if one was saved, otherwise
+/// the body as it is.
+pub fn entry(item: &Item) -> ::Result<Entry> {
+    let configuration = &item.bind().configuration;
+
+    let url = match item.permalink() {
+        Some(url) => url,
+        None => return Err(From::from(format!("{:?} isn't written, so it can't be in a feed", item))),
+    };
+
+    let metadata = |key: &str| -> Option<String> {
+        item.extensions.get::<item::Metadata>()
+            .and_then(|m| m.get(key))
+            .and_then(toml::Value::as_str)
+            .map(String::from)
+    };
+
+    let title =
+        metadata("title")
+        .or_else(|| {
+            item.route().reading()
+                .and_then(Path::file_stem)
+                .map(|s| s.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(String::new);
+
+    let content = match Body::Rendered.of(item) {
+        Ok(content) => content,
+        Err(_) => item.text()?,
+    };
+
+    let content = html::rewrite_urls(content, |url| {
+        if url.starts_with('/') && !url.starts_with("//") {
+            Some(configuration.absolute_url(url))
+        } else {
+            None
+        }
+    });
+
+    Ok(Entry {
+        title: title,
+        url: url,
+        date: item.extensions.get::<item::Date>().cloned(),
+        summary: metadata("summary"),
+        content: content,
+    })
+}
+
+/// Which part of each entry a feed includes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Content {
+    /// The full content.
+    Full,
+
+    /// The summary, or the beginning of the content's text if there is none.
+    Summary,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    Rss,
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'static str,
+    title: &'a str,
+    home_page_url: &'a str,
+    feed_url: &'a str,
+    description: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<&'a str>,
+    items: Vec<JsonItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_published: Option<String>,
+}
+
+pub struct Feed<M>
+where M: Fn(&Item) -> ::Result<Entry>, M: Sync + Send + 'static {
+    format: Format,
+    target: PathBuf,
+    channel: Channel,
+    sources: Vec<String>,
+    limit: usize,
+    content: Content,
+    map: M,
+}
+
+impl<M> Feed<M>
+where M: Fn(&Item) -> ::Result<Entry>, M: Sync + Send + 'static {
+    /// Include the items of a dependency.
+    pub fn from<D>(mut self, dependency: D) -> Feed<M>
+    where D: Into<String> {
+        self.sources.push(dependency.into());
+        self
+    }
+
+    /// The maximum number of entries, most recent first. Defaults to 20.
+    pub fn limit(mut self, limit: usize) -> Feed<M> {
+        self.limit = limit;
+        self
+    }
+
+    /// Which part of each entry to include. Defaults to the full content.
+    pub fn content(mut self, content: Content) -> Feed<M> {
+        self.content = content;
+        self
+    }
+
+    /// Map items to entries with the given closure rather than `entry`.
+    pub fn map<N>(self, map: N) -> Feed<N>
+    where N: Fn(&Item) -> ::Result<Entry>, N: Sync + Send + 'static {
+        Feed {
+            format: self.format,
+            target: self.target,
+            channel: self.channel,
+            sources: self.sources,
+            limit: self.limit,
+            content: self.content,
+            map: map,
+        }
+    }
+
+    fn entries(&self, bind: &Bind) -> ::Result<Vec<Entry>> {
+        let mut entries = vec![];
+
+        for source in &self.sources {
+            let dependency = bind.dependency(source)?;
+
+            for item in dependency.items() {
+                entries.push((self.map)(item)?);
+            }
+        }
+
+        // most recent first, with undated entries last
+        entries.sort_by(|a, b| -> cmp::Ordering {
+            b.date.cmp(&a.date)
+        });
+
+        entries.truncate(self.limit);
+
+        Ok(entries)
+    }
+
+    /// The part of the entry that the feed includes.
+    ///
+    /// A summary that's made from the content is plain text, which the
+    /// formats escape themselves.
+    fn content_of(&self, entry: &Entry) -> String {
+        match self.content {
+            Content::Full => entry.content.clone(),
+            Content::Summary => {
+                entry.summary.clone().unwrap_or_else(|| {
+                    let text = html::text(&entry.content);
+
+                    let end = text.char_indices().nth(300).map(|(end, _)| end);
+
+                    match end {
+                        Some(end) => format!("{}…", &text[..end]),
+                        None => text,
+                    }
+                })
+            },
+        }
+    }
+
+    fn rss(&self, entries: &[Entry], link: &str, url: &str) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n\
+             <channel>\n");
+
+        xml.push_str(&format!("<title>{}</title>\n", escape(&self.channel.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape(link)));
+        xml.push_str(&format!("<description>{}</description>\n", escape(&self.channel.description)));
+        xml.push_str(&format!(
+            "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n", escape(url)));
+
+        if let Some(ref language) = self.channel.language {
+            xml.push_str(&format!("<language>{}</language>\n", escape(language)));
+        }
+
+        if let Some(date) = entries.first().and_then(|e| e.date) {
+            xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", date.to_rfc2822()));
+        }
+
+        for entry in entries {
+            xml.push_str("<item>\n");
+            xml.push_str(&format!("<title>{}</title>\n", escape(&entry.title)));
+            xml.push_str(&format!("<link>{}</link>\n", escape(&entry.url)));
+            xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", escape(&entry.url)));
+
+            if let Some(date) = entry.date {
+                xml.push_str(&format!("<pubDate>{}</pubDate>\n", date.to_rfc2822()));
+            }
+
+            xml.push_str(&format!("<description>{}</description>\n",
+                                  escape(&self.content_of(entry))));
+            xml.push_str("</item>\n");
+        }
+
+        xml.push_str("</channel>\n</rss>\n");
+        xml
+    }
+
+    fn json(&self, entries: &[Entry], link: &str, url: &str) -> ::Result<String> {
+        let items =
+            entries.iter()
+            .map(|entry| JsonItem {
+                id: &entry.url,
+                url: &entry.url,
+                title: &entry.title,
+                content_html: match self.content {
+                    Content::Full => Some(self.content_of(entry)),
+                    Content::Summary => None,
+                },
+                content_text: match self.content {
+                    Content::Full => None,
+                    Content::Summary => Some(self.content_of(entry)),
+                },
+                summary: match self.content {
+                    Content::Full => entry.summary.as_ref().map(|s| &s[..]),
+                    Content::Summary => None,
+                },
+                date_published: entry.date.map(|d| d.to_rfc3339()),
+            })
+            .collect();
+
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: &self.channel.title,
+            home_page_url: link,
+            feed_url: url,
+            description: &self.channel.description,
+            language: self.channel.language.as_ref().map(|l| &l[..]),
+            items: items,
+        };
+
+        Ok(serde_json::to_string_pretty(&feed)?)
+    }
+}
+
+impl<M> Handle<Bind> for Feed<M>
+where M: Fn(&Item) -> ::Result<Entry>, M: Sync + Send + 'static {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let entries = self.entries(bind)?;
+
+        let mut feed = Item::writing(self.target.clone());
+
+        let (link, url) = {
+            let configuration = &bind.configuration;
+
+            let link =
+                self.channel.link.clone()
+                .unwrap_or_else(|| configuration.absolute_url("/"));
+
+            (link, configuration.absolute_url(&feed.url().unwrap()))
+        };
+
+        feed.body = match self.format {
+            Format::Rss => self.rss(&entries, &link, &url),
+            Format::Json => self.json(&entries, &link, &url)?,
+        }.into();
+
+        bind.attach(feed);
+
+        Ok(())
+    }
+}
+
+fn feed(format: Format, target: PathBuf, channel: Channel) -> Feed<fn(&Item) -> ::Result<Entry>> {
+    Feed {
+        format: format,
+        target: target,
+        channel: channel,
+        sources: vec![],
+        limit: 20,
+        content: Content::Full,
+        map: entry as fn(&Item) -> ::Result<Entry>,
+    }
+}
+
+/// Handle<Bind> that creates an RSS 2.0 feed.
+#[inline]
+pub fn rss<P>(target: P, channel: Channel) -> Feed<fn(&Item) -> ::Result<Entry>>
+where P: Into<PathBuf> {
+    feed(Format::Rss, target.into(), channel)
+}
+
+/// Handle<Bind> that creates a JSON Feed 1.1 feed, e.g. `feed.json`.
+///
+/// Entries are mapped the same way as for `rss`, so a site can offer
+/// both from the same `map` closure.
+#[inline]
+pub fn json_feed<P>(target: P, channel: Channel) -> Feed<fn(&Item) -> ::Result<Entry>>
+where P: Into<PathBuf> {
+    feed(Format::Json, target.into(), channel)
+}
@@ -1,17 +1,23 @@
 use std::sync::Arc;
-use std::any::Any;
+use std::any::{Any, type_name};
 use std::path::PathBuf;
+#[cfg(feature = "sass")]
+use std::path::Path;
 use std::{cmp, mem};
+use std::time::{Duration, Instant};
 
 use typemap;
 
-use futures::prelude::*;
-use futures::{self, future, Future};
+use futures::{self, future};
+use futures::task::SpawnExt;
 
-use item::Item;
+use item::{self, Item};
 use bind::Bind;
-use handler::Handle;
+use handler::{Handle, HandleAsync};
+use job::{Event, ItemPool};
+use job::watchdog::Watchdog;
 use pattern::Pattern;
+use util::query::Predicate;
 
 use super::Extender;
 
@@ -24,7 +30,9 @@ impl typemap::Key for InputPaths {
 impl<T> Handle<Bind> for Extender<T>
 where T: typemap::Key, T::Value: Any + Sync + Send + Clone {
     fn handle(&self, bind: &mut Bind) -> ::Result<()> {
-        bind.extensions.write().unwrap().insert::<T>(self.payload.clone());
+        let payload = self.payload.clone();
+
+        bind.with_ext_mut(|extensions| extensions.insert::<T>(payload));
         Ok(())
     }
 }
@@ -49,17 +57,17 @@ where P: Pattern + Sync + Send + 'static {
 impl<P> Handle<Bind> for Select<P>
 where P: Pattern + Sync + Send + 'static {
     fn handle(&self, bind: &mut Bind) -> ::Result<()> {
-        let paths = bind.extensions.read().unwrap().get::<InputPaths>().unwrap().clone();
+        let paths = bind.with_ext(|extensions| extensions.get::<InputPaths>().unwrap().clone());
 
         for path in paths.iter() {
             let relative = path.strip_prefix(&bind.configuration.input)?.to_path_buf();
 
-            // TODO
-            // decide how to handle pattern matching consistently
-            // for example, Configuration::ignore matches on the file_name,
-            // but this pattern seems to be matching on the whole pattern rooted
-            // at the input directory
-            if self.pattern.matches(&relative) {
+            // a rule may ignore more than the site
+            let ignored =
+                bind.configuration.ignore.as_ref()
+                .map_or(false, |ignore| ignore.matches(&relative));
+
+            if !ignored && self.pattern.matches(&relative) {
                 bind.attach(Item::reading(relative));
             }
         }
@@ -99,12 +107,60 @@ where C: Fn(&Item) -> bool, C: Sync + Send + 'static {
 
 #[inline]
 pub fn retain<C>(condition: C) -> Retain<C>
-where C: Fn(&Item) -> bool, C: Copy + Sync + Send + 'static {
+where C: Fn(&Item) -> bool, C: Sync + Send + 'static {
     Retain {
         condition: condition,
     }
 }
 
+/// A condition on an item, e.g. for `retain`.
+pub type Condition = Box<Fn(&Item) -> bool + Sync + Send>;
+
+/// A condition that a field of the item's metadata matches a predicate.
+///
+/// See `util::query` for the predicates, e.g.
+/// `bind::retain(bind::where_meta("tags", query::contains("rust")))`.
+pub fn where_meta<K, P>(key: K, predicate: P) -> Condition
+where K: Into<String>, P: Predicate + 'static {
+    let key = key.into();
+
+    Box::new(move |item: &Item| {
+        let value = item.extensions.get::<item::Metadata>().and_then(|m| m.get(&key[..]));
+        predicate.test(value)
+    })
+}
+
+/// Whether the item is published as of the configuration's `now`.
+///
+/// An item isn't published before its `Date`, nor on or after the date
+/// in its `expires` metadata field, in any format that `dates::parse` reads.
+pub fn is_published(item: &Item) -> bool {
+    use util::dates;
+
+    let now = item.bind().configuration.now();
+
+    if let Some(date) = item.extensions.get::<item::Date>() {
+        if *date > now {
+            return false;
+        }
+    }
+
+    match dates::metadata(item, "expires") {
+        Some(Ok(expires)) => expires > now,
+        _ => true,
+    }
+}
+
+/// Handle<Bind> that removes items that aren't published yet or have expired.
+///
+/// Dates are judged by `Configuration::now`, so `--as-of` previews
+/// the site as it will be on a given date. This must run after
+/// the items' dates have been parsed.
+#[inline]
+pub fn retain_published() -> Retain<fn(&Item) -> bool> {
+    retain(is_published as fn(&Item) -> bool)
+}
+
 pub struct PooledEach {}
 
 impl PooledEach {
@@ -120,6 +176,9 @@ impl PooledEach {
     }
 }
 
+/// Handle<Bind> that runs the handler on each of the bind's items in parallel.
+///
+/// The items keep their order regardless of which finishes first.
 pub fn each<H>(handler: H) -> Each<H>
 where H: Handle<Item> + Sync + Send + 'static {
     Each {
@@ -135,33 +194,137 @@ where H: Handle<Item> + Sync + Send + 'static {
 impl<H> Handle<Bind> for Each<H>
 where H: Handle<Item> + Sync + Send + 'static {
     fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let watchdog = bind.with_ext(|extensions| extensions.get::<Watchdog>().cloned());
+        let name = bind.name.clone();
+
         let items = mem::replace(bind.items_mut(), vec![]);
-        let futures: Vec<_> = items
-            .into_iter()
-            .map(|mut item| {
-                let handler = self.handler.clone();
-
-                let future = future::lazy(move |_| {
-                    match handler.handle(&mut item) {
-                        Ok(()) => Box::new(future::ok(item)),
-                        Err(e) => Box::new(future::err((e, item))),
-                    }
-                });
 
-                futures::executor::block_on(futures::executor::spawn_with_handle(future)).unwrap()
-            })
-            .collect();
+        // the scheduler provides the threads, which take the items as
+        // they free up; outside of a build, e.g. in tests, the items
+        // are handled on this one
+        let pool = bind.with_ext(|extensions| extensions.get::<ItemPool>().cloned());
+
+        let results: Vec<Result<Item, (::Error, Item)>> = match pool {
+            Some(ref pool) => {
+                let mut spawned = Vec::with_capacity(items.len());
+
+                for item in items {
+                    let handler = self.handler.clone();
+                    let watchdog = watchdog.clone();
+                    let name = name.clone();
+
+                    spawned.push(pool.spawn_with_handle(future::lazy(move |_| {
+                        handle_item(&*handler, watchdog.as_ref(), name, item)
+                    }))?);
+                }
+
+                futures::executor::block_on(future::join_all(spawned))
+            },
+            None => {
+                items.into_iter()
+                    .map(|item| handle_item(&*self.handler, watchdog.as_ref(), name.clone(), item))
+                    .collect()
+            },
+        };
+
+        gather(bind, results)
+    }
+}
 
-        match futures::executor::block_on(future::join_all(futures)) {
-            Ok(mut results) => mem::swap(&mut results, bind.items_mut()),
+/// Put the handled items back into the bind, or report the first that failed.
+fn gather(bind: &mut Bind, results: Vec<Result<Item, (::Error, Item)>>) -> ::Result<()> {
+    let mut handled = Vec::with_capacity(results.len());
+
+    for result in results {
+        match result {
+            Ok(item) => handled.push(item),
             Err((e, item)) => {
-                println!("\nthe following item encountered an error:\n  {:?}\n\n{}\n",
-                            item, e);
+                bind.configuration.reporter.report(&Event::ItemFailed {
+                    rule: &bind.name,
+                    item: &format!("{:?}", item),
+                    error: &e.to_string(),
+                });
+
                 return Err(e);
-            }
+            },
         }
+    }
 
-        Ok(())
+    mem::swap(&mut handled, bind.items_mut());
+
+    Ok(())
+}
+
+/// Handle<Bind> that runs an asynchronous handler on each of the bind's
+/// items, all at once.
+///
+/// While an item's handler waits, its thread handles other items. The
+/// items keep their order regardless of which finishes first.
+pub fn each_async<H>(handler: H) -> EachAsync<H>
+where H: HandleAsync<Item> + Sync + Send + 'static {
+    EachAsync {
+        handler: handler,
+    }
+}
+
+pub struct EachAsync<H>
+where H: HandleAsync<Item> + Sync + Send + 'static {
+    handler: H,
+}
+
+impl<H> Handle<Bind> for EachAsync<H>
+where H: HandleAsync<Item> + Sync + Send + 'static {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let items = mem::replace(bind.items_mut(), vec![]);
+        let pool = bind.with_ext(|extensions| extensions.get::<ItemPool>().cloned());
+
+        let pending = items.into_iter().map(|item| self.handler.handle(item));
+
+        let handled = match pool {
+            Some(ref pool) => {
+                let mut spawned = Vec::new();
+
+                for future in pending {
+                    spawned.push(pool.spawn_with_handle(future)?);
+                }
+
+                futures::executor::block_on(future::join_all(spawned))
+            },
+            None => futures::executor::block_on(future::join_all(pending)),
+        };
+
+        let results =
+            handled.into_iter()
+            .map(|(item, result)| match result {
+                Ok(()) => Ok(item),
+                Err(e) => Err((e, item)),
+            })
+            .collect();
+
+        gather(bind, results)
+    }
+}
+
+fn handle_item<H>(
+    handler: &H,
+    watchdog: Option<&Arc<Watchdog>>,
+    name: String,
+    mut item: Item)
+-> Result<Item, (::Error, Item)>
+where H: Handle<Item> {
+    let _guard = watchdog.map(|watchdog| {
+        Watchdog::watch(watchdog, name, Some(item.to_string()), Some(type_name::<H>()))
+    });
+
+    let started = Instant::now();
+    let result = handler.handle(&mut item);
+
+    *item.extensions.entry::<item::Elapsed>()
+        .or_insert(Duration::from_secs(0)) += started.elapsed();
+
+    match result {
+        Ok(()) => Ok(item),
+        Err(e) => Err((e, item)),
     }
 }
 
@@ -184,6 +347,22 @@ where F: Fn(&Item, &Item) -> cmp::Ordering,
     }
 }
 
+impl<F> SortBy<F>
+where F: Fn(&Item, &Item) -> cmp::Ordering,
+      F: Sync + Send + 'static {
+    /// Break ties with another comparison.
+    pub fn then_by<G>(self, compare: G) -> Sort
+    where G: Fn(&Item, &Item) -> cmp::Ordering, G: Sync + Send + 'static {
+        Sort::new(self.compare).then_by(compare)
+    }
+
+    /// Break ties by a key.
+    pub fn then_by_key<B, G>(self, key: G) -> Sort
+    where B: Ord, G: Fn(&Item) -> B, G: Sync + Send + 'static {
+        Sort::new(self.compare).then_by_key(key)
+    }
+}
+
 impl<F> Handle<Bind> for SortBy<F>
 where F: Fn(&Item, &Item) -> cmp::Ordering,
       F: Sync + Send + 'static {
@@ -202,6 +381,24 @@ where B: Ord, F: Fn(&Item) -> B,
     key: F,
 }
 
+impl<B, F> SortByKey<B, F>
+where B: Ord + 'static, F: Fn(&Item) -> B,
+      F: Sync + Send + 'static {
+    /// Break ties with a comparison.
+    pub fn then_by<G>(self, compare: G) -> Sort
+    where G: Fn(&Item, &Item) -> cmp::Ordering, G: Sync + Send + 'static {
+        let key = self.key;
+        Sort::new(move |a: &Item, b: &Item| key(a).cmp(&key(b))).then_by(compare)
+    }
+
+    /// Break ties by another key.
+    pub fn then_by_key<C, G>(self, key: G) -> Sort
+    where C: Ord, G: Fn(&Item) -> C, G: Sync + Send + 'static {
+        let first = self.key;
+        Sort::new(move |a: &Item, b: &Item| first(a).cmp(&first(b))).then_by_key(key)
+    }
+}
+
 impl<B, F> Handle<Bind> for SortByKey<B, F>
 where B: Ord, F: Fn(&Item) -> B,
       F: Sync + Send + 'static {
@@ -221,3 +418,291 @@ where B: Ord, F: Fn(&Item) -> B,
         key: key,
     }
 }
+
+/// A sort by several comparisons, each breaking the ties of the last.
+pub struct Sort {
+    comparisons: Vec<Box<Fn(&Item, &Item) -> cmp::Ordering + Sync + Send>>,
+}
+
+impl Sort {
+    fn new<F>(compare: F) -> Sort
+    where F: Fn(&Item, &Item) -> cmp::Ordering, F: Sync + Send + 'static {
+        Sort {
+            comparisons: vec![Box::new(compare)],
+        }
+    }
+
+    /// Break ties with another comparison.
+    pub fn then_by<F>(mut self, compare: F) -> Sort
+    where F: Fn(&Item, &Item) -> cmp::Ordering, F: Sync + Send + 'static {
+        self.comparisons.push(Box::new(compare));
+        self
+    }
+
+    /// Break ties by a key.
+    pub fn then_by_key<B, F>(self, key: F) -> Sort
+    where B: Ord, F: Fn(&Item) -> B, F: Sync + Send + 'static {
+        self.then_by(move |a: &Item, b: &Item| key(a).cmp(&key(b)))
+    }
+}
+
+impl Handle<Bind> for Sort {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        bind.items_mut().sort_by(|a, b| {
+            self.comparisons.iter()
+                .map(|compare| compare(a, b))
+                .find(|&ordering| ordering != cmp::Ordering::Equal)
+                .unwrap_or(cmp::Ordering::Equal)
+        });
+
+        Ok(())
+    }
+}
+
+/// Compare items by their `Date`, newest first, with undated items last.
+pub fn newest_first(a: &Item, b: &Item) -> cmp::Ordering {
+    match (a.extensions.get::<item::Date>(), b.extensions.get::<item::Date>()) {
+        (Some(a), Some(b)) => b.cmp(a),
+        (Some(_), None) => cmp::Ordering::Less,
+        (None, Some(_)) => cmp::Ordering::Greater,
+        (None, None) => cmp::Ordering::Equal,
+    }
+}
+
+/// Handle<Bind> that sorts the items by their `Date`, newest first.
+///
+/// Undated items go last. Ties can be broken with `then_by`, e.g.
+/// `bind::sort_by_date_desc().then_by_key(title)`.
+#[inline]
+pub fn sort_by_date_desc() -> Sort {
+    Sort::new(newest_first)
+}
+
+/// Handle<Bind> that reverses the order of the items.
+pub fn reverse(bind: &mut Bind) -> ::Result<()> {
+    bind.items_mut().reverse();
+    Ok(())
+}
+
+/// The items around an item in its bind, nearest first.
+#[derive(Clone)]
+pub struct Neighbors {
+    pub before: Vec<Arc<Item>>,
+    pub after: Vec<Arc<Item>>,
+}
+
+impl typemap::Key for Neighbors {
+    type Value = Neighbors;
+}
+
+pub struct Neighbor {
+    count: usize,
+}
+
+impl Handle<Bind> for Neighbor {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let snapshot: Vec<Arc<Item>> = bind.items().iter().cloned().map(Arc::new).collect();
+
+        for (index, item) in bind.items_mut().iter_mut().enumerate() {
+            let start = index.saturating_sub(self.count);
+            let end = cmp::min(index + 1 + self.count, snapshot.len());
+
+            item.extensions.insert::<Neighbors>(Neighbors {
+                before: snapshot[start .. index].iter().rev().cloned().collect(),
+                after: snapshot[index + 1 .. end].to_vec(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that gives each item up to `count` of its `Neighbors` on each side.
+///
+/// With a count of 1 these are the previous and next items, e.g. for
+/// links between posts, so the bind should be sorted first. The
+/// neighbors are copies of the items as they were at this point.
+#[inline]
+pub fn neighbors(count: usize) -> Neighbor {
+    Neighbor {
+        count: count,
+    }
+}
+
+/// The group of items that an item is in.
+#[derive(Clone)]
+pub struct Chunk {
+    /// The position of the group, from 0
+    pub index: usize,
+
+    /// The number of groups
+    pub count: usize,
+
+    pub items: Arc<Vec<Item>>,
+}
+
+impl typemap::Key for Chunk {
+    type Value = Chunk;
+}
+
+pub struct Chunks {
+    size: usize,
+}
+
+impl Handle<Bind> for Chunks {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        if self.size == 0 {
+            return Err(From::from("items can't be grouped in chunks of zero"));
+        }
+
+        let chunks: Vec<Arc<Vec<Item>>> =
+            bind.chunks(self.size)
+            .map(|chunk| Arc::new(chunk.to_vec()))
+            .collect();
+
+        let count = chunks.len();
+
+        for (position, item) in bind.items_mut().iter_mut().enumerate() {
+            let index = position / self.size;
+
+            item.extensions.insert::<Chunk>(Chunk {
+                index: index,
+                count: count,
+                items: chunks[index].clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that groups the items in `Chunk`s of `size`, e.g. rows of a gallery.
+///
+/// The items in a chunk are copies of the items as they were at this point.
+#[inline]
+pub fn chunks(size: usize) -> Chunks {
+    Chunks {
+        size: size,
+    }
+}
+
+/// The input files that a bind's output was derived from,
+/// other than the sources of its items.
+///
+/// Watchers can use this to rebuild a rule when, for example,
+/// an imported Sass partial changes.
+pub struct Imports;
+
+impl typemap::Key for Imports {
+    type Value = Arc<Vec<PathBuf>>;
+}
+
+#[cfg(feature = "sass")]
+pub struct Sass {
+    entry: PathBuf,
+    output: PathBuf,
+}
+
+#[cfg(feature = "sass")]
+impl Sass {
+    /// The files that a stylesheet may refer to by an import name.
+    fn candidates(directory: &Path, name: &str) -> Vec<PathBuf> {
+        let path = directory.join(name);
+
+        let stem =
+            path.file_name()
+            .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+
+        let mut candidates = vec![];
+
+        for extension in &["scss", "sass", "css"] {
+            candidates.push(path.with_file_name(format!("{}.{}", stem, extension)));
+            candidates.push(path.with_file_name(format!("_{}.{}", stem, extension)));
+        }
+
+        for extension in &["scss", "sass"] {
+            candidates.push(path.join(format!("_index.{}", extension)));
+            candidates.push(path.join(format!("index.{}", extension)));
+        }
+
+        candidates
+    }
+
+    /// Collect the partials imported by the stylesheet, recursively.
+    fn imports(&self, bind: &Bind, stylesheet: &Path, found: &mut Vec<PathBuf>) -> ::Result<()> {
+        use regex::Regex;
+
+        let sandbox = bind.configuration.sandbox();
+        let contents = sandbox.read_to_string(stylesheet)?;
+
+        let statement = Regex::new(r#"@(?:import|use|forward)\s+([^;\n]+)"#).unwrap();
+        let quoted = Regex::new(r#"["']([^"']+)["']"#).unwrap();
+
+        let directory = stylesheet.parent().unwrap_or(Path::new(""));
+
+        for captures in statement.captures_iter(&contents) {
+            for name in quoted.captures_iter(&captures[1]) {
+                let name = &name[1];
+
+                // built-in modules and plain CSS imports aren't partials
+                if name.starts_with("sass:") || name.contains("://") || name.ends_with(".css") {
+                    continue;
+                }
+
+                let partial =
+                    Sass::candidates(directory, name).into_iter()
+                    .find(|candidate| sandbox.exists(candidate));
+
+                if let Some(partial) = partial {
+                    if !found.contains(&partial) {
+                        found.push(partial.clone());
+                        self.imports(bind, &partial, found)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sass")]
+impl Handle<Bind> for Sass {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        use grass;
+
+        let entry = bind.configuration.input.join(&self.entry);
+
+        let css =
+            grass::from_path(&entry, &grass::Options::default())
+            .map_err(|e| format!("could not compile {}: {}", entry.display(), e))?;
+
+        let mut imports = vec![];
+        self.imports(bind, &entry, &mut imports)?;
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Imports>(Arc::new(imports)));
+
+        let mut item = Item::read_write(self.entry.clone(), self.output.clone());
+        item.body = css.into();
+
+        bind.attach(item);
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that compiles a Sass or SCSS stylesheet.
+///
+/// The entry is relative to the input directory and the output is
+/// relative to the output directory. The compiled stylesheet is
+/// attached as an item to be written, and the partials it imports
+/// are recorded in the bind's `Imports`.
+#[cfg(feature = "sass")]
+#[inline]
+pub fn sass<E, O>(entry: E, output: O) -> Sass
+where E: Into<PathBuf>, O: Into<PathBuf> {
+    Sass {
+        entry: entry.into(),
+        output: output.into(),
+    }
+}
@@ -1,10 +1,20 @@
 use std::any::Any;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use typemap;
+use toml;
 
+use configuration::Passthrough;
+use diag;
 use handler::Handle;
-use item::Item;
+use item::{self, Content, Item};
+use manifest::Checksum;
+use phase::{self, Phase};
 use support;
+use util::dates::{self, DateTime};
 
 use super::Extender;
 
@@ -16,20 +26,143 @@ where T: typemap::Key, T::Value: Any + Sync + Send + Clone {
     }
 }
 
+/// The number of files that a bind wrote with the same contents
+/// as in the previous build.
+pub struct Unchanged;
+
+impl typemap::Key for Unchanged {
+    type Value = usize;
+}
+
+/// The previous build's file for the target, if there is one.
+fn previous_of(item: &Item, to: &Path) -> Option<PathBuf> {
+    let configuration = &item.bind().configuration;
+
+    configuration.previous.as_ref()
+        .and_then(|previous| {
+            to.strip_prefix(&configuration.output).ok()
+                .map(|relative| previous.join(relative))
+        })
+        .and_then(|previous| {
+            if configuration.sandbox().exists(&previous) { Some(previous) }
+            else { None }
+        })
+}
+
+/// Reuse the previous build's file for the target.
+///
+/// The file is hard-linked into place, which preserves its modification
+/// time for mtime-based deploys and watchers. Returns whether it was reused.
+fn reuse(item: &Item, previous: &Path, to: &Path) -> bool {
+    if item.bind().configuration.sandbox().hard_link(previous, to).is_err() {
+        return false;
+    }
+
+    item.bind().with_ext_mut(|extensions| {
+        *extensions.entry::<Unchanged>().or_insert(0) += 1;
+    });
+
+    true
+}
+
+/// Whether the item's metadata turns off a step of its processing.
+///
+/// A step is off if its key is `false` or `"none"`, e.g. `minify = false`
+/// or `markup = "none"`. The built-in handlers honor these reserved keys:
+///
+/// * `minify`: `minify_css` and `minify_js`
+/// * `precompress`: `precompress`, and so `write`
+/// * `template`: the name of the template that `render_template` uses instead
+///
+/// Other steps, such as `markup` or `highlight`, can be made optional
+/// with `optional`.
+pub fn is_disabled(item: &Item, key: &str) -> bool {
+    match item.extensions.get::<item::Metadata>().and_then(|m| m.get(key)) {
+        Some(&toml::Value::Boolean(enabled)) => !enabled,
+        Some(&toml::Value::String(ref value)) => value == "none",
+        _ => false,
+    }
+}
+
+pub struct Optional<H>
+where H: Handle<Item> {
+    key: &'static str,
+    handler: H,
+}
+
+impl<H> Handle<Item> for Optional<H>
+where H: Handle<Item> {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        if is_disabled(item, self.key) {
+            return Ok(());
+        }
+
+        self.handler.handle(item)
+    }
+}
+
+/// Handle<Item> that runs the handler unless the item's metadata turns off its key.
+///
+/// This lets one rule host the occasional special page, e.g. with
+/// `item::optional("markup", markdown)` a page with `markup = "none"`
+/// is passed through as is.
+#[inline]
+pub fn optional<H>(key: &'static str, handler: H) -> Optional<H>
+where H: Handle<Item> {
+    Optional {
+        key: key,
+        handler: handler,
+    }
+}
+
+/// Handle<Item> that copies the `Item`'s source to its target.
+///
+/// The file is hard-linked or cloned instead if the configuration's
+/// `passthrough` asks for it and the file system supports it. The file
+/// is streamed rather than read into memory, and the body is left alone,
+/// so this suits large files such as videos and archives.
 pub fn copy(item: &mut Item) -> ::Result<()> {
-    use std::fs;
+    phase::enter(item, Phase::Write)?;
 
     if let Some(from) = item.source() {
         if let Some(to) = item.target() {
-            // TODO: once path normalization is in, make sure
-            // writing to output folder
+            let sandbox = item.bind().configuration.sandbox();
 
             if let Some(parent) = to.parent() {
-                // TODO: this errors out if the path already exists? dumb
-                support::mkdir_p(parent).unwrap();
+                sandbox.create_dir_all(parent)?;
+            }
+
+            // the body isn't what's written, so record a checksum
+            // of the file for the manifest instead
+            let checksum = support::hash_reader(sandbox.reader(&from)?)?;
+
+            if let Some(previous) = previous_of(item, &to) {
+                let unchanged =
+                    sandbox.reader(&previous)
+                    .and_then(support::hash_reader)
+                    .map(|existing| existing == checksum)
+                    .unwrap_or(false);
+
+                if unchanged && reuse(item, &previous, &to) {
+                    item.extensions.insert::<Checksum>(checksum);
+                    return Ok(());
+                }
+            }
+
+            let linked = match item.bind().configuration.passthrough {
+                Passthrough::Copy => false,
+                Passthrough::HardLink => sandbox.hard_link(&from, &to).is_ok(),
+                Passthrough::Reflink => sandbox.reflink(&from, &to).is_ok(),
+                Passthrough::Symlink => sandbox.symlink(&from, &to).is_ok(),
+            };
+
+            // fall back to copying if the input and output are on different
+            // file systems, or the file system doesn't support it
+            if !linked {
+                sandbox.copy(&from, &to)?;
             }
 
-            fs::copy(from, to)?;
+            item.extensions.insert::<Checksum>(checksum);
         }
     }
 
@@ -37,45 +170,703 @@ pub fn copy(item: &mut Item) -> ::Result<()> {
 }
 
 /// Handle<Item> that reads the `Item`'s body.
+///
+/// The body is binary if the file isn't valid UTF-8.
 pub fn read(item: &mut Item) -> ::Result<()> {
-    use std::fs::File;
-    use std::io::Read;
+    phase::enter(item, Phase::Source)?;
 
     if let Some(from) = item.source() {
-        let mut buf = String::new();
-
-        // TODO: use try!
-        File::open(from)
-            .unwrap()
-            .read_to_string(&mut buf)
-            .unwrap();
+        let body = item.bind().configuration.sandbox().read(&from)?;
 
-        item.body = buf;
+        item.body = Content::from_bytes(body);
     }
 
     Ok(())
 }
 
 /// Handle<Item> that writes the `Item`'s body.
+///
+/// If the previous build wrote the same contents to the target,
+/// that file is reused instead and counted as `Unchanged`.
 pub fn write(item: &mut Item) -> ::Result<()> {
-    use std::fs::File;
-    use std::io::Write;
+    phase::enter(item, Phase::Write)?;
 
     if let Some(to) = item.target() {
-        // TODO: once path normalization is in, make sure
-        // writing to output folder
+        let sandbox = item.bind().configuration.sandbox();
+
         if let Some(parent) = to.parent() {
-            // TODO: this errors out if the path already exists? dumb
-            support::mkdir_p(parent).unwrap();
+            sandbox.create_dir_all(parent)?;
+        }
+
+        let unchanged =
+            previous_of(item, &to)
+            .map_or(false, |previous| {
+                sandbox.read(&previous)
+                    .map(|existing| existing == item.body.as_bytes())
+                    .unwrap_or(false)
+                    && reuse(item, &previous, &to)
+            });
+
+        if !unchanged {
+            sandbox.write(&to, item.body.as_bytes())?;
+        }
+    }
+
+    precompress(item)
+}
+
+/// Extensions of the text outputs that are worth precompressing.
+static COMPRESSIBLE: [&'static str; 10] =
+    ["html", "htm", "css", "js", "json", "xml", "svg", "txt", "map", "md"];
+
+#[cfg(feature = "precompress")]
+fn gzip(body: &[u8]) -> ::Result<Vec<u8>> {
+    use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(body)?;
+
+    Ok(encoder.finish()?)
+}
+
+#[cfg(feature = "precompress")]
+fn brotli(body: &[u8]) -> ::Result<Vec<u8>> {
+    use std::io::Write;
+    use brotli::CompressorWriter;
+
+    let mut encoder = CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    encoder.write_all(body)?;
+
+    Ok(encoder.into_inner())
+}
+
+#[cfg(not(feature = "precompress"))]
+fn gzip(_body: &[u8]) -> ::Result<Vec<u8>> {
+    Err(From::from("gzip precompression requires the `precompress` feature"))
+}
+
+#[cfg(not(feature = "precompress"))]
+fn brotli(_body: &[u8]) -> ::Result<Vec<u8>> {
+    Err(From::from("brotli precompression requires the `precompress` feature"))
+}
+
+/// Handle<Item> that writes precompressed siblings of the `Item`'s target.
+///
+/// Depending on the configuration, `.gz` and `.br` files are written
+/// next to text outputs at least `precompress_threshold` bytes in size,
+/// for servers that serve precompressed files, e.g. nginx's `gzip_static`.
+/// `write` does this after writing, so this is only needed for items
+/// that are written by other means.
+pub fn precompress(item: &mut Item) -> ::Result<()> {
+    let configuration = item.bind().configuration.clone();
+
+    if !(configuration.gzip || configuration.brotli)
+        || item.body.len() < configuration.precompress_threshold
+        || is_disabled(item, "precompress") {
+        return Ok(());
+    }
+
+    let to = match item.target() {
+        Some(to) => to,
+        None => return Ok(()),
+    };
+
+    let compressible =
+        to.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| COMPRESSIBLE.iter().any(|c| c.eq_ignore_ascii_case(e)));
+
+    if !compressible {
+        return Ok(());
+    }
+
+    let sandbox = configuration.sandbox();
+    let file_name = to.file_name().unwrap().to_string_lossy().into_owned();
+
+    if configuration.gzip {
+        sandbox.write(&to.with_file_name(format!("{}.gz", file_name)),
+                      &gzip(item.body.as_bytes())?)?;
+    }
+
+    if configuration.brotli {
+        sandbox.write(&to.with_file_name(format!("{}.br", file_name)),
+                      &brotli(item.body.as_bytes())?)?;
+    }
+
+    Ok(())
+}
+
+/// Handle<Item> that minifies the `Item`'s body as CSS.
+#[cfg(feature = "minify")]
+pub fn minify_css(item: &mut Item) -> ::Result<()> {
+    use minifier::css;
+
+    if is_disabled(item, "minify") {
+        return Ok(());
+    }
+
+    let minified =
+        css::minify(item.text()?)
+        .map_err(|e| format!("could not minify {:?} as CSS: {}", item, e))?
+        .to_string();
+
+    item.body = minified.into();
+
+    Ok(())
+}
+
+/// Handle<Item> that minifies the `Item`'s body as JavaScript.
+#[cfg(feature = "minify")]
+pub fn minify_js(item: &mut Item) -> ::Result<()> {
+    use minifier::js;
+
+    if is_disabled(item, "minify") {
+        return Ok(());
+    }
+
+    let minified = js::minify(item.text()?).to_string();
+
+    item.body = minified.into();
+
+    Ok(())
+}
+
+pub struct PipeThrough {
+    program: String,
+    args: Vec<String>,
+    timeout: Option<Duration>,
+}
+
+impl PipeThrough {
+    /// Fail if the command takes longer than this. One minute by default.
+    pub fn timeout(mut self, timeout: Duration) -> PipeThrough {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Don't limit how long the command can take.
+    pub fn no_timeout(mut self) -> PipeThrough {
+        self.timeout = None;
+        self
+    }
+
+    fn command(&self) -> String {
+        let mut command = self.program.clone();
+
+        for arg in &self.args {
+            command.push(' ');
+            command.push_str(arg);
+        }
+
+        command
+    }
+}
+
+impl Handle<Item> for PipeThrough {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        use std::io::{Read, Write};
+        use std::process::{Command, Stdio};
+        use std::thread;
+        use std::time::Instant;
+
+        let mut child =
+            Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("could not run `{}` on {}: {}", self.command(), item, e))?;
+
+        // the pipes are drained on their own threads, so that a command
+        // that writes a lot before reading all of its input doesn't block
+        let mut stdin = child.stdin.take().unwrap();
+        let input = item.body.as_bytes().to_vec();
+        let writer = thread::spawn(move || stdin.write_all(&input));
+
+        let mut stdout = child.stdout.take().unwrap();
+        let reader = thread::spawn(move || {
+            let mut output = vec![];
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+
+        let mut stderr = child.stderr.take().unwrap();
+        let errors = thread::spawn(move || {
+            let mut errors = String::new();
+            let _ = stderr.read_to_string(&mut errors);
+            errors
+        });
+
+        let started = Instant::now();
+
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            if let Some(timeout) = self.timeout {
+                if started.elapsed() > timeout {
+                    let _ = child.kill();
+                    let _ = child.wait();
+
+                    return Err(From::from(
+                        format!("`{}` took longer than {:?} on {}",
+                                self.command(), timeout, item)));
+                }
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        let output = reader.join().unwrap()?;
+        let errors = errors.join().unwrap();
+
+        // a command may exit without reading all of its input
+        let _ = writer.join();
+
+        if !status.success() {
+            let reason = match status.code() {
+                Some(code) => format!("exited with code {}", code),
+                None => String::from("was killed by a signal"),
+            };
+
+            return Err(From::from(
+                format!("`{}` {} on {}:\n{}", self.command(), reason, item, errors.trim())));
+        }
+
+        item.body = Content::from_bytes(output);
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that pipes the `Item`'s body through an external command.
+///
+/// The body is written to the command's standard input and replaced by
+/// its standard output, e.g. `pipe_through("pandoc", &["-f", "rst", "-t", "html"])`.
+/// The command fails the item if it exits unsuccessfully, with what it
+/// wrote to standard error, or if it takes longer than its timeout.
+#[inline]
+pub fn pipe_through<S>(program: &str, args: &[S]) -> PipeThrough
+where S: AsRef<str> {
+    PipeThrough {
+        program: String::from(program),
+        args: args.iter().map(|arg| String::from(arg.as_ref())).collect(),
+        timeout: Some(Duration::from_secs(60)),
+    }
+}
+
+/// Split TOML front matter delimited by `---` lines from the rest of a body.
+///
+/// The rest of the body is a suffix of it, so the front matter can be
+/// written back verbatim.
+pub fn split_front_matter(body: &str) -> Option<(&str, &str)> {
+    let delimiter = "---";
+
+    let rest =
+        if body.starts_with("---\n") { &body[4..] }
+        else if body.starts_with("---\r\n") { &body[5..] }
+        else { return None };
+
+    let mut offset = 0;
+
+    for line in rest.split('\n') {
+        let end = offset + line.len();
+
+        if line.trim_right() == delimiter {
+            let metadata = &rest[..offset];
+            let body = if end < rest.len() { &rest[end + 1..] } else { "" };
+
+            return Some((metadata, body));
         }
 
-        // TODO: this sometimes crashes
-        File::create(&to)
-            .unwrap()
-            .write_all(item.body.as_bytes())
-            .unwrap();
+        offset = end + 1;
+    }
+
+    None
+}
+
+/// Handle<Item> that parses the `Item`'s front matter.
+///
+/// The front matter is TOML delimited by `---` lines at the very top
+/// of the body. It's stored as the `Metadata` extension and removed
+/// from the body, over the rule's default metadata, if any.
+///
+/// Front matter that isn't valid TOML is reported with `diag::warn`,
+/// with the line and column of the problem in the source file.
+pub fn parse_metadata(item: &mut Item) -> ::Result<()> {
+    // the front matter starts on the line after the opening delimiter,
+    // so a newline is prepended for the parser's lines to match the file's
+    let parsed =
+        item.body.as_str()
+        .and_then(split_front_matter)
+        .map(|(metadata, body)| (format!("\n{}", metadata).parse::<toml::Value>(), String::from(body)));
+
+    if let Some((metadata, body)) = parsed {
+        match metadata {
+            Ok(parsed) => {
+                item.extensions.insert::<item::Metadata>(parsed);
+            },
+            Err(e) => {
+                let source =
+                    item.source()
+                    .map_or_else(|| item.to_string(), |source| source.display().to_string());
+
+                diag::warn(item, format!("could not parse the front matter of {}: {}", source, e));
+            },
+        }
+
+        item.body = body.into();
+    }
+
+    if let Some(defaults) = item.default_metadata() {
+        let merged = match item.extensions.remove::<item::Metadata>() {
+            Some(toml::Value::Table(mut metadata)) => {
+                support::merge(&mut metadata, (*defaults).clone(), false);
+                metadata
+            },
+            _ => (*defaults).clone(),
+        };
+
+        item.extensions.insert::<item::Metadata>(toml::Value::Table(merged));
     }
 
     Ok(())
 }
 
+/// Which value wins when a key is defined in both
+/// the front matter and the sidecar file.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Precedence {
+    Sidecar,
+    FrontMatter,
+}
+
+pub struct Sidecar {
+    precedence: Precedence,
+}
+
+impl Sidecar {
+    fn candidates(source: &Path) -> Vec<PathBuf> {
+        let mut extensions = vec!["toml"];
+
+        if cfg!(feature = "yaml") {
+            extensions.push("yaml");
+            extensions.push("yml");
+        }
+
+        extensions.into_iter()
+            .map(|extension| source.with_extension(extension))
+            .filter(|candidate| candidate != source)
+            .collect()
+    }
+
+    #[cfg(feature = "yaml")]
+    fn parse_yaml(contents: &str) -> ::Result<toml::Value> {
+        use serde_yaml;
+
+        Ok(serde_yaml::from_str(contents)?)
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    fn parse_yaml(_contents: &str) -> ::Result<toml::Value> {
+        Err(From::from("YAML metadata requires the `yaml` feature"))
+    }
+}
+
+impl Handle<Item> for Sidecar {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let source = match item.source() {
+            Some(source) => source,
+            None => return Ok(()),
+        };
+
+        let parsed = {
+            let sandbox = item.bind().configuration.sandbox();
+
+            let found =
+                Sidecar::candidates(&source).into_iter()
+                .find(|candidate| sandbox.exists(candidate));
+
+            let path = match found {
+                Some(path) => path,
+                None => return Ok(()),
+            };
+
+            let contents = sandbox.read_to_string(&path)?;
+
+            let parsed = match path.extension().and_then(|e| e.to_str()) {
+                Some("toml") => contents.parse::<toml::Value>().map_err(From::from),
+                _ => Sidecar::parse_yaml(&contents),
+            };
+
+            match parsed {
+                Ok(toml::Value::Table(table)) => table,
+                Ok(_) => {
+                    return Err(From::from(
+                        format!("the metadata in {} must be a table", path.display())));
+                },
+                Err(e) => {
+                    return Err(From::from(
+                        format!("could not parse {}: {}", path.display(), e)));
+                },
+            }
+        };
+
+        let overwrite = self.precedence == Precedence::Sidecar;
+
+        let merged = match item.extensions.remove::<item::Metadata>() {
+            Some(toml::Value::Table(mut metadata)) => {
+                support::merge(&mut metadata, parsed, overwrite);
+                metadata
+            },
+            _ => parsed,
+        };
+
+        item.extensions.insert::<item::Metadata>(toml::Value::Table(merged));
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that merges metadata from a sidecar file.
+///
+/// The sidecar file sits next to the item's source with a `toml` extension,
+/// or a `yaml`/`yml` extension with the `yaml` feature, e.g. `photo.jpg`
+/// and `photo.toml`. This lets items that can't have front matter, such as
+/// images, carry metadata. It should run after `parse_metadata`.
+#[inline]
+pub fn sidecar(precedence: Precedence) -> Sidecar {
+    Sidecar {
+        precedence: precedence,
+    }
+}
+
+/// The files of directory-level defaults, in increasing precedence.
+pub static CASCADE_FILES: [&'static str; 2] = ["_defaults.toml", "_index.toml"];
+
+pub struct Cascade {
+    /// The defaults of each directory, relative to the input directory
+    defaults: Mutex<HashMap<PathBuf, Arc<toml::value::Table>>>,
+}
+
+impl Cascade {
+    /// The defaults of a directory, merged over those of its parents.
+    fn defaults(&self, item: &Item, directory: &Path) -> ::Result<Arc<toml::value::Table>> {
+        if let Some(defaults) = self.defaults.lock().unwrap().get(directory) {
+            return Ok(defaults.clone());
+        }
+
+        let mut defaults = match directory.parent() {
+            Some(parent) => (*self.defaults(item, parent)?).clone(),
+            None => toml::value::Table::new(),
+        };
+
+        let configuration = &item.bind().configuration;
+        let sandbox = configuration.sandbox();
+
+        for name in CASCADE_FILES.iter() {
+            let path = configuration.input.join(directory).join(name);
+
+            if !sandbox.exists(&path) {
+                continue;
+            }
+
+            let contents = sandbox.read_to_string(&path)?;
+
+            match contents.parse::<toml::Value>() {
+                Ok(toml::Value::Table(table)) => support::merge(&mut defaults, table, true),
+                Ok(_) => {
+                    return Err(From::from(
+                        format!("the defaults in {} must be a table", path.display())));
+                },
+                Err(e) => {
+                    return Err(From::from(
+                        format!("could not parse {}: {}", path.display(), e)));
+                },
+            }
+        }
+
+        let defaults = Arc::new(defaults);
+
+        self.defaults.lock().unwrap().insert(directory.to_path_buf(), defaults.clone());
+
+        Ok(defaults)
+    }
+}
+
+impl Handle<Item> for Cascade {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let directory = match item.route().reading().and_then(Path::parent) {
+            Some(directory) => directory.to_path_buf(),
+            None => return Ok(()),
+        };
+
+        let defaults = (*self.defaults(item, &directory)?).clone();
+
+        if defaults.is_empty() {
+            return Ok(());
+        }
+
+        let merged = match item.extensions.remove::<item::Metadata>() {
+            Some(toml::Value::Table(mut metadata)) => {
+                support::merge(&mut metadata, defaults, false);
+                metadata
+            },
+            _ => defaults,
+        };
+
+        item.extensions.insert::<item::Metadata>(toml::Value::Table(merged));
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that fills in metadata from the defaults of the
+/// directories the item's source is in.
+///
+/// Each directory of the input can have an `_index.toml` or a
+/// `_defaults.toml`, whose keys are defaults for every item beneath
+/// it, e.g. a `layout` or `section`. Deeper directories override their
+/// parents, and an item's own metadata overrides them all, so this
+/// should run after `parse_metadata`. Use one `cascade` per rule so
+/// that each directory is only read once.
+#[inline]
+pub fn cascade() -> Cascade {
+    Cascade {
+        defaults: Mutex::new(HashMap::new()),
+    }
+}
+
+/// Split a `YYYY-MM-DD-title` file stem into its date and title.
+///
+/// The date is at midnight in the site's timezone.
+fn split_dated<'a>(stem: &'a str, item: &Item) -> Option<(DateTime, &'a str)> {
+    if stem.len() < 11 || !stem.is_char_boundary(10) || stem.as_bytes()[10] != b'-' {
+        return None;
+    }
+
+    let (date, title) = (&stem[..10], &stem[11..]);
+
+    if title.is_empty() {
+        return None;
+    }
+
+    dates::parse(date, &item.bind().configuration.timezone).ok().map(|date| (date, title))
+}
+
+/// Handle<Item> that takes the date and slug from a Jekyll-style file name.
+///
+/// A source named e.g. `2015-03-21-hello-world.md` gets the `Date` of
+/// 2015-03-21 and the `Slug` `hello-world`. Other sources get their
+/// file stem as the `Slug`, and their date from the `date` metadata
+/// field, as with `dates::date`, so it should run after `parse_metadata`.
+pub fn filename_date(item: &mut Item) -> ::Result<()> {
+    let stem =
+        item.route().reading()
+        .and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned());
+
+    let stem = match stem {
+        Some(stem) => stem,
+        None => return dates::date(item),
+    };
+
+    match split_dated(&stem, item) {
+        Some((parsed, slug)) => {
+            item.extensions.insert::<item::Date>(parsed);
+            item.extensions.insert::<item::Slug>(String::from(slug));
+        },
+        None => {
+            item.extensions.insert::<item::Slug>(stem.clone());
+            dates::date(item)?;
+        },
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    #[cfg(unix)]
+    use std::time::Duration;
+
+    use toml;
+
+    use bind::{self, Bind};
+    use configuration::Configuration;
+    use handler::Handle;
+    use item::{self, Item};
+    use vfs::Memory;
+    use super::cascade;
+    #[cfg(unix)]
+    use super::pipe_through;
+
+    #[cfg(unix)]
+    fn pipe<H>(handler: H, body: &str) -> ::Result<String>
+    where H: Handle<Item> {
+        let mut item = Item::writing("index.html");
+        item.body = body.into();
+
+        handler.handle(&mut item)?;
+
+        Ok(String::from(item.text()?))
+    }
+
+    #[test]
+    fn cascade_precedence() {
+        let memory = Memory::new();
+        memory.insert("input/_defaults.toml", "layout = \"page\"\nsection = \"site\"\nauthor = \"root\"\n");
+        memory.insert("input/posts/_defaults.toml", "layout = \"post\"\nsection = \"blog\"\n");
+        memory.insert("input/posts/_index.toml", "section = \"posts\"\n");
+
+        let configuration = Arc::new(Configuration::default().vfs(memory));
+        let mut bind = Bind::new(bind::Data::new(String::from("posts"), configuration));
+
+        let mut item = Item::reading("posts/hello.md");
+        item.extensions.insert::<item::Metadata>("author = \"me\"".parse::<toml::Value>().unwrap());
+        bind.attach(item);
+
+        let handler = cascade();
+        let item = &mut bind.items_mut()[0];
+        handler.handle(item).unwrap();
+
+        let metadata = item.extensions.get::<item::Metadata>().unwrap();
+        let get = |key: &str| metadata.get(key).and_then(toml::Value::as_str);
+
+        // deeper directories override their parents, _index.toml overrides
+        // _defaults.toml, and the item's own metadata overrides them all
+        assert_eq!(get("layout"), Some("post"));
+        assert_eq!(get("section"), Some("posts"));
+        assert_eq!(get("author"), Some("me"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_through_commands() {
+        assert_eq!(pipe(pipe_through::<&str>("cat", &[]), "hello").unwrap(), "hello");
+        assert_eq!(pipe(pipe_through("tr", &["a-z", "A-Z"]), "hello").unwrap(), "HELLO");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_through_failure() {
+        let error = pipe(pipe_through("sh", &["-c", "echo oops >&2; exit 3"]), "hello").err().unwrap();
+        let message = error.to_string();
+
+        assert!(message.contains("exited with code 3"));
+        assert!(message.contains("oops"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipe_through_timeout() {
+        let handler = pipe_through("sleep", &["5"]).timeout(Duration::from_millis(50));
+        let error = pipe(handler, "hello").err().unwrap();
+
+        assert!(error.to_string().contains("took longer than 50ms"));
+    }
+}
@@ -0,0 +1,686 @@
+//! HTML processing.
+
+use std::cell::RefCell;
+use std::cmp;
+use std::path::{Component, Path};
+
+use handler::Handle;
+use item::Item;
+use util::handle::item::is_disabled;
+
+/// Elements whose contents must be preserved verbatim.
+static PRESERVED: [&'static str; 4] = ["pre", "textarea", "script", "style"];
+
+/// Whether the lowercased input starts with an opening tag of the given name.
+fn opens(lowercased: &str, name: &str) -> bool {
+    lowercased.starts_with('<')
+        && lowercased[1..].starts_with(name)
+        && lowercased[1 + name.len()..].chars().next()
+            .map_or(false, |c| c == '>' || c == '/' || c.is_whitespace())
+}
+
+/// Escape text for inclusion in HTML or XML.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Insert markup at the end of the document's `<head>`.
+///
+/// Without a `</head>`, it goes before the `<body>`, or at the very
+/// start if there's no `<body>` either.
+pub fn inject_head(html: &str, markup: &str) -> String {
+    // ASCII lowercasing preserves byte offsets
+    let lowercased = html.to_ascii_lowercase();
+
+    let index =
+        lowercased.find("</head")
+        .or_else(|| {
+            lowercased.match_indices("<body")
+                .map(|(index, _)| index)
+                .find(|&index| opens(&lowercased[index..], "body"))
+        })
+        .unwrap_or(0);
+
+    let mut injected = String::with_capacity(html.len() + markup.len() + 1);
+
+    injected.push_str(&html[..index]);
+    injected.push_str(markup);
+
+    if !markup.ends_with('\n') {
+        injected.push('\n');
+    }
+
+    injected.push_str(&html[index..]);
+    injected
+}
+
+/// Extract the plain text of HTML.
+///
+/// Tags are removed, along with the contents of `script` and `style`
+/// elements and comments. Common entities are decoded and whitespace
+/// is collapsed.
+pub fn text(html: &str) -> String {
+    let lowercased = html.to_ascii_lowercase();
+
+    let mut text = String::with_capacity(html.len());
+    let mut index = 0;
+
+    while index < html.len() {
+        let rest = &html[index..];
+        let lower = &lowercased[index..];
+
+        if rest.starts_with("<!--") {
+            index += rest.find("-->").map_or(rest.len(), |end| end + 3);
+            continue;
+        }
+
+        if let Some(name) = ["script", "style"].iter().find(|name| opens(lower, name)) {
+            let closing = format!("</{}", name);
+            index += lower.find(&closing[..]).unwrap_or(rest.len());
+        }
+
+        let rest = &html[index..];
+
+        if rest.starts_with('<') {
+            index += rest.find('>').map_or(rest.len(), |end| end + 1);
+            text.push(' ');
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        text.push_str(&rest[..end]);
+        index += end;
+    }
+
+    let decoded =
+        text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The length of the tag at the start of the input, including the closing `>`.
+fn tag_length(rest: &str) -> usize {
+    let mut quote = None;
+
+    for (index, c) in rest.char_indices() {
+        match quote {
+            Some(q) => if c == q { quote = None },
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '>' => return index + 1,
+                _ => (),
+            },
+        }
+    }
+
+    rest.len()
+}
+
+fn is_space(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t' || byte == b'\n' || byte == b'\r' || byte == b'\x0c'
+}
+
+/// Rewrite the values of the given attributes of a single tag.
+fn rewrite_tag<F>(tag: &str, attributes: &[&str], rewrite: &F) -> String
+where F: Fn(&str, &str) -> Option<String> {
+    let bytes = tag.as_bytes();
+    let mut rewritten = String::with_capacity(tag.len());
+    let mut copied = 0;
+
+    // skip the tag name
+    let mut index = 1;
+
+    while index < bytes.len() && !is_space(bytes[index]) && bytes[index] != b'>' && bytes[index] != b'/' {
+        index += 1;
+    }
+
+    loop {
+        while index < bytes.len() && (is_space(bytes[index]) || bytes[index] == b'/') {
+            index += 1;
+        }
+
+        if index >= bytes.len() || bytes[index] == b'>' {
+            break;
+        }
+
+        let name_start = index;
+
+        while index < bytes.len() && !is_space(bytes[index]) && !b"=>/".contains(&bytes[index]) {
+            index += 1;
+        }
+
+        let name = tag[name_start..index].to_ascii_lowercase();
+
+        let mut equals = index;
+
+        while equals < bytes.len() && is_space(bytes[equals]) {
+            equals += 1;
+        }
+
+        // an attribute without a value
+        if equals >= bytes.len() || bytes[equals] != b'=' {
+            continue;
+        }
+
+        index = equals + 1;
+
+        while index < bytes.len() && is_space(bytes[index]) {
+            index += 1;
+        }
+
+        let (start, end, next) = match bytes.get(index) {
+            Some(&quote) if quote == b'"' || quote == b'\'' => {
+                let start = index + 1;
+                let end = tag[start..].find(quote as char).map_or(tag.len(), |end| start + end);
+
+                (start, end, cmp::min(end + 1, tag.len()))
+            },
+            _ => {
+                let mut end = index;
+
+                while end < bytes.len() && !is_space(bytes[end]) && bytes[end] != b'>' {
+                    end += 1;
+                }
+
+                (index, end, end)
+            },
+        };
+
+        if attributes.contains(&&name[..]) {
+            if let Some(url) = rewrite(&name, &tag[start..end]) {
+                rewritten.push_str(&tag[copied..start]);
+                rewritten.push_str(&url);
+                copied = end;
+            }
+        }
+
+        index = next;
+    }
+
+    rewritten.push_str(&tag[copied..]);
+    rewritten
+}
+
+/// Rewrite the URLs in the `href` and `src` attributes of HTML.
+///
+/// The closure returns the replacement of a URL, or `None` to leave
+/// it as it is. The contents of comments and of `script` and `style`
+/// elements aren't touched.
+pub fn rewrite_urls<F>(html: &str, rewrite: F) -> String
+where F: Fn(&str) -> Option<String> {
+    rewrite_attributes(html, &["href", "src"], |_name, url| rewrite(url))
+}
+
+/// The values of the given attributes in HTML, in order, along with
+/// the lowercased attribute names.
+pub fn attributes(html: &str, names: &[&str]) -> Vec<(String, String)> {
+    let found = RefCell::new(vec![]);
+
+    rewrite_attributes(html, names, |name, value| {
+        found.borrow_mut().push((String::from(name), String::from(value)));
+        None
+    });
+
+    found.into_inner()
+}
+
+/// Rewrite the values of the given attributes of HTML.
+///
+/// Attribute names are matched case-insensitively and passed to the
+/// closure lowercased, along with the value.
+pub fn rewrite_attributes<F>(html: &str, names: &[&str], rewrite: F) -> String
+where F: Fn(&str, &str) -> Option<String> {
+    let lowercased = html.to_ascii_lowercase();
+
+    let mut rewritten = String::with_capacity(html.len());
+    let mut index = 0;
+
+    while index < html.len() {
+        let rest = &html[index..];
+        let lower = &lowercased[index..];
+
+        if rest.starts_with("<!--") {
+            let end = rest.find("-->").map_or(rest.len(), |end| end + 3);
+            rewritten.push_str(&rest[..end]);
+            index += end;
+            continue;
+        }
+
+        // the text may start with a multibyte character
+        let first = rest.chars().next().map_or(1, char::len_utf8);
+
+        let is_tag =
+            rest.starts_with('<')
+            && rest[first..].chars().next().map_or(false, |c| c.is_ascii_alphabetic());
+
+        if !is_tag {
+            let end = rest[first..].find('<').map_or(rest.len(), |end| end + first);
+            rewritten.push_str(&rest[..end]);
+            index += end;
+            continue;
+        }
+
+        let end = tag_length(rest);
+        rewritten.push_str(&rewrite_tag(&rest[..end], names, &rewrite));
+
+        if let Some(name) = ["script", "style"].iter().find(|name| opens(lower, name)) {
+            let closing = format!("</{}", name);
+            let raw = lower[end..].find(&closing[..]).map_or(rest.len(), |start| end + start);
+
+            rewritten.push_str(&rest[end..raw]);
+            index += raw;
+            continue;
+        }
+
+        index += end;
+    }
+
+    rewritten
+}
+
+/// The names of a relative path's components.
+fn segments(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Rebase a relative URL that's relative to the `from` directory
+/// to be relative to the `to` directory instead.
+///
+/// Returns `None` for URLs that aren't relative, or that escape the root.
+fn rebase(url: &str, from: &Path, to: &Path) -> Option<String> {
+    let has_scheme = url.find(':').map_or(false, |colon| !url[..colon].contains('/'));
+
+    if url.is_empty() || url.starts_with('/') || url.starts_with('#') || url.starts_with('?')
+        || has_scheme {
+        return None;
+    }
+
+    let (path, suffix) = url.split_at(url.find(|c| c == '?' || c == '#').unwrap_or(url.len()));
+
+    let mut resolved = segments(from);
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => (),
+            ".." => {
+                resolved.pop()?;
+            },
+            segment => resolved.push(String::from(segment)),
+        }
+    }
+
+    let target = segments(to);
+
+    let common =
+        resolved.iter().zip(target.iter())
+        .take_while(|&(a, b)| a == b)
+        .count();
+
+    let mut rebased = "../".repeat(target.len() - common);
+    rebased.push_str(&resolved[common..].join("/"));
+
+    if rebased.is_empty() {
+        rebased.push_str("./");
+    } else if path.ends_with('/') && !rebased.ends_with('/') {
+        rebased.push('/');
+    }
+
+    rebased.push_str(suffix);
+
+    Some(rebased)
+}
+
+/// Handle<Item> that keeps relative links working when routing moves the `Item`.
+///
+/// Routes such as `route::pretty` move `posts/hello.md` to
+/// `posts/hello/index.html`, one directory deeper, which would break
+/// a relative link such as `diagram.png`. Each relative `href` and
+/// `src` is rewritten to point to the same file from the target's
+/// directory, e.g. `../diagram.png`. This must run after routing.
+pub fn rebase_links(item: &mut Item) -> ::Result<()> {
+    let (from, to) = match (item.route().reading(), item.route().writing()) {
+        (Some(from), Some(to)) => {
+            (from.parent().unwrap_or(Path::new("")).to_path_buf(),
+             to.parent().unwrap_or(Path::new("")).to_path_buf())
+        },
+        _ => return Ok(()),
+    };
+
+    if from == to {
+        return Ok(());
+    }
+
+    let rebased = rewrite_urls(item.text()?, |url| rebase(url, &from, &to));
+
+    item.body = rebased.into();
+
+    Ok(())
+}
+
+/// Whether the URL is relative to the site root, e.g. `/posts/`.
+fn is_root_relative(url: &str) -> bool {
+    url.starts_with('/') && !url.starts_with("//")
+}
+
+/// Minify HTML.
+///
+/// The contents of `pre`, `textarea`, `script`, and `style` elements
+/// are left untouched, as are conditional comments.
+pub fn minify(html: &str, strip_comments: bool, collapse_whitespace: bool) -> String {
+    // ASCII lowercasing preserves byte offsets
+    let lowercased = html.to_ascii_lowercase();
+
+    let mut minified = String::with_capacity(html.len());
+    let mut after_space = false;
+    let mut index = 0;
+
+    while index < html.len() {
+        let rest = &html[index..];
+        let lower = &lowercased[index..];
+
+        if strip_comments && rest.starts_with("<!--") && !rest.starts_with("<!--[") {
+            match rest.find("-->") {
+                Some(end) => {
+                    index += end + 3;
+                    continue;
+                },
+                None => break,
+            }
+        }
+
+        if let Some(name) = PRESERVED.iter().find(|name| opens(lower, name)) {
+            let closing = format!("</{}", name);
+
+            let end =
+                lower.find(&closing)
+                .and_then(|start| lower[start..].find('>').map(|end| start + end + 1))
+                .unwrap_or(lower.len());
+
+            minified.push_str(&rest[..end]);
+            after_space = false;
+            index += end;
+            continue;
+        }
+
+        let c = rest.chars().next().unwrap();
+
+        if collapse_whitespace && c.is_whitespace() {
+            if !after_space {
+                minified.push(' ');
+                after_space = true;
+            }
+        } else {
+            minified.push(c);
+            after_space = false;
+        }
+
+        index += c.len_utf8();
+    }
+
+    if collapse_whitespace {
+        minified.trim().to_string()
+    } else {
+        minified
+    }
+}
+
+/// Handle<Item> that minifies the `Item`'s body as HTML.
+///
+/// This should run after the body has been rendered into its layout
+/// and before it's written.
+#[derive(Copy, Clone)]
+pub struct MinifyHtml {
+    strip_comments: bool,
+    collapse_whitespace: bool,
+}
+
+impl MinifyHtml {
+    /// Whether to remove comments. Defaults to true.
+    pub fn strip_comments(mut self, strip_comments: bool) -> MinifyHtml {
+        self.strip_comments = strip_comments;
+        self
+    }
+
+    /// Whether to collapse runs of whitespace. Defaults to true.
+    pub fn collapse_whitespace(mut self, collapse_whitespace: bool) -> MinifyHtml {
+        self.collapse_whitespace = collapse_whitespace;
+        self
+    }
+}
+
+impl Handle<Item> for MinifyHtml {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        if is_disabled(item, "minify") {
+            return Ok(());
+        }
+
+        let minified = minify(item.text()?, self.strip_comments, self.collapse_whitespace);
+
+        item.body = minified.into();
+
+        Ok(())
+    }
+}
+
+#[inline]
+pub fn minify_html() -> MinifyHtml {
+    MinifyHtml {
+        strip_comments: true,
+        collapse_whitespace: true,
+    }
+}
+
+/// How `RewriteLinks` rewrites root-relative links.
+#[derive(Clone, Debug)]
+enum Links {
+    /// Make them absolute with the base URL.
+    Absolute,
+
+    /// Prefix them with a path, or the path of the base URL if there is none.
+    Prefix(Option<String>),
+}
+
+/// Handle<Item> that rewrites the root-relative links of the `Item`'s body.
+///
+/// Links are written relative to the site root, e.g. `/posts/hello/`,
+/// so that they're independent of where the site is deployed. Feeds
+/// need them to be absolute, and sites hosted under a sub-path such as
+/// `/blog/` need them to be prefixed with it.
+#[derive(Clone, Debug)]
+pub struct RewriteLinks {
+    links: Links,
+}
+
+impl RewriteLinks {
+    /// Prefix links with the given path rather than that of the base URL.
+    pub fn prefix<S>(mut self, prefix: S) -> RewriteLinks
+    where S: Into<String> {
+        self.links = Links::Prefix(Some(prefix.into()));
+        self
+    }
+}
+
+impl Handle<Item> for RewriteLinks {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let configuration = item.bind().configuration.clone();
+
+        let rewritten = match self.links {
+            Links::Absolute => {
+                if configuration.base_url.is_none() {
+                    return Err(From::from(
+                        "absolute links require `diecast.base_url` to be configured"));
+                }
+
+                rewrite_urls(item.text()?, |url| {
+                    if is_root_relative(url) {
+                        Some(configuration.absolute_url(url))
+                    } else {
+                        None
+                    }
+                })
+            },
+            Links::Prefix(ref prefix) => {
+                let prefix = match *prefix {
+                    Some(ref prefix) => prefix.trim_end_matches('/'),
+                    None => configuration.base_path(),
+                };
+
+                if prefix.is_empty() {
+                    return Ok(());
+                }
+
+                rewrite_urls(item.text()?, |url| {
+                    let prefixed =
+                        url == prefix
+                        || url.starts_with(prefix) && url[prefix.len()..].starts_with('/');
+
+                    if is_root_relative(url) && !prefixed {
+                        Some(format!("{}{}", prefix, url))
+                    } else {
+                        None
+                    }
+                })
+            },
+        };
+
+        item.body = rewritten.into();
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that makes root-relative links absolute with `Configuration::base_url`.
+///
+/// This is meant for content that's read outside of the site, such as feeds.
+#[inline]
+pub fn absolute_links() -> RewriteLinks {
+    RewriteLinks {
+        links: Links::Absolute,
+    }
+}
+
+/// Handle<Item> that prefixes root-relative links with the path of
+/// `Configuration::base_url`, e.g. `/blog`.
+#[inline]
+pub fn prefix_links() -> RewriteLinks {
+    RewriteLinks {
+        links: Links::Prefix(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{minify, text, rewrite_urls, attributes, rebase, inject_head};
+
+    #[test]
+    fn collapse_whitespace() {
+        assert_eq!(minify("<p>\n  a   b\n</p>\n", true, true), "<p> a b </p>");
+    }
+
+    #[test]
+    fn strip_comments() {
+        assert_eq!(minify("<p>a<!-- hidden --></p>", true, true), "<p>a</p>");
+        assert_eq!(minify("<!--[if IE]>x<![endif]-->", true, true),
+                   "<!--[if IE]>x<![endif]-->");
+        assert_eq!(minify("<p>a<!-- kept --></p>", false, true), "<p>a<!-- kept --></p>");
+    }
+
+    #[test]
+    fn preserve_verbatim_elements() {
+        let html = "<pre>\n  fn main() {}\n</pre>  <PRE>a  b</PRE>";
+
+        assert_eq!(minify(html, true, true),
+                   "<pre>\n  fn main() {}\n</pre> <PRE>a  b</PRE>");
+    }
+
+    #[test]
+    fn plain_text() {
+        let html = "<h1>Title</h1>\n<p>a &amp; <em>b</em></p><script>x < y</script><!-- c -->";
+
+        assert_eq!(text(html), "Title a & b");
+    }
+
+    #[test]
+    fn rewrite_links() {
+        let html = "<a class=x href=\"/a/\">a</a> <IMG SRC='/b.png'> <a href=c.html>\
+                    <script src=/d.js>var e = '<a href=\"/e\">';</script>";
+
+        let rewritten = rewrite_urls(html, |url| {
+            if url.starts_with('/') { Some(format!("/blog{}", url)) } else { None }
+        });
+
+        assert_eq!(rewritten,
+                   "<a class=x href=\"/blog/a/\">a</a> <IMG SRC='/blog/b.png'> <a href=c.html>\
+                    <script src=/blog/d.js>var e = '<a href=\"/e\">';</script>");
+    }
+
+    #[test]
+    fn rebase_relative_urls() {
+        let (from, to) = (Path::new("posts"), Path::new("posts/hello"));
+
+        assert_eq!(rebase("diagram.png", from, to), Some(String::from("../diagram.png")));
+        assert_eq!(rebase("./a/b.png?v=1#c", from, to), Some(String::from("../a/b.png?v=1#c")));
+        assert_eq!(rebase("../about/", from, to), Some(String::from("../../about/")));
+        assert_eq!(rebase("hello/x.png", from, to), Some(String::from("x.png")));
+        assert_eq!(rebase("/abs.png", from, to), None);
+        assert_eq!(rebase("https://example.com/", from, to), None);
+        assert_eq!(rebase("#top", from, to), None);
+        assert_eq!(rebase("../../escape.png", from, to), None);
+    }
+
+    #[test]
+    fn inject_into_head() {
+        let tag = "<meta name=x>";
+
+        assert_eq!(inject_head("<HEAD><title>a</title></HEAD><body>", tag),
+                   "<HEAD><title>a</title><meta name=x>\n</HEAD><body>");
+        assert_eq!(inject_head("<p><bodyguard></p><body class=a>b", tag),
+                   "<p><bodyguard></p><meta name=x>\n<body class=a>b");
+        assert_eq!(inject_head("<p>a</p>", tag), "<meta name=x>\n<p>a</p>");
+    }
+
+    #[test]
+    fn attribute_values() {
+        let html = "<h2 ID=top>a</h2><a name='b' href=\"#top\">";
+
+        assert_eq!(attributes(html, &["id", "name"]),
+                   vec![(String::from("id"), String::from("top")),
+                        (String::from("name"), String::from("b"))]);
+    }
+
+    #[test]
+    fn rewrite_non_ascii_text() {
+        let html = "é<p>ñ <a href=/ü.html>ö</a><<é";
+
+        let rewritten = rewrite_urls(html, |url| Some(format!("/blog{}", url)));
+
+        assert_eq!(rewritten, "é<p>ñ <a href=/blog/ü.html>ö</a><<é");
+        assert_eq!(attributes("ñ<a href=x>", &["href"]),
+                   vec![(String::from("href"), String::from("x"))]);
+    }
+}
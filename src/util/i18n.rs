@@ -0,0 +1,436 @@
+//! Internationalization.
+//!
+//! The languages of a site are listed in `Configuration::languages`,
+//! default first. A content file is in a language if it has the
+//! language's code as a suffix of its file stem, e.g. `about.fr.md`,
+//! or as its first directory, e.g. `fr/about.md`; otherwise it's in
+//! the default language. Either way, its `Identity` is its path without
+//! the language, e.g. `about.md`, so the translations of a page are the
+//! items with the same identity.
+//!
+//! A rule can be expanded into one rule per language with `per_language`:
+//!
+//! ```ignore
+//! let languages = vec![String::from("en"), String::from("fr")];
+//!
+//! let posts = i18n::per_language(&languages, |language| {
+//!     Rule::named(format!("posts-{}", language))
+//!     .handler(chain![
+//!         bind::select(and!(glob!("posts/*.md"), i18n::in_language(&languages, language))),
+//!         bind::each(chain![item::read, i18n::detect, route::pretty]),
+//!         i18n::strings("i18n"),
+//!         bind::each(chain![tera::render_template("post", context::item), item::write])])
+//!     .build()
+//! });
+//! ```
+//!
+//! Translated strings are read from a TOML file per language, e.g.
+//! `i18n/fr.toml`, by `strings`. With the `tera` feature, templates
+//! registered after them can call `t(key="read_more", lang=lang)`.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use toml;
+use typemap;
+
+use bind::Bind;
+use handler::Handle;
+use item::Item;
+use pattern::Pattern;
+use rule::Rule;
+use util::html;
+
+/// The language of an item, e.g. `fr`.
+pub struct Language;
+
+impl typemap::Key for Language {
+    type Value = String;
+}
+
+/// The path of an item's source without its language, e.g. `about.md`.
+pub struct Identity;
+
+impl typemap::Key for Identity {
+    type Value = PathBuf;
+}
+
+/// The URLs of an item's translations, including itself, by language.
+pub struct Translations;
+
+impl typemap::Key for Translations {
+    type Value = BTreeMap<String, String>;
+}
+
+/// Split the language from a path.
+///
+/// Returns the language, if the path is tagged with one of the given
+/// languages, and the path without it.
+pub fn split<'a>(path: &Path, languages: &'a [String]) -> (Option<&'a str>, PathBuf) {
+    let mut components = path.components();
+
+    if let Some(Component::Normal(first)) = components.next() {
+        let directory = languages.iter().find(|language| first.to_str() == Some(&language[..]));
+
+        if let Some(language) = directory {
+            // only a directory, not a file named after the language
+            if components.clone().next().is_some() {
+                return (Some(&language[..]), components.as_path().to_path_buf());
+            }
+        }
+    }
+
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+
+    if let Some(stem) = stem {
+        for language in languages {
+            let suffix = format!(".{}", language);
+
+            if stem.ends_with(&suffix) && stem.len() > suffix.len() {
+                let mut name = String::from(&stem[.. stem.len() - suffix.len()]);
+
+                if let Some(extension) = path.extension() {
+                    name.push('.');
+                    name.push_str(&extension.to_string_lossy());
+                }
+
+                return (Some(&language[..]), path.with_file_name(name));
+            }
+        }
+    }
+
+    (None, path.to_path_buf())
+}
+
+/// The language of a path, which is the default language if it isn't tagged.
+pub fn language_of<'a>(path: &Path, languages: &'a [String]) -> Option<&'a str> {
+    split(path, languages).0.or_else(|| languages.first().map(|language| &language[..]))
+}
+
+/// Matches the paths in a language.
+pub struct InLanguage {
+    languages: Vec<String>,
+    language: String,
+}
+
+impl Pattern for InLanguage {
+    fn matches(&self, path: &Path) -> bool {
+        language_of(path, &self.languages) == Some(&self.language[..])
+    }
+}
+
+/// A pattern that matches the paths in the given language.
+pub fn in_language<L>(languages: &[String], language: L) -> InLanguage
+where L: Into<String> {
+    InLanguage {
+        languages: languages.to_vec(),
+        language: language.into(),
+    }
+}
+
+/// One rule per language, made by the closure from the language's code.
+///
+/// Rule names must be unique, so the closure should include the code
+/// in the name.
+pub fn per_language<F>(languages: &[String], rule: F) -> Vec<Rule>
+where F: Fn(&str) -> Rule {
+    languages.iter().map(|language| rule(language)).collect()
+}
+
+/// Handle<Item> that sets the item's `Language` and `Identity`.
+///
+/// The `lang` metadata field overrides the language of the path.
+/// Items that aren't read from a file are left alone.
+pub fn detect(item: &mut Item) -> ::Result<()> {
+    use item::Metadata;
+
+    let source = match item.route().reading() {
+        Some(source) => source.to_path_buf(),
+        None => return Ok(()),
+    };
+
+    let (language, identity) = {
+        let languages = &item.bind().configuration.languages;
+
+        if languages.is_empty() {
+            return Err(From::from("no languages are configured in `diecast.languages`"));
+        }
+
+        let (language, identity) = split(&source, languages);
+
+        let language =
+            item.extensions.get::<Metadata>()
+            .and_then(|m| m.get("lang"))
+            .and_then(toml::Value::as_str)
+            .map(String::from)
+            .or_else(|| language.map(String::from))
+            .unwrap_or_else(|| languages[0].clone());
+
+        if !languages.contains(&language) {
+            return Err(From::from(format!(
+                "{} is in `{}`, which isn't one of the configured languages", item, language)));
+        }
+
+        (language, identity)
+    };
+
+    item.extensions.insert::<Language>(language);
+    item.extensions.insert::<Identity>(identity);
+
+    Ok(())
+}
+
+/// The translated strings of each language.
+#[derive(Clone, Debug, Default)]
+pub struct Strings {
+    default: Option<String>,
+    languages: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Strings {
+    /// The string for the key in the language, or in the default language.
+    pub fn translate(&self, language: &str, key: &str) -> Option<&str> {
+        let lookup = |language: &str| {
+            self.languages.get(language)
+                .and_then(|strings| strings.get(key))
+                .map(|string| &string[..])
+        };
+
+        lookup(language).or_else(|| self.default.as_ref().and_then(|default| lookup(default)))
+    }
+}
+
+impl typemap::Key for Strings {
+    type Value = Arc<Strings>;
+}
+
+/// Flatten nested tables into dotted keys, e.g. `nav.home`.
+fn flatten(prefix: &str, table: &toml::value::Table, strings: &mut BTreeMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+
+        match *value {
+            toml::Value::String(ref string) => { strings.insert(key, string.clone()); },
+            toml::Value::Table(ref table) => flatten(&key, table, strings),
+            _ => (),
+        }
+    }
+}
+
+pub struct LoadStrings {
+    directory: PathBuf,
+}
+
+impl Handle<Bind> for LoadStrings {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut strings = Strings::default();
+
+        {
+            let configuration = &bind.configuration;
+            let sandbox = configuration.sandbox();
+
+            strings.default = configuration.languages.first().cloned();
+
+            for language in &configuration.languages {
+                let path = configuration.input.join(&self.directory).join(format!("{}.toml", language));
+
+                if !sandbox.exists(&path) {
+                    continue;
+                }
+
+                let table = match sandbox.read_to_string(&path)?.parse::<toml::Value>() {
+                    Ok(toml::Value::Table(table)) => table,
+                    Ok(_) => return Err(From::from(format!("{:?} isn't a table", path))),
+                    Err(e) => return Err(From::from(format!("could not parse {:?}: {}", path, e))),
+                };
+
+                let mut flattened = BTreeMap::new();
+                flatten("", &table, &mut flattened);
+
+                strings.languages.insert(language.clone(), flattened);
+            }
+        }
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Strings>(Arc::new(strings)));
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that reads the translated strings for templates.
+///
+/// Each language's strings are read from the TOML file named after it
+/// in the directory, relative to the input directory. Nested tables
+/// become dotted keys. Missing strings fall back to the default language.
+#[inline]
+pub fn strings<P>(directory: P) -> LoadStrings
+where P: Into<PathBuf> {
+    LoadStrings {
+        directory: directory.into(),
+    }
+}
+
+pub struct Link {
+    dependencies: Vec<String>,
+}
+
+impl Link {
+    /// Also find translations among the items of a dependency.
+    pub fn from<D>(mut self, dependency: D) -> Link
+    where D: Into<String> {
+        self.dependencies.push(dependency.into());
+        self
+    }
+}
+
+impl Handle<Bind> for Link {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut translations: BTreeMap<PathBuf, BTreeMap<String, String>> = BTreeMap::new();
+
+        {
+            let mut index = |item: &Item| {
+                let identity = item.extensions.get::<Identity>();
+                let language = item.extensions.get::<Language>();
+
+                if let (Some(identity), Some(language), Some(url)) = (identity, language, item.url()) {
+                    translations.entry(identity.clone())
+                        .or_insert_with(BTreeMap::new)
+                        .insert(language.clone(), url);
+                }
+            };
+
+            for dependency in &self.dependencies {
+                let dependency = bind.dependency(dependency)?;
+
+                for item in dependency.items() {
+                    index(item);
+                }
+            }
+
+            for item in bind.items() {
+                index(item);
+            }
+        }
+
+        for item in bind.items_mut() {
+            let found =
+                item.extensions.get::<Identity>()
+                .and_then(|identity| translations.get(identity))
+                .cloned();
+
+            if let Some(found) = found {
+                item.extensions.entry::<Translations>()
+                    .or_insert_with(BTreeMap::new)
+                    .extend(found);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that records the `Translations` of the bind's items.
+///
+/// Translations are the items with the same `Identity`, among the bind's
+/// own items and those of the given dependencies, such as the rules of
+/// the other languages. This must run after the items have been routed.
+#[inline]
+pub fn link() -> Link {
+    Link {
+        dependencies: vec![],
+    }
+}
+
+/// The `<link rel="alternate">` tags of the item's `Translations`.
+///
+/// The URLs are absolute, as search engines require, so
+/// `Configuration::base_url` should be set. The translation in the
+/// default language is also the `x-default`.
+pub fn hreflang(item: &Item) -> String {
+    let configuration = &item.bind().configuration;
+
+    let translations = match item.extensions.get::<Translations>() {
+        Some(translations) => translations,
+        None => return String::new(),
+    };
+
+    let mut tags = String::new();
+
+    let mut push = |language: &str, url: &str| {
+        tags.push_str(&format!(
+            "<link rel=\"alternate\" hreflang=\"{}\" href=\"{}\">\n",
+            html::escape(language), html::escape(&configuration.absolute_url(url))));
+    };
+
+    for (language, url) in translations {
+        push(language, url);
+    }
+
+    let default =
+        configuration.languages.first()
+        .and_then(|default| translations.get(default));
+
+    if let Some(url) = default {
+        push("x-default", url);
+    }
+
+    tags
+}
+
+/// Handle<Item> that adds the `hreflang` tags of the item to its `<head>`.
+pub fn inject_hreflang(item: &mut Item) -> ::Result<()> {
+    let tags = hreflang(item);
+
+    if !tags.is_empty() {
+        item.body = html::inject_head(item.text()?, &tags).into();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::{split, language_of};
+
+    fn languages() -> Vec<String> {
+        vec![String::from("en"), String::from("fr")]
+    }
+
+    #[test]
+    fn split_directory() {
+        let languages = languages();
+
+        assert_eq!(split(Path::new("fr/about.md"), &languages), (Some("fr"), From::from("about.md")));
+        assert_eq!(split(Path::new("de/about.md"), &languages), (None, From::from("de/about.md")));
+    }
+
+    #[test]
+    fn split_suffix() {
+        let languages = languages();
+
+        assert_eq!(split(Path::new("posts/hello.fr.md"), &languages),
+                   (Some("fr"), From::from("posts/hello.md")));
+        assert_eq!(split(Path::new("posts/hello.md"), &languages),
+                   (None, From::from("posts/hello.md")));
+    }
+
+    #[test]
+    fn split_file_named_after_language() {
+        let languages = languages();
+
+        assert_eq!(split(Path::new("fr"), &languages), (None, From::from("fr")));
+        assert_eq!(split(Path::new("fr.md"), &languages), (None, From::from("fr.md")));
+    }
+
+    #[test]
+    fn default_language() {
+        let languages = languages();
+
+        assert_eq!(language_of(Path::new("posts/hello.md"), &languages), Some("en"));
+        assert_eq!(language_of(Path::new("posts/hello.fr.md"), &languages), Some("fr"));
+        assert_eq!(language_of(Path::new("posts/hello.md"), &[]), None);
+    }
+}
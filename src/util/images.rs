@@ -0,0 +1,191 @@
+//! Resized variants of images.
+//!
+//! Each variant of an image is written next to the image's target,
+//! with the variant's name appended to the file stem. For example,
+//! the `thumb` variant of `photos/cat.jpg` is `photos/cat-thumb.jpg`.
+//!
+//! ```ignore
+//! let photos =
+//!     Rule::named("photos")
+//!     .handler(chain![
+//!         bind::select(glob!("photos/*.jpg")),
+//!         bind::each(chain![
+//!             route::identity,
+//!             item::copy,
+//!             images::variants()
+//!                 .fit("large", 1600, 1600)
+//!                 .fill("thumb", 200, 200)])])
+//!     .build();
+//! ```
+//!
+//! The URL and dimensions of each variant are recorded in the item's
+//! `Variants`, so that templates and galleries can refer to them.
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use typemap;
+use image::{self, DynamicImage, GenericImageView, ImageFormat};
+use image::imageops::FilterType;
+
+use handler::Handle;
+use item::Item;
+use support;
+
+/// How an image is resized into a variant's dimensions.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Mode {
+    /// Scale to fit within the dimensions, preserving the aspect ratio.
+    ///
+    /// Images that already fit are not enlarged.
+    Fit,
+
+    /// Scale and crop to fill the dimensions exactly.
+    Fill,
+}
+
+#[derive(Clone, Debug)]
+struct Variant {
+    name: String,
+    width: u32,
+    height: u32,
+    mode: Mode,
+}
+
+/// A generated variant of an image.
+#[derive(Clone, Debug, Serialize)]
+pub struct Generated {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The variants generated for an item, keyed by name.
+pub struct Variants;
+
+impl typemap::Key for Variants {
+    type Value = BTreeMap<String, Generated>;
+}
+
+/// The path of the named variant of the target.
+fn variant_path(target: &Path, name: &str) -> PathBuf {
+    let stem =
+        target.file_stem()
+        .map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+
+    let file_name = match target.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, name, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, name),
+    };
+
+    target.with_file_name(file_name)
+}
+
+pub struct Images {
+    variants: Vec<Variant>,
+    filter: FilterType,
+}
+
+impl Images {
+    fn variant(mut self, name: &str, width: u32, height: u32, mode: Mode) -> Images {
+        self.variants.push(Variant {
+            name: String::from(name),
+            width: width,
+            height: height,
+            mode: mode,
+        });
+
+        self
+    }
+
+    /// Add a variant that fits within the given dimensions.
+    pub fn fit(self, name: &str, width: u32, height: u32) -> Images {
+        self.variant(name, width, height, Mode::Fit)
+    }
+
+    /// Add a variant that is cropped to the given dimensions.
+    pub fn fill(self, name: &str, width: u32, height: u32) -> Images {
+        self.variant(name, width, height, Mode::Fill)
+    }
+
+    /// The filter used when resampling.
+    ///
+    /// This is `Lanczos3` by default.
+    pub fn filter(mut self, filter: FilterType) -> Images {
+        self.filter = filter;
+        self
+    }
+
+    fn resize(&self, original: &DynamicImage, variant: &Variant) -> DynamicImage {
+        match variant.mode {
+            Mode::Fit => {
+                if original.width() <= variant.width && original.height() <= variant.height {
+                    original.clone()
+                } else {
+                    original.resize(variant.width, variant.height, self.filter)
+                }
+            },
+            Mode::Fill => original.resize_to_fill(variant.width, variant.height, self.filter),
+        }
+    }
+}
+
+impl Handle<Item> for Images {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let (source, target, routed) = match (item.source(), item.target(), item.route().writing()) {
+            (Some(source), Some(target), Some(routed)) => (source, target, routed.to_path_buf()),
+            _ => return Ok(()),
+        };
+
+        let sandbox = item.bind().configuration.sandbox();
+
+        let format =
+            ImageFormat::from_path(&source)
+            .map_err(|e| format!("unsupported image {:?}: {}", item, e))?;
+
+        let original =
+            image::load_from_memory_with_format(&sandbox.read(&source)?, format)
+            .map_err(|e| format!("could not decode {:?}: {}", item, e))?;
+
+        if let Some(parent) = target.parent() {
+            sandbox.create_dir_all(parent)?;
+        }
+
+        let mut generated = BTreeMap::new();
+
+        for variant in &self.variants {
+            let resized = self.resize(&original, variant);
+
+            let mut encoded = Cursor::new(Vec::new());
+
+            resized.write_to(&mut encoded, format)
+                .map_err(|e| format!("could not encode the `{}` variant of {:?}: {}",
+                                     variant.name, item, e))?;
+
+            sandbox.write(&variant_path(&target, &variant.name), encoded.get_ref())?;
+
+            generated.insert(variant.name.clone(), Generated {
+                url: support::url(&variant_path(&routed, &variant.name)),
+                width: resized.width(),
+                height: resized.height(),
+            });
+        }
+
+        item.extensions.insert::<Variants>(generated);
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that generates resized variants of an image.
+///
+/// The image is read from the item's source, so its body is unused.
+/// Items that aren't both read and written are left alone.
+#[inline]
+pub fn variants() -> Images {
+    Images {
+        variants: Vec::new(),
+        filter: FilterType::Lanczos3,
+    }
+}
@@ -0,0 +1,263 @@
+//! Internal links that are resolved at build time.
+//!
+//! Rather than hard-coding the URL of another page, which breaks when
+//! its route changes, content can refer to it by its source path or its
+//! slug with a `{{ link(path="posts/hello.md") }}` shortcode. The `index`
+//! handler collects the routes of the bind's own items and those of its
+//! dependencies once they've been routed, and `expand` then replaces
+//! each shortcode with the URL of the item it refers to.
+//!
+//! ```ignore
+//! let posts =
+//!     Rule::named("posts")
+//!     .depends_on(&pages)
+//!     .handler(chain![
+//!         bind::select(glob!("posts/*.md")),
+//!         bind::each(chain![item::read, route::pretty]),
+//!         links::index().from(&pages),
+//!         bind::each(chain![links::expand(), markdown, item::write])])
+//!     .build();
+//! ```
+//!
+//! With the `tera` feature, templates registered after the index can
+//! call `link` the same way.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use regex::{Captures, Regex};
+use toml;
+use typemap;
+
+use bind::Bind;
+use handler::Handle;
+use item::{self, Item};
+use diag;
+
+/// The slug that links refer to an item by.
+///
+/// This is the `slug` metadata field if there is one,
+/// otherwise the `Slug` extension or the file stem of the source.
+pub fn slug(item: &Item) -> Option<String> {
+    item.extensions.get::<item::Metadata>()
+        .and_then(|m| m.get("slug"))
+        .and_then(toml::Value::as_str)
+        .map(String::from)
+        .or_else(|| item.extensions.get::<item::Slug>().cloned())
+        .or_else(|| {
+            item.route().reading()
+                .and_then(Path::file_stem)
+                .map(|s| s.to_string_lossy().into_owned())
+        })
+}
+
+/// The URLs of items, by source path and by slug.
+#[derive(Clone, Debug, Default)]
+pub struct Routes {
+    sources: BTreeMap<PathBuf, String>,
+    slugs: BTreeMap<String, Vec<String>>,
+}
+
+impl Routes {
+    /// Add an item's route, if it's both read and written.
+    pub fn insert(&mut self, item: &Item) {
+        let (source, url) = match (item.route().reading(), item.url()) {
+            (Some(source), Some(url)) => (source, url),
+            _ => return,
+        };
+
+        if let Some(slug) = slug(item) {
+            let urls = self.slugs.entry(slug).or_insert_with(Vec::new);
+
+            if !urls.contains(&url) {
+                urls.push(url.clone());
+            }
+        }
+
+        self.sources.insert(source.to_path_buf(), url);
+    }
+
+    /// The URL of the item with the given source path or slug.
+    pub fn resolve(&self, target: &str) -> ::Result<String> {
+        if let Some(url) = self.sources.get(Path::new(target)) {
+            return Ok(url.clone());
+        }
+
+        match self.slugs.get(target) {
+            Some(urls) if urls.len() == 1 => Ok(urls[0].clone()),
+            Some(urls) => {
+                Err(From::from(
+                    format!("the link to `{}` is ambiguous; it could be any of {}",
+                            target, urls.join(", "))))
+            },
+            None => Err(From::from(format!("the link to `{}` doesn't match any item", target))),
+        }
+    }
+}
+
+impl typemap::Key for Routes {
+    type Value = Arc<Routes>;
+}
+
+pub struct Index {
+    dependencies: Vec<String>,
+}
+
+impl Index {
+    /// Also index the items of a dependency.
+    pub fn from<D>(mut self, dependency: D) -> Index
+    where D: Into<String> {
+        self.dependencies.push(dependency.into());
+        self
+    }
+}
+
+impl Handle<Bind> for Index {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut routes = Routes::default();
+
+        for dependency in &self.dependencies {
+            let dependency = bind.dependency(dependency)?;
+
+            for item in dependency.items() {
+                routes.insert(item);
+            }
+        }
+
+        for item in bind.items() {
+            routes.insert(item);
+        }
+
+        bind.with_ext_mut(|extensions| extensions.insert::<Routes>(Arc::new(routes)));
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that collects the routes of the bind's items for `expand`.
+///
+/// This must run after the items have been routed.
+#[inline]
+pub fn index() -> Index {
+    Index {
+        dependencies: vec![],
+    }
+}
+
+pub struct Expand {
+    strict: bool,
+    shortcode: Regex,
+}
+
+impl Expand {
+    /// Whether a link that doesn't resolve fails the build. Defaults to true.
+    ///
+    /// Otherwise a warning is printed and the shortcode is left as it is.
+    pub fn strict(mut self, strict: bool) -> Expand {
+        self.strict = strict;
+        self
+    }
+}
+
+impl Handle<Item> for Expand {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let routes = item.bind().with_ext(|extensions| extensions.get::<Routes>().cloned());
+
+        let routes = match routes {
+            Some(routes) => routes,
+            None => {
+                return Err(From::from(
+                    format!("links weren't indexed for `{}`", item.bind().name)));
+            },
+        };
+
+        let mut errors = vec![];
+
+        let expanded = self.shortcode.replace_all(item.text()?, |captures: &Captures| {
+            match routes.resolve(&captures[1]) {
+                Ok(url) => url,
+                Err(e) => {
+                    errors.push(e.to_string());
+                    String::from(&captures[0])
+                },
+            }
+        }).into_owned();
+
+        if !errors.is_empty() {
+            let message = format!("{}: {}", item, errors.join("; "));
+
+            if self.strict {
+                return Err(From::from(message));
+            }
+
+            diag::warn(item, errors.join("; "));
+        }
+
+        item.body = expanded.into();
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that replaces `{{ link(path="...") }}` shortcodes with URLs.
+#[inline]
+pub fn expand() -> Expand {
+    Expand {
+        strict: true,
+        shortcode: Regex::new(r#"\{\{\s*link\(\s*path\s*=\s*"([^"]+)"\s*\)\s*\}\}"#).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use item::Item;
+    use super::{Routes, expand};
+
+    fn routes() -> Routes {
+        let mut routes = Routes::default();
+
+        routes.insert(&Item::read_write("posts/hello.md", "posts/hello/index.html"));
+        routes.insert(&Item::read_write("pages/about.md", "about/index.html"));
+        routes.insert(&Item::read_write("2015/notes.md", "2015/notes.html"));
+        routes.insert(&Item::read_write("2016/notes.md", "2016/notes.html"));
+        routes.insert(&Item::writing("feed.xml"));
+
+        routes
+    }
+
+    #[test]
+    fn resolve_by_source() {
+        assert_eq!(routes().resolve("posts/hello.md").unwrap(), "/posts/hello/");
+        assert_eq!(routes().resolve("2015/notes.md").unwrap(), "/2015/notes.html");
+    }
+
+    #[test]
+    fn resolve_by_slug() {
+        assert_eq!(routes().resolve("about").unwrap(), "/about/");
+    }
+
+    #[test]
+    fn resolve_ambiguous_slug() {
+        let error = routes().resolve("notes").err().unwrap();
+
+        assert!(error.to_string().contains("ambiguous"));
+    }
+
+    #[test]
+    fn resolve_missing() {
+        assert!(routes().resolve("feed").is_err());
+        assert!(routes().resolve("posts/missing.md").is_err());
+    }
+
+    #[test]
+    fn match_shortcodes() {
+        let shortcode = expand().shortcode;
+
+        let captures = shortcode.captures(r#"<a href="{{ link(path="posts/hello.md") }}">"#).unwrap();
+        assert_eq!(&captures[1], "posts/hello.md");
+
+        assert!(shortcode.is_match(r#"{{link( path = "about" )}}"#));
+        assert!(!shortcode.is_match(r#"{{ link("about") }}"#));
+    }
+}
@@ -0,0 +1,307 @@
+//! The messages sent to previewing browsers when outputs change.
+//!
+//! Each written output is classified by its extension so that a
+//! preview server can do the least disruptive thing: HTML pages are
+//! pushed in full, stylesheets are swapped in place without reloading,
+//! images are refreshed wherever they're displayed, and anything else
+//! reloads the page.
+//!
+//! ```ignore
+//! let posts =
+//!     Rule::named("posts")
+//!     .handler(chain![
+//!         bind::select(glob!("posts/*.md")),
+//!         bind::each(chain![item::read, markdown, item::write, livereload::notify])])
+//!     .build();
+//! ```
+//!
+//! A preview server then broadcasts each of the bind's `Messages`
+//! as JSON at `ENDPOINT`, which `CLIENT` knows how to apply. The `inject`
+//! handler adds the client to previewed pages, and only to them.
+
+use std::path::Path;
+
+use serde_json;
+use typemap;
+
+use item::Item;
+use util::html;
+
+/// A change to an output, as sent to previewing browsers.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message {
+    /// An HTML page, along with its new contents.
+    Html { url: String, body: String },
+
+    /// A stylesheet, which can be swapped without reloading.
+    Css { url: String },
+
+    /// A script, which requires reloading the page.
+    Script { url: String },
+
+    /// An image, which can be refreshed without reloading.
+    Image { url: String },
+
+    /// Any other output, which requires reloading the page.
+    Reload { url: String },
+}
+
+impl Message {
+    /// The message for an item's output, if it has one.
+    pub fn of(item: &Item) -> Option<Message> {
+        let target = match item.route().writing() {
+            Some(target) => target,
+            None => return None,
+        };
+
+        let url = url_of(target);
+
+        let extension =
+            target.extension()
+            .and_then(|e| e.to_str())
+            .map_or_else(String::new, |e| e.to_ascii_lowercase());
+
+        let message = match &extension[..] {
+            "html" | "htm" => {
+                let body = String::from_utf8_lossy(item.body.as_bytes()).into_owned();
+                Message::Html { url: url, body: body }
+            },
+            "css" => Message::Css { url: url },
+            "js" | "mjs" => Message::Script { url: url },
+            "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "avif" | "ico" =>
+                Message::Image { url: url },
+            _ => Message::Reload { url: url },
+        };
+
+        Some(message)
+    }
+
+    /// The URL of the changed output, relative to the site root.
+    pub fn url(&self) -> &str {
+        match *self {
+            Message::Html { ref url, .. } |
+            Message::Css { ref url } |
+            Message::Script { ref url } |
+            Message::Image { ref url } |
+            Message::Reload { ref url } => url,
+        }
+    }
+
+    /// The message as JSON, e.g. `{"type":"css","url":"/style.css"}`.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+/// The URL of a path relative to the output directory.
+fn url_of(target: &Path) -> String {
+    let segments =
+        target.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    format!("/{}", segments.join("/"))
+}
+
+/// The messages for a bind's changed outputs.
+pub struct Messages;
+
+impl typemap::Key for Messages {
+    type Value = Vec<Message>;
+}
+
+/// Record the message for the item's output in its bind's `Messages`.
+///
+/// This should run after the item is written.
+pub fn notify(item: &mut Item) -> ::Result<()> {
+    if let Some(message) = Message::of(item) {
+        item.bind().with_ext_mut(|extensions| {
+            extensions.entry::<Messages>().or_insert_with(Vec::new).push(message);
+        });
+    }
+
+    Ok(())
+}
+
+/// The path that the preview server accepts livereload connections on.
+pub static ENDPOINT: &'static str = "/__livereload";
+
+/// The comments that delimit the injected markup.
+static BEGIN: &'static str = "<!-- livereload -->";
+static END: &'static str = "<!-- /livereload -->";
+
+/// Remove any markup that `inject` added.
+fn strip(html: &str) -> String {
+    let mut stripped = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(begin) = rest.find(BEGIN) {
+        let end = match rest[begin..].find(END) {
+            Some(end) => begin + end + END.len(),
+            None => break,
+        };
+
+        stripped.push_str(&rest[..begin]);
+
+        // along with the newline that was added after it
+        rest = &rest[end..];
+
+        if rest.starts_with('\n') {
+            rest = &rest[1..];
+        }
+    }
+
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Handle<Item> that adds the livereload client to HTML pages.
+///
+/// The client connects as `Configuration::livereload_injected` says,
+/// which is never the case outside of previews or in the `production`
+/// profile. In that case, any client that was injected before, such as
+/// in a reused output, is removed instead.
+pub fn inject(item: &mut Item) -> ::Result<()> {
+    use configuration::Livereload;
+
+    let is_html =
+        item.route().writing()
+        .and_then(|target| target.extension())
+        .map_or(false, |extension| extension == "html" || extension == "htm");
+
+    if !is_html {
+        return Ok(());
+    }
+
+    let body = strip(item.text()?);
+
+    let transport = match item.bind().configuration.livereload_injected() {
+        Livereload::Off => {
+            item.body = body.into();
+            return Ok(());
+        },
+        Livereload::WebSocket => "websocket",
+        Livereload::Sse => "sse",
+    };
+
+    let markup = format!(
+        "{}\n<script>\nvar LIVERELOAD_URL = {}, LIVERELOAD_TRANSPORT = \"{}\";\n{}</script>\n{}",
+        BEGIN,
+        serde_json::to_string(&format!("{}{}", item.bind().configuration.base_path(), ENDPOINT))?,
+        transport,
+        CLIENT,
+        END);
+
+    item.body = html::inject_head(&body, &markup).into();
+
+    Ok(())
+}
+
+/// The browser side of the protocol.
+///
+/// It expects the page to define `LIVERELOAD_URL` as the path of the
+/// preview server's endpoint, and `LIVERELOAD_TRANSPORT` as `websocket`
+/// or `sse`. Browsers without WebSockets fall back to server-sent events,
+/// and those without either poll the endpoint with `?poll`, which should
+/// respond with a JSON array of the messages since the last poll. It
+/// avoids newer DOM APIs so that it works in older browsers.
+pub static CLIENT: &'static str = r#"(function () {
+  function fresh(url) {
+    return url.split("?")[0] + "?livereload=" + new Date().getTime();
+  }
+
+  function pathname(url) {
+    var a = document.createElement("a");
+    a.href = url;
+    return a.pathname.charAt(0) === "/" ? a.pathname : "/" + a.pathname;
+  }
+
+  function matches(attribute, url) {
+    return attribute && pathname(attribute) === url;
+  }
+
+  function each(selector, callback) {
+    var elements = document.querySelectorAll(selector);
+
+    for (var i = 0; i < elements.length; i++) {
+      callback(elements[i]);
+    }
+  }
+
+  function apply(message) {
+    switch (message.type) {
+      case "html":
+        if (location.pathname === message.url ||
+            location.pathname + "index.html" === message.url) {
+          document.open();
+          document.write(message.body);
+          document.close();
+        }
+        break;
+
+      case "css":
+        each("link[rel=stylesheet]", function (link) {
+          if (matches(link.getAttribute("href"), message.url)) {
+            link.href = fresh(link.getAttribute("href"));
+          }
+        });
+        break;
+
+      case "image":
+        each("img", function (img) {
+          if (matches(img.getAttribute("src"), message.url)) {
+            img.src = fresh(img.getAttribute("src"));
+          }
+        });
+        break;
+
+      default:
+        location.reload();
+    }
+  }
+
+  function receive(event) {
+    apply(JSON.parse(event.data));
+  }
+
+  function poll() {
+    var request = new XMLHttpRequest();
+
+    request.onreadystatechange = function () {
+      if (request.readyState !== 4) {
+        return;
+      }
+
+      if (request.status === 200) {
+        var messages = JSON.parse(request.responseText);
+
+        for (var i = 0; i < messages.length; i++) {
+          apply(messages[i]);
+        }
+      }
+
+      setTimeout(poll, 1000);
+    };
+
+    request.open("GET", LIVERELOAD_URL + "?poll", true);
+    request.send();
+  }
+
+  function sse() {
+    if (!window.EventSource) {
+      return poll();
+    }
+
+    new EventSource(LIVERELOAD_URL).onmessage = receive;
+  }
+
+  if (LIVERELOAD_TRANSPORT === "websocket" && window.WebSocket) {
+    var scheme = location.protocol === "https:" ? "wss://" : "ws://";
+
+    new WebSocket(scheme + location.host + LIVERELOAD_URL).onmessage = receive;
+  } else {
+    sse();
+  }
+})();
+"#;
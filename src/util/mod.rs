@@ -1,2 +1,27 @@
 pub mod route;
 pub mod handle;
+pub mod asset;
+pub mod html;
+pub mod export;
+pub mod changelog;
+pub mod authors;
+pub mod versions;
+pub mod teaser;
+pub mod livereload;
+pub mod links;
+pub mod feed;
+pub mod random;
+pub mod dates;
+pub mod i18n;
+pub mod data;
+pub mod source;
+pub mod query;
+pub mod tombstone;
+pub mod validate;
+pub mod recipes;
+
+#[cfg(feature = "images")]
+pub mod images;
+
+#[cfg(feature = "images")]
+pub mod favicon;
@@ -0,0 +1,133 @@
+//! Predicates on metadata values.
+//!
+//! These test a field of an item's front matter without unwrapping the
+//! TOML by hand, e.g. to keep only the posts of a series or the featured
+//! pages with `bind::where_meta`:
+//!
+//! ```ignore
+//! bind::retain(bind::where_meta("tags", query::contains("rust")))
+//! bind::retain(bind::where_meta("featured", query::is_true()))
+//! bind::retain(bind::where_meta("series", query::equals("rust-in-anger")))
+//! bind::retain(bind::where_meta("draft", query::not(query::is_true())))
+//! ```
+//!
+//! Strings are compared with strings, integers with integers, and so on;
+//! values of different types never match.
+
+use toml;
+
+/// A test of a metadata field, which is `None` if the item doesn't have it.
+pub trait Predicate: Sync + Send {
+    fn test(&self, value: Option<&toml::Value>) -> bool;
+}
+
+impl<F> Predicate for F
+where F: Fn(Option<&toml::Value>) -> bool, F: Sync + Send {
+    fn test(&self, value: Option<&toml::Value>) -> bool {
+        self(value)
+    }
+}
+
+pub struct Equals {
+    value: toml::Value,
+}
+
+impl Predicate for Equals {
+    fn test(&self, value: Option<&toml::Value>) -> bool {
+        value == Some(&self.value)
+    }
+}
+
+/// The field is the value.
+#[inline]
+pub fn equals<V>(value: V) -> Equals
+where V: Into<toml::Value> {
+    Equals {
+        value: value.into(),
+    }
+}
+
+pub struct Contains {
+    value: toml::Value,
+}
+
+impl Predicate for Contains {
+    fn test(&self, value: Option<&toml::Value>) -> bool {
+        match value {
+            Some(&toml::Value::Array(ref values)) => values.contains(&self.value),
+            Some(&toml::Value::String(ref string)) => {
+                self.value.as_str().map_or(false, |value| string.contains(value))
+            },
+            _ => false,
+        }
+    }
+}
+
+/// The field is an array with the value, or a string with the substring.
+#[inline]
+pub fn contains<V>(value: V) -> Contains
+where V: Into<toml::Value> {
+    Contains {
+        value: value.into(),
+    }
+}
+
+pub struct OneOf {
+    values: Vec<toml::Value>,
+}
+
+impl Predicate for OneOf {
+    fn test(&self, value: Option<&toml::Value>) -> bool {
+        value.map_or(false, |value| self.values.contains(value))
+    }
+}
+
+/// The field is one of the values.
+#[inline]
+pub fn one_of<V>(values: Vec<V>) -> OneOf
+where V: Into<toml::Value> {
+    OneOf {
+        values: values.into_iter().map(Into::into).collect(),
+    }
+}
+
+pub struct Exists;
+
+impl Predicate for Exists {
+    fn test(&self, value: Option<&toml::Value>) -> bool {
+        value.is_some()
+    }
+}
+
+/// The field is set to anything.
+#[inline]
+pub fn exists() -> Exists {
+    Exists
+}
+
+/// The field is `true`.
+#[inline]
+pub fn is_true() -> Equals {
+    equals(true)
+}
+
+pub struct Not<P>
+where P: Predicate {
+    predicate: P,
+}
+
+impl<P> Predicate for Not<P>
+where P: Predicate {
+    fn test(&self, value: Option<&toml::Value>) -> bool {
+        !self.predicate.test(value)
+    }
+}
+
+/// The predicate doesn't hold, including when the field isn't set.
+#[inline]
+pub fn not<P>(predicate: P) -> Not<P>
+where P: Predicate {
+    Not {
+        predicate: predicate,
+    }
+}
@@ -0,0 +1,127 @@
+//! Reproducible randomness.
+//!
+//! Items are processed in parallel and in no particular order, so a
+//! shared random number generator would make every build different.
+//! Instead, each item gets its own generator, seeded by the build's
+//! `Configuration::seed`, its rule, and its path, so that it produces
+//! the same numbers in every build until one of those changes.
+//!
+//! ```ignore
+//! fn related(item: &mut Item) -> diecast::Result<()> {
+//!     let mut posts = candidates_of(item);
+//!     random::rng(item).shuffle(&mut posts);
+//!     posts.truncate(3);
+//!     // ...
+//! }
+//! ```
+//!
+//! This isn't suitable for anything security-related.
+
+use bind::Data;
+use item::Item;
+use support;
+
+/// A seeded SplitMix64 generator.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {
+            state: seed,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `[0, bound)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bound` is zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "the bound must be positive");
+
+        let bound = bound as u64;
+
+        // reject the values that would bias the result
+        let zone = u64::max_value() - u64::max_value() % bound;
+
+        loop {
+            let value = self.next_u64();
+
+            if value < zone {
+                return (value % bound) as usize;
+            }
+        }
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Shuffle the slice in place.
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for index in (1 .. values.len()).rev() {
+            let other = self.below(index + 1);
+            values.swap(index, other);
+        }
+    }
+
+    /// A random element of the slice, if it isn't empty.
+    pub fn choose<'a, T>(&mut self, values: &'a [T]) -> Option<&'a T> {
+        if values.is_empty() {
+            None
+        } else {
+            Some(&values[self.below(values.len())])
+        }
+    }
+}
+
+fn seeded(seed: u64, rule: &str, id: &str) -> Rng {
+    let mut bytes = Vec::with_capacity(8 + rule.len() + id.len() + 1);
+
+    bytes.extend((0 .. 8).map(|shift| (seed >> (shift * 8)) as u8));
+    bytes.extend(rule.as_bytes());
+    bytes.push(0);
+    bytes.extend(id.as_bytes());
+
+    Rng::new(support::hash_u64(&bytes))
+}
+
+/// A generator for the item.
+///
+/// The item is identified by its source, or its target if it isn't
+/// read from a file, so the same path in the same rule always gets
+/// the same numbers for a given seed.
+pub fn rng(item: &Item) -> Rng {
+    let id =
+        item.route().reading()
+        .or_else(|| item.route().writing())
+        .map_or_else(String::new, |path| {
+            // the same on every platform
+            path.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/")
+        });
+
+    let bind = item.bind();
+
+    seeded(bind.configuration.seed, &bind.name, &id)
+}
+
+/// A generator for bind-level handlers.
+pub fn bind_rng(bind: &Data) -> Rng {
+    seeded(bind.configuration.seed, &bind.name, "")
+}
@@ -0,0 +1,121 @@
+//! Reusable rules.
+//!
+//! Sites often have several rules that only differ in their name,
+//! pattern, and template, such as pages, notes, and posts. A recipe
+//! builds such a rule from those parameters:
+//!
+//! ```ignore
+//! let pages = recipes::pages("pages", glob!("pages/*.md")).markup(markdown)
+//!     .template(&templates, "page").build();
+//! let notes = recipes::pages("notes", glob!("notes/*.md")).markup(markdown)
+//!     .template(&templates, "note").build();
+//! let posts = recipes::pages("posts", glob!("posts/*.md")).markup(markdown)
+//!     .template(&templates, "post").depends_on(&notes).build();
+//! ```
+
+use std::sync::Arc;
+
+use bind::Bind;
+use handler::Handle;
+use item::Item;
+use pattern::Pattern;
+use rule::Rule;
+use util::handle::{self, bind, item};
+use util::route;
+
+#[cfg(feature = "tera")]
+use templates::context;
+#[cfg(feature = "tera")]
+use templates::tera;
+
+/// A rule that reads, transforms, routes, renders, and writes pages.
+#[must_use]
+pub struct Pages {
+    name: String,
+    pattern: Arc<Pattern + Sync + Send>,
+    dependencies: Vec<String>,
+    markup: Option<Box<Handle<Item> + Sync + Send>>,
+    router: Box<Handle<Item> + Sync + Send>,
+    render: Option<(String, Box<Handle<Bind> + Sync + Send>, Box<Handle<Item> + Sync + Send>)>,
+}
+
+impl Pages {
+    /// Convert the pages' bodies after their front matter is parsed,
+    /// e.g. from markdown to HTML.
+    pub fn markup<H>(mut self, handler: H) -> Pages
+    where H: Handle<Item> + Sync + Send + 'static {
+        self.markup = Some(Box::new(handler));
+        self
+    }
+
+    /// Route the pages with this instead of `route::pretty`.
+    pub fn route<H>(mut self, handler: H) -> Pages
+    where H: Handle<Item> + Sync + Send + 'static {
+        self.router = Box::new(handler);
+        self
+    }
+
+    /// Render the pages with a template of a dependency's templates.
+    #[cfg(feature = "tera")]
+    pub fn template<D, N>(mut self, templates: D, name: N) -> Pages
+    where D: Into<String>, N: Into<String> {
+        let templates = templates.into();
+
+        self.render = Some((
+            templates.clone(),
+            Box::new(tera::register(templates)),
+            Box::new(tera::render_template(name, context::item))));
+
+        self
+    }
+
+    /// Register a dependency for the rule.
+    pub fn depends_on<D>(mut self, dependency: D) -> Pages
+    where D: Into<String> {
+        self.dependencies.push(dependency.into());
+        self
+    }
+
+    pub fn build(self) -> Rule {
+        let mut each = handle::Chain::<Item>::new().link(item::read).link(item::parse_metadata);
+
+        if let Some(markup) = self.markup {
+            each = each.link(markup);
+        }
+
+        each = each.link(self.router);
+
+        let mut chain = handle::Chain::<Bind>::new().link(bind::select(self.pattern.clone()));
+        let mut builder = Rule::named(self.name).pattern(self.pattern);
+
+        if let Some((templates, register, render)) = self.render {
+            builder = builder.depends_on(templates);
+            chain = chain.link(register);
+            each = each.link(render);
+        }
+
+        for dependency in self.dependencies {
+            builder = builder.depends_on(dependency);
+        }
+
+        builder.handler(chain.link(bind::each(each.link(item::write)))).build()
+    }
+}
+
+/// A rule of pages that match a pattern.
+///
+/// Each page is read, its front matter parsed, and it's transformed by
+/// its `markup`, routed with `route::pretty`, rendered with its
+/// `template`, and written.
+#[inline]
+pub fn pages<N, P>(name: N, pattern: P) -> Pages
+where N: Into<String>, P: Pattern + Sync + Send + 'static {
+    Pages {
+        name: name.into(),
+        pattern: Arc::new(pattern),
+        dependencies: vec![],
+        markup: None,
+        router: Box::new(route::pretty),
+        render: None,
+    }
+}
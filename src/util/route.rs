@@ -1,6 +1,8 @@
-use item::Item;
+use item::{self, Item};
 use handler::Handle;
-use std::path::{PathBuf, Path};
+use phase::{self, Phase};
+use std::collections::BTreeMap;
+use std::path::{Component, PathBuf, Path};
 
 use regex;
 
@@ -13,6 +15,8 @@ use regex;
 /// file.txt -> file.txt
 /// gen.route(Identity)
 pub fn identity(item: &mut Item) -> ::Result<()> {
+    phase::enter(item, Phase::Route)?;
+
     item.route_with(|path: &Path| -> PathBuf {
         path.to_path_buf()
     });
@@ -21,6 +25,8 @@ pub fn identity(item: &mut Item) -> ::Result<()> {
 }
 
 pub fn pretty(item: &mut Item) -> ::Result<()> {
+    phase::enter(item, Phase::Route)?;
+
     item.route_with(|path: &Path| -> PathBuf {
         let mut result = path.with_extension("");
         result.push("index.html");
@@ -30,9 +36,109 @@ pub fn pretty(item: &mut Item) -> ::Result<()> {
     Ok(())
 }
 
+/// posts/2015-03-21-hello.md -> posts/hello/index.html
+///
+/// This routes by the item's `Slug`, falling back to its file stem.
+pub fn pretty_slug(item: &mut Item) -> ::Result<()> {
+    phase::enter(item, Phase::Route)?;
+
+    let slug = item.extensions.get::<item::Slug>().cloned();
+
+    item.route_with(|path: &Path| -> PathBuf {
+        let mut result = match slug {
+            Some(ref slug) => path.with_file_name(slug),
+            None => path.with_extension(""),
+        };
+
+        result.push("index.html");
+        result
+    });
+
+    Ok(())
+}
+
+/// Remove the language from each part of a path that's tagged with it.
+///
+/// fr/about.md -> about.md, about.fr/index.html -> about/index.html
+fn strip_language(path: &Path, language: &str) -> PathBuf {
+    let suffix = format!(".{}", language);
+    let mut stripped = PathBuf::new();
+
+    for (index, component) in path.components().enumerate() {
+        let part = match component {
+            Component::Normal(part) => part.to_string_lossy().into_owned(),
+            other => {
+                stripped.push(other.as_os_str());
+                continue;
+            },
+        };
+
+        if index == 0 && part == language {
+            continue;
+        }
+
+        let part = match part.rfind('.') {
+            _ if part.ends_with(&suffix) && part.len() > suffix.len() => {
+                String::from(&part[.. part.len() - suffix.len()])
+            },
+            Some(dot) if part[..dot].ends_with(&suffix) && dot > suffix.len() => {
+                format!("{}{}", &part[.. dot - suffix.len()], &part[dot..])
+            },
+            _ => part,
+        };
+
+        stripped.push(part);
+    }
+
+    stripped
+}
+
+/// about.fr/index.html -> fr/about/index.html
+///
+/// This moves an item that has already been routed under a directory
+/// named after its `i18n::Language`, unless it's the default language,
+/// removing the language from the rest of the path. Items without a
+/// language are left alone. The item's URL is recorded in its
+/// `i18n::Translations`, which `i18n::link` completes with those of
+/// the other languages for `hreflang` alternates.
+pub fn localized(item: &mut Item) -> ::Result<()> {
+    use util::i18n::{Language, Translations};
+
+    phase::enter(item, Phase::Route)?;
+
+    let language = match item.extensions.get::<Language>() {
+        Some(language) => language.clone(),
+        None => return Ok(()),
+    };
+
+    let is_default =
+        item.bind().configuration.languages.first()
+        .map_or(true, |default| *default == language);
+
+    item.retarget_with(|target: &Path| -> PathBuf {
+        let stripped = strip_language(target, &language);
+
+        if is_default {
+            stripped
+        } else {
+            Path::new(&language).join(stripped)
+        }
+    });
+
+    if let Some(url) = item.url() {
+        item.extensions.entry::<Translations>()
+            .or_insert_with(BTreeMap::new)
+            .insert(language, url);
+    }
+
+    Ok(())
+}
+
 // TODO fallback semantics
 // currently if there is no file_name, then keeps same path?
 pub fn pretty_page(item: &mut Item) -> ::Result<()> {
+    phase::enter(item, Phase::Route)?;
+
     item.route_with(|path: &Path| -> PathBuf {
         let without = path.with_extension("");
 
@@ -64,6 +170,8 @@ pub struct SetExtension {
 
 impl Handle<Item> for SetExtension {
     fn handle(&self, item: &mut Item) -> ::Result<()> {
+        phase::enter(item, Phase::Route)?;
+
         item.route_with(|path: &Path| -> PathBuf {
             path.with_extension(self.extension)
         });
@@ -98,6 +206,8 @@ impl Regex {
 
 impl Handle<Item> for Regex {
     fn handle(&self, item: &mut Item) -> ::Result<()> {
+        phase::enter(item, Phase::Route)?;
+
         item.route_with(|path: &Path| -> PathBuf {
             let caps = self.regex.captures(path.to_str().unwrap()).unwrap();
             let mut expanded = String::new();
@@ -110,3 +220,25 @@ impl Handle<Item> for Regex {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::strip_language;
+
+    #[test]
+    fn strip_language_directory() {
+        assert_eq!(strip_language(Path::new("fr/about.md"), "fr"), Path::new("about.md"));
+        assert_eq!(strip_language(Path::new("posts/fr/about.md"), "fr"), Path::new("posts/fr/about.md"));
+        assert_eq!(strip_language(Path::new("france/about.md"), "fr"), Path::new("france/about.md"));
+    }
+
+    #[test]
+    fn strip_language_suffix() {
+        assert_eq!(strip_language(Path::new("about.fr/index.html"), "fr"), Path::new("about/index.html"));
+        assert_eq!(strip_language(Path::new("posts/hello.fr.md"), "fr"), Path::new("posts/hello.md"));
+        assert_eq!(strip_language(Path::new("posts/hello.md"), "fr"), Path::new("posts/hello.md"));
+        assert_eq!(strip_language(Path::new("posts/.fr"), "fr"), Path::new("posts/.fr"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Items generated from the items of other rules.
+//!
+//! `archives` creates an index page for every year and every month in
+//! which a dependency has dated items:
+//!
+//! ```ignore
+//! let archives =
+//!     Rule::named("archives")
+//!     .depends_on(&posts)
+//!     .handler(chain![
+//!         source::archives(&posts, |period| {
+//!             match period.month {
+//!                 Some(month) => PathBuf::from(format!("{}/{:02}/index.html", period.year, month)),
+//!                 None => PathBuf::from(format!("{}/index.html", period.year)),
+//!             }
+//!         }),
+//!         bind::each(chain![render_archive, item::write])])
+//!     .build();
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use chrono::Datelike;
+use typemap;
+
+use bind::Bind;
+use handler::Handle;
+use item::{self, Item};
+use util::dates;
+
+/// A year, or a month of a year.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Period {
+    pub year: i32,
+
+    /// The month from 1 to 12, if the period is a month
+    pub month: Option<u32>,
+}
+
+/// The archive page of a period.
+#[derive(Clone)]
+pub struct Archive {
+    pub period: Period,
+
+    /// The items dated in the period, in the order of the dependency.
+    pub items: Vec<Item>,
+}
+
+impl typemap::Key for Archive {
+    type Value = Archive;
+}
+
+pub struct Archives<R>
+where R: Fn(&Period) -> PathBuf, R: Sync + Send + 'static {
+    dependency: String,
+    router: R,
+    monthly: bool,
+    yearly: bool,
+}
+
+impl<R> Archives<R>
+where R: Fn(&Period) -> PathBuf, R: Sync + Send + 'static {
+    /// Only create the pages of years.
+    pub fn yearly(mut self) -> Archives<R> {
+        self.monthly = false;
+        self.yearly = true;
+        self
+    }
+
+    /// Only create the pages of months.
+    pub fn monthly(mut self) -> Archives<R> {
+        self.monthly = true;
+        self.yearly = false;
+        self
+    }
+}
+
+impl<R> Handle<Bind> for Archives<R>
+where R: Fn(&Period) -> PathBuf, R: Sync + Send + 'static {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut archives: BTreeMap<Period, Vec<Item>> = BTreeMap::new();
+
+        {
+            let dependency = bind.dependency(&self.dependency)?;
+
+            for item in dependency.items() {
+                if let Some(date) = item.extensions.get::<item::Date>() {
+                    let date = dates::in_timezone(date, &bind.configuration);
+                    let year = Period { year: date.year(), month: None };
+                    let month = Period { year: date.year(), month: Some(date.month()) };
+
+                    if self.yearly {
+                        archives.entry(year).or_insert_with(Vec::new).push(item.clone());
+                    }
+
+                    if self.monthly {
+                        archives.entry(month).or_insert_with(Vec::new).push(item.clone());
+                    }
+                }
+            }
+        }
+
+        for (period, items) in archives {
+            let mut page = Item::writing((self.router)(&period));
+
+            page.extensions.insert::<Archive>(Archive {
+                period: period,
+                items: items,
+            });
+
+            bind.attach(page);
+        }
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that creates a page for each year and month of the dependency's items.
+///
+/// The router maps a period to the page's output path. Each page has
+/// an `Archive` with its period and items. Periods are in the site's
+/// `Configuration::timezone`. Items without a `Date` are left out, so
+/// this must run after the dependency's dates are parsed.
+#[inline]
+pub fn archives<D, R>(dependency: D, router: R) -> Archives<R>
+where D: Into<String>, R: Fn(&Period) -> PathBuf, R: Sync + Send + 'static {
+    Archives {
+        dependency: dependency.into(),
+        router: router,
+        monthly: true,
+        yearly: true,
+    }
+}
@@ -0,0 +1,155 @@
+//! Teasers, the part of a body before a `<!--more-->` marker.
+//!
+//! Index pages and feeds often show the beginning of each post with a
+//! link to the rest. The `split` handler saves the part of the body
+//! before the marker as the `TEASER` version, so that it can be accessed
+//! with `Item::version` or `Body::Named`, and records whether there was
+//! more to the body with `Truncated`. Bodies without the marker are
+//! saved whole.
+//!
+//! ```ignore
+//! bind::each(chain![
+//!     item::read,
+//!     item::parse_metadata,
+//!     markdown,
+//!     teaser::split(),
+//!     render_layout,
+//!     item::write])
+//! ```
+
+use std::collections::BTreeMap;
+
+use typemap;
+
+use handler::Handle;
+use item::Item;
+use util::versions::Versions;
+
+/// The name of the version that holds the teaser.
+pub static TEASER: &'static str = "teaser";
+
+/// The conventional marker.
+pub static MORE: &'static str = "<!--more-->";
+
+/// Whether the teaser is only part of the body.
+pub struct Truncated;
+
+impl typemap::Key for Truncated {
+    type Value = bool;
+}
+
+pub struct Split {
+    marker: String,
+    strip: bool,
+}
+
+impl Split {
+    /// Split at another marker, e.g. `<!-- excerpt -->`.
+    pub fn marker<M>(mut self, marker: M) -> Split
+    where M: Into<String> {
+        self.marker = marker.into();
+        self
+    }
+
+    /// Remove the marker from the body.
+    pub fn strip(mut self) -> Split {
+        self.strip = true;
+        self
+    }
+}
+
+impl Handle<Item> for Split {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let index = item.text()?.find(&self.marker[..]);
+
+        let index = match index {
+            Some(index) => index,
+            None => {
+                item.snapshot(TEASER);
+                item.extensions.insert::<Truncated>(false);
+                return Ok(());
+            },
+        };
+
+        let (teaser, stripped) = {
+            let body = item.text()?;
+            let rest = &body[index + self.marker.len()..];
+
+            (String::from(body[..index].trim_end()), format!("{}{}", &body[..index], rest))
+        };
+
+        if self.strip {
+            item.body = stripped.into();
+        }
+
+        item.extensions.entry::<Versions>()
+            .or_insert_with(BTreeMap::new)
+            .insert(String::from(TEASER), teaser.into());
+
+        item.extensions.insert::<Truncated>(true);
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that saves the part of the body before the `MORE` marker
+/// as the `TEASER` version.
+#[inline]
+pub fn split() -> Split {
+    Split {
+        marker: String::from(MORE),
+        strip: false,
+    }
+}
+
+/// The teaser of an item, if it was split.
+pub fn teaser(item: &Item) -> Option<&str> {
+    item.version(TEASER).and_then(|body| body.as_str())
+}
+
+/// Whether the item's teaser is only part of its body.
+pub fn is_truncated(item: &Item) -> bool {
+    item.extensions.get::<Truncated>().cloned().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use handler::Handle;
+    use item::Item;
+    use super::{split, teaser, is_truncated};
+
+    fn item(body: &str) -> Item {
+        let mut item = Item::writing("index.html");
+        item.body = body.into();
+        item
+    }
+
+    #[test]
+    fn split_at_marker() {
+        let mut item = item("<p>first</p>\n<!--more-->\n<p>second</p>");
+        split().handle(&mut item).unwrap();
+
+        assert_eq!(teaser(&item), Some("<p>first</p>"));
+        assert!(is_truncated(&item));
+        assert_eq!(item.text().unwrap(), "<p>first</p>\n<!--more-->\n<p>second</p>");
+    }
+
+    #[test]
+    fn split_and_strip_marker() {
+        let mut item = item("<p>first</p><!-- excerpt --><p>second</p>");
+        split().marker("<!-- excerpt -->").strip().handle(&mut item).unwrap();
+
+        assert_eq!(teaser(&item), Some("<p>first</p>"));
+        assert!(is_truncated(&item));
+        assert_eq!(item.text().unwrap(), "<p>first</p><p>second</p>");
+    }
+
+    #[test]
+    fn keep_whole_body_without_marker() {
+        let mut item = item("<p>only</p>");
+        split().handle(&mut item).unwrap();
+
+        assert_eq!(teaser(&item), Some("<p>only</p>"));
+        assert!(!is_truncated(&item));
+    }
+}
@@ -0,0 +1,107 @@
+//! Tombstone pages.
+//!
+//! When a page that the previous build generated is gone, inbound links
+//! to it would otherwise 404 without explanation. With
+//! `Configuration::tombstones` and a `manifest` to compare against, a
+//! build writes a short page in its place that says it was removed, or
+//! that links to where it moved. Tombstones are recorded in the manifest,
+//! so they're kept by later builds until a page takes their place again.
+
+use std::path::Path;
+
+use configuration::Configuration;
+use manifest::{Diff, Entry, Manifest};
+use support;
+use util::html;
+
+fn is_html(path: &Path) -> bool {
+    path.extension().map_or(false, |extension| extension == "html" || extension == "htm")
+}
+
+/// The page that takes the place of a removed page, or of a moved one
+/// if its new URL is given.
+pub fn page(configuration: &Configuration, moved_to: Option<&str>) -> String {
+    let title = configuration.site().title.map(|title| html::escape(&title));
+
+    let (heading, message, refresh) = match moved_to {
+        Some(url) => {
+            let url = html::escape(&format!("{}{}", configuration.base_path(), url));
+
+            ("This page has moved",
+             format!("<p>It's now at <a href=\"{}\">{}</a>.</p>", url, url),
+             format!("<meta http-equiv=\"refresh\" content=\"0; url={}\">\n", url))
+        },
+        None => {
+            ("This page has been removed",
+             String::from("<p>The content that was here is no longer available.</p>"),
+             String::new())
+        },
+    };
+
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <meta name=\"robots\" content=\"noindex\">\n\
+         {}<title>{}</title>\n\
+         </head>\n\
+         <body>\n\
+         <h1>{}</h1>\n\
+         {}\n\
+         </body>\n\
+         </html>\n",
+        refresh,
+        title.map_or_else(|| String::from(heading), |title| format!("{} - {}", heading, title)),
+        heading,
+        message)
+}
+
+/// Whether a manifest entry is a tombstone written by `bury`.
+pub fn is_tombstone(configuration: &Configuration, entry: &Entry) -> bool {
+    let body = page(configuration, entry.moved_to.as_ref().map(|url| &url[..]));
+
+    entry.source.is_none() && entry.hash == support::hash(body.as_bytes())
+}
+
+/// Write tombstones for the HTML pages that were removed or moved since
+/// the previous build, and record them in the manifest.
+///
+/// Returns the number of tombstones written.
+pub fn bury(configuration: &Configuration, diff: &Diff, manifest: &mut Manifest) -> ::Result<usize> {
+    let sandbox = configuration.sandbox();
+
+    // a tombstone that's still needed keeps pointing where it did
+    let removed = diff.removed.iter().map(|entry| (entry, entry.moved_to.clone()));
+    let moved = diff.moved.iter().map(|&(ref from, ref to)| (from, Some(to.url())));
+
+    let mut count = 0;
+
+    for (entry, moved_to) in removed.chain(moved) {
+        if !is_html(&entry.target) {
+            continue;
+        }
+
+        let body = page(configuration, moved_to.as_ref().map(|url| &url[..]));
+        let target = configuration.output.join(&entry.target);
+
+        if let Some(parent) = target.parent() {
+            sandbox.create_dir_all(parent)?;
+        }
+
+        sandbox.write(&target, body.as_bytes())?;
+
+        manifest.entries.push(Entry {
+            source: None,
+            target: entry.target.clone(),
+            hash: support::hash(body.as_bytes()),
+            moved_to: moved_to,
+            rule: None,
+            millis: None,
+        });
+
+        count += 1;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,130 @@
+//! Validation across rules.
+//!
+//! Some constraints only hold site-wide, such as every post and note
+//! having a different slug, or every feed entry a different GUID. A
+//! `unique` constraint checks a key of the items of several rules at
+//! once, and reports every item that shares a key in a single message.
+//!
+//! ```ignore
+//! let validation =
+//!     Rule::named("validation")
+//!     .depends_on(&posts)
+//!     .depends_on(&notes)
+//!     .handler(chain![
+//!         validate::unique("slug", links::slug).across(&posts).across(&notes),
+//!         validate::unique("title", validate::title).across(&posts).strict(false)])
+//!     .build();
+//! ```
+
+use std::collections::BTreeMap;
+
+use toml;
+
+use bind::Bind;
+use handler::Handle;
+use item::{self, Item};
+use diag;
+
+/// The `title` metadata field of an item.
+pub fn title(item: &Item) -> Option<String> {
+    item.extensions.get::<item::Metadata>()
+        .and_then(|m| m.get("title"))
+        .and_then(toml::Value::as_str)
+        .map(String::from)
+}
+
+/// The URL of an item, which feeds use as its GUID.
+pub fn url(item: &Item) -> Option<String> {
+    item.url()
+}
+
+pub struct Unique<K>
+where K: Fn(&Item) -> Option<String>, K: Sync + Send + 'static {
+    name: String,
+    key: K,
+    dependencies: Vec<String>,
+    strict: bool,
+}
+
+impl<K> Unique<K>
+where K: Fn(&Item) -> Option<String>, K: Sync + Send + 'static {
+    /// Also check the items of a dependency.
+    pub fn across<D>(mut self, dependency: D) -> Unique<K>
+    where D: Into<String> {
+        self.dependencies.push(dependency.into());
+        self
+    }
+
+    /// Whether a violation fails the build. Defaults to true.
+    ///
+    /// Otherwise a warning is printed.
+    pub fn strict(mut self, strict: bool) -> Unique<K> {
+        self.strict = strict;
+        self
+    }
+}
+
+impl<K> Handle<Bind> for Unique<K>
+where K: Fn(&Item) -> Option<String>, K: Sync + Send + 'static {
+    fn handle(&self, bind: &mut Bind) -> ::Result<()> {
+        let mut seen: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        {
+            let mut check = |item: &Item| {
+                if let Some(key) = (self.key)(item) {
+                    seen.entry(key).or_insert_with(Vec::new).push(item.to_string());
+                }
+            };
+
+            for dependency in &self.dependencies {
+                let dependency = bind.dependency(dependency)?;
+
+                for item in dependency.items() {
+                    check(item);
+                }
+            }
+
+            for item in bind.items() {
+                check(item);
+            }
+        }
+
+        let violations =
+            seen.into_iter()
+            .filter(|&(_, ref items)| items.len() > 1)
+            .map(|(key, items)| {
+                format!("the {} `{}` is shared by:\n    {}", self.name, key, items.join("\n    "))
+            })
+            .collect::<Vec<_>>();
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let message = violations.join("\n");
+
+        if self.strict {
+            return Err(From::from(message));
+        }
+
+        diag::warn_bind(bind, message);
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that checks that no two items share a key.
+///
+/// The name describes the key in messages, e.g. `slug`. Items without
+/// a key are skipped. The bind's own items are checked along with
+/// those of the dependencies given with `across`.
+#[inline]
+pub fn unique<N, K>(name: N, key: K) -> Unique<K>
+where N: Into<String>, K: Fn(&Item) -> Option<String>, K: Sync + Send + 'static {
+    Unique {
+        name: name.into(),
+        key: key,
+        dependencies: vec![],
+        strict: true,
+    }
+}
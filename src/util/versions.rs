@@ -0,0 +1,138 @@
+//! Saved versions of an item's body.
+//!
+//! An item's body changes as it's processed: it may start as Markdown,
+//! be rendered to HTML, then be wrapped in a layout. Handlers that
+//! derive other outputs from items, such as feeds, exports, and search
+//! indexes, usually want one of the earlier versions, so the pipeline
+//! saves them by name as it goes.
+//!
+//! ```ignore
+//! bind::each(chain![
+//!     item::read,
+//!     item::parse_metadata,
+//!     versions::save(versions::RAW),
+//!     markdown,
+//!     versions::save(versions::RENDERED),
+//!     render_layout,
+//!     item::write])
+//! ```
+//!
+//! Handlers can also save and access versions directly, with
+//! `Item::snapshot` and `Item::version`. Derived-output handlers then
+//! take a `Body` that states explicitly which version they consume.
+
+use std::collections::BTreeMap;
+
+use typemap;
+
+use handler::Handle;
+use item::{Content, Item};
+use util::html;
+
+/// The conventional name of the body as it was read.
+pub static RAW: &'static str = "raw";
+
+/// The conventional name of the body once rendered, before any layout.
+pub static RENDERED: &'static str = "rendered";
+
+/// The saved versions of an item's body, keyed by name.
+pub struct Versions;
+
+impl typemap::Key for Versions {
+    type Value = BTreeMap<String, Content>;
+}
+
+pub struct Save {
+    name: String,
+}
+
+impl Handle<Item> for Save {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        item.snapshot(self.name.clone());
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that saves the current body under the given name.
+#[inline]
+pub fn save<N>(name: N) -> Save
+where N: Into<String> {
+    Save {
+        name: name.into(),
+    }
+}
+
+pub struct Load {
+    name: String,
+}
+
+impl Handle<Item> for Load {
+    fn handle(&self, item: &mut Item) -> ::Result<()> {
+        let body = item.version(&self.name).cloned().ok_or_else(|| missing(item, &self.name))?;
+
+        item.body = body;
+
+        Ok(())
+    }
+}
+
+/// Handle<Item> that restores the body saved under the given name.
+#[inline]
+pub fn load<N>(name: N) -> Load
+where N: Into<String> {
+    Load {
+        name: name.into(),
+    }
+}
+
+fn missing(item: &Item, name: &str) -> ::Error {
+    From::from(
+        format!("{:?} has no `{}` version of its body; \
+                 save one with `versions::save`", item, name))
+}
+
+/// Access a saved version of the item's body as text.
+pub fn version<'a>(item: &'a Item, name: &str) -> ::Result<&'a str> {
+    let body = item.version(name).ok_or_else(|| missing(item, name))?;
+
+    body.as_str().ok_or_else(|| From::from(
+        format!("the `{}` version of {:?} is binary, but text was expected", name, item)))
+}
+
+/// The version of an item's body that a handler consumes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Body {
+    /// The `RAW` version.
+    Raw,
+
+    /// The `RENDERED` version.
+    Rendered,
+
+    /// The body as it currently is, e.g. the fully laid-out page.
+    Final,
+
+    /// A version saved under any other name.
+    Named(String),
+}
+
+impl Body {
+    /// Access this version of the item's body.
+    ///
+    /// It's an error for the version not to have been saved.
+    pub fn of<'a>(&self, item: &'a Item) -> ::Result<&'a str> {
+        match *self {
+            Body::Raw => version(item, RAW),
+            Body::Rendered => version(item, RENDERED),
+            Body::Final => item.text(),
+            Body::Named(ref name) => version(item, name),
+        }
+    }
+
+    /// The plain text of this version of the item's body.
+    ///
+    /// The version is assumed to be HTML.
+    pub fn text_of(&self, item: &Item) -> ::Result<String> {
+        self.of(item).map(html::text)
+    }
+}
@@ -0,0 +1,208 @@
+//! The last commit of each item, from the git repository of the input.
+//!
+//! Items are annotated with the `changelog::Change` of the last commit
+//! that changed their source, so they can be listed by `changelog`.
+//! Requires the `git` feature.
+//!
+//! ```ignore
+//! let pages =
+//!     Rule::named("pages")
+//!     .handler(chain![
+//!         bind::select(glob!("pages/*.md")),
+//!         bind::each(item::read),
+//!         git::last_commit,
+//!         bind::each(chain![render, item::write])])
+//!     .build();
+//! ```
+//!
+//! Finding the last commit of a file means walking the history, so the
+//! last commit of every file is kept in `git.json` in the cache directory,
+//! along with the `HEAD` it was found at. Later builds only walk the
+//! commits made since then, unless the history was rewritten.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{self, FixedOffset, TimeZone};
+use git2::{Oid, Repository, Sort};
+use serde_json;
+
+use bind::Bind;
+use util::changelog::Change;
+use vfs::Sandbox;
+
+/// A commit that changed a file.
+#[derive(Clone, Serialize, Deserialize)]
+struct Commit {
+    sha: String,
+
+    /// The first line of the commit's message
+    message: String,
+
+    /// When the commit was made, in RFC 3339
+    date: String,
+}
+
+impl Commit {
+    fn change(&self) -> ::Result<Change> {
+        Ok(Change {
+            date: chrono::DateTime::parse_from_rfc3339(&self.date)?,
+            message: self.message.clone(),
+            commit: self.sha.clone(),
+        })
+    }
+}
+
+/// The last commit of every file, as of a `HEAD`.
+#[derive(Default, Serialize, Deserialize)]
+struct History {
+    head: String,
+
+    /// Keyed by the path relative to the repository's work tree
+    files: BTreeMap<String, Commit>,
+}
+
+impl History {
+    fn load(sandbox: &Sandbox, path: &Path) -> History {
+        sandbox.read_to_string(path).ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, sandbox: &Sandbox, path: &Path) -> ::Result<()> {
+        if let Some(parent) = path.parent() {
+            sandbox.create_dir_all(parent)?;
+        }
+
+        // written whole, so that a rule reading it concurrently never
+        // sees it half-written
+        let temporary = path.with_extension("json.tmp");
+        sandbox.write(&temporary, serde_json::to_string(self)?.as_bytes())?;
+        sandbox.rename(&temporary, path)?;
+
+        Ok(())
+    }
+
+    /// Walk the commits since the last `HEAD`, or all of them if the
+    /// history was rewritten since.
+    fn update(&mut self, repository: &Repository) -> ::Result<()> {
+        let head = repository.head()?.peel_to_commit()?.id();
+
+        if self.head == head.to_string() {
+            return Ok(());
+        }
+
+        let mut walk = repository.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        walk.push(head)?;
+
+        let previous = Oid::from_str(&self.head).ok();
+
+        match previous {
+            Some(previous) if repository.graph_descendant_of(head, previous).unwrap_or(false) => {
+                walk.hide(previous)?;
+            },
+            _ => self.files.clear(),
+        }
+
+        // commits are walked newest first, so only the first commit
+        // that's seen to change a file in this walk is kept
+        let mut seen = BTreeMap::new();
+
+        for oid in walk {
+            let commit = repository.find_commit(oid?)?;
+            let tree = commit.tree()?;
+
+            let parent = match commit.parent(0) {
+                Ok(parent) => Some(parent.tree()?),
+                Err(_) => None,
+            };
+
+            let diff = repository.diff_tree_to_tree(parent.as_ref(), Some(&tree), None)?;
+
+            for delta in diff.deltas() {
+                let path = match delta.new_file().path().and_then(|path| path.to_str()) {
+                    Some(path) => String::from(path),
+                    None => continue,
+                };
+
+                if seen.contains_key(&path) {
+                    continue;
+                }
+
+                let time = commit.time();
+                let offset = FixedOffset::east(time.offset_minutes() * 60);
+
+                seen.insert(path, Commit {
+                    sha: commit.id().to_string(),
+                    message: String::from(commit.summary().unwrap_or("")),
+                    date: offset.timestamp(time.seconds(), 0).to_rfc3339(),
+                });
+            }
+        }
+
+        self.files.extend(seen);
+        self.head = head.to_string();
+
+        Ok(())
+    }
+}
+
+/// Handle<Bind> that attaches the `Change` of the last commit of each item's source.
+///
+/// Items without a source, or whose source was never committed, are
+/// left alone. This fails if the input isn't in a git repository.
+pub fn last_commit(bind: &mut Bind) -> ::Result<()> {
+    let configuration = bind.configuration.clone();
+
+    let repository =
+        Repository::discover(&configuration.input)
+        .map_err(|e| format!("the input isn't in a git repository: {}", e))?;
+
+    let workdir = match repository.workdir() {
+        Some(workdir) => fs::canonicalize(workdir)?,
+        None => return Err(From::from("the input's git repository doesn't have a work tree")),
+    };
+
+    // the input is where git found the repository, so it's on disk, but
+    // the items' sources are only known to the vfs, so they're located
+    // within the repository by way of the input
+    let input = fs::canonicalize(&configuration.input)?;
+
+    let input = match input.strip_prefix(&workdir) {
+        Ok(input) => input.to_path_buf(),
+        Err(_) => return Err(From::from("the input isn't in its git repository's work tree")),
+    };
+
+    let sandbox = configuration.sandbox();
+    let cache = configuration.cache.join("git.json");
+
+    let mut history = History::load(&sandbox, &cache);
+    let head = history.head.clone();
+
+    history.update(&repository)?;
+
+    if history.head != head {
+        history.save(&sandbox, &cache)?;
+    }
+
+    for item in bind.items_mut() {
+        let source = match item.route().reading() {
+            Some(source) => input.join(source),
+            None => continue,
+        };
+
+        let relative = match source.to_str() {
+            // git always separates paths with slashes
+            Some(relative) => relative.replace('\\', "/"),
+            None => continue,
+        };
+
+        if let Some(commit) = history.files.get(&relative) {
+            item.extensions.insert::<Change>(commit.change()?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,4 @@
+//! Version control metadata.
+
+#[cfg(feature = "git")]
+pub mod git;
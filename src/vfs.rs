@@ -0,0 +1,463 @@
+//! File system access.
+//!
+//! The built-in handlers access the file system through the `Vfs`
+//! in the `Configuration` by way of its `Sandbox`. In strict mode, the
+//! sandbox only permits reading from the input and cache directories
+//! and writing to the output and cache directories. The previous build's
+//! output may also be read, so that unchanged files can be reused.
+//!
+//! Tests can swap in a `Memory` file system to observe exactly what
+//! the handlers read and wrote. A handler that goes behind its back
+//! with `std::fs` won't find its files there, nor show up in its record.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use configuration::Configuration;
+
+/// File system operations available to handlers.
+pub trait Vfs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Make `to` refer to the same file as `from`.
+    ///
+    /// File systems without links copy the file instead.
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.copy(from, to)
+    }
+
+    /// Make `to` a copy-on-write clone of `from`.
+    ///
+    /// File systems without clones copy the file instead.
+    fn reflink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.copy(from, to)
+    }
+
+    /// Make `to` a symbolic link to `from`.
+    ///
+    /// File systems without symbolic links copy the file instead.
+    fn symlink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.copy(from, to)
+    }
+
+    /// Move `from` to `to`, replacing it whole.
+    ///
+    /// File systems without renames copy the file instead.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.copy(from, to)
+    }
+
+    /// Open a file for streaming.
+    ///
+    /// File systems that can't stream read the whole file instead.
+    fn reader(&self, path: &Path) -> io::Result<Box<Read + Send>> {
+        let contents = self.read(path)?;
+        Ok(Box::new(io::Cursor::new(contents)))
+    }
+}
+
+impl<V: ?Sized> Vfs for Arc<V>
+where V: Vfs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        (**self).read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).copy(from, to)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        (**self).create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        (**self).exists(path)
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).hard_link(from, to)
+    }
+
+    fn reflink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).reflink(from, to)
+    }
+
+    fn symlink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).symlink(from, to)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).rename(from, to)
+    }
+
+    fn reader(&self, path: &Path) -> io::Result<Box<Read + Send>> {
+        (**self).reader(path)
+    }
+}
+
+/// The real file system.
+#[derive(Copy, Clone)]
+pub struct Disk;
+
+impl Vfs for Disk {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::File::create(path)?.write_all(contents)
+    }
+
+    /// Copy with the operating system's own mechanism where it has one,
+    /// otherwise by streaming the file through a fixed-size buffer.
+    ///
+    /// `fs::copy` also copies permissions, which fails on some file
+    /// systems, such as network shares, that can still be written to.
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ()).or_else(|_| {
+            let mut reader = io::BufReader::with_capacity(64 * 1024, fs::File::open(from)?);
+            let mut writer = fs::File::create(to)?;
+
+            io::copy(&mut reader, &mut writer)?;
+            writer.sync_data()
+        })
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::hard_link(from, to)
+    }
+
+    /// Clone the file with the `FICLONE` ioctl, as supported by e.g. Btrfs and XFS.
+    #[cfg(target_os = "linux")]
+    fn reflink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+        use libc;
+
+        const FICLONE: libc::c_ulong = 0x40049409;
+
+        let source = fs::File::open(from)?;
+        let target = fs::File::create(to)?;
+
+        let result = unsafe { libc::ioctl(target.as_raw_fd(), FICLONE as _, source.as_raw_fd()) };
+
+        if result == 0 {
+            return Ok(());
+        }
+
+        let error = io::Error::last_os_error();
+
+        drop(target);
+        let _ = fs::remove_file(to);
+
+        Err(error)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn reflink(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "reflinks aren't supported on this platform"))
+    }
+
+    /// Link to the absolute path of `from`, so that the link stays valid
+    /// when the output directory is moved into place.
+    #[cfg(unix)]
+    fn symlink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        use std::os::unix::fs::symlink;
+
+        symlink(fs::canonicalize(from)?, to)
+    }
+
+    #[cfg(not(unix))]
+    fn symlink(&self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "symlinks aren't supported on this platform"))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn reader(&self, path: &Path) -> io::Result<Box<Read + Send>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+}
+
+/// An operation performed on a `Memory` file system.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    Read(PathBuf),
+    Write(PathBuf),
+    Copy(PathBuf, PathBuf),
+    Rename(PathBuf, PathBuf),
+    CreateDir(PathBuf),
+}
+
+/// An in-memory file system which records every operation.
+pub struct Memory {
+    files: RwLock<HashMap<PathBuf, Vec<u8>>>,
+    operations: Mutex<Vec<Operation>>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            files: RwLock::new(HashMap::new()),
+            operations: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add a file without recording an operation.
+    pub fn insert<P, C>(&self, path: P, contents: C)
+    where P: Into<PathBuf>, C: Into<Vec<u8>> {
+        self.files.write().unwrap().insert(path.into(), contents.into());
+    }
+
+    /// The contents of a file, if it exists.
+    pub fn contents(&self, path: &Path) -> Option<Vec<u8>> {
+        self.files.read().unwrap().get(path).cloned()
+    }
+
+    /// The operations performed so far, in order.
+    pub fn operations(&self) -> Vec<Operation> {
+        self.operations.lock().unwrap().clone()
+    }
+
+    fn record(&self, operation: Operation) {
+        self.operations.lock().unwrap().push(operation);
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound,
+                   format!("{} does not exist", path.display()))
+}
+
+impl Vfs for Memory {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.record(Operation::Read(path.to_path_buf()));
+        self.contents(path).ok_or_else(|| not_found(path))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.record(Operation::Write(path.to_path_buf()));
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record(Operation::Copy(from.to_path_buf(), to.to_path_buf()));
+        let contents = self.contents(from).ok_or_else(|| not_found(from))?;
+        self.insert(to, contents);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.record(Operation::CreateDir(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.record(Operation::Rename(from.to_path_buf(), to.to_path_buf()));
+        let contents = self.files.write().unwrap().remove(from).ok_or_else(|| not_found(from))?;
+        self.insert(to, contents);
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.read().unwrap().contains_key(path)
+    }
+}
+
+/// The kind of access being made to a path.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// Lexically resolve `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+
+    normalized
+}
+
+/// Access to the configured `Vfs`, confined to the site's
+/// directories when the configuration is strict.
+pub struct Sandbox<'a> {
+    configuration: &'a Configuration,
+}
+
+impl<'a> Sandbox<'a> {
+    pub fn new(configuration: &'a Configuration) -> Sandbox<'a> {
+        Sandbox {
+            configuration: configuration,
+        }
+    }
+
+    /// Whether the given access to the path is permitted.
+    pub fn permits(&self, path: &Path, access: Access) -> bool {
+        if !self.configuration.is_strict {
+            return true;
+        }
+
+        let path = normalize(path);
+
+        if access == Access::Read {
+            let previous =
+                self.configuration.previous.as_ref()
+                .map_or(false, |previous| path.starts_with(normalize(previous)));
+
+            if previous || path.starts_with(normalize(&self.configuration.data)) {
+                return true;
+            }
+        }
+
+        let roots = match access {
+            Access::Read => [&self.configuration.input, &self.configuration.cache],
+            Access::Write => [&self.configuration.output, &self.configuration.cache],
+        };
+
+        roots.iter().any(|root| path.starts_with(normalize(root)))
+    }
+
+    fn check(&self, path: &Path, access: Access) -> io::Result<()> {
+        if self.permits(path, access) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{:?} access to {} is outside of the sandbox",
+                        access, path.display())))
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.check(path, Access::Read)?;
+        self.configuration.vfs.read(path)
+    }
+
+    pub fn reader(&self, path: &Path) -> io::Result<Box<Read + Send>> {
+        self.check(path, Access::Read)?;
+        self.configuration.vfs.reader(path)
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check(path, Access::Write)?;
+        self.configuration.vfs.write(path, contents)
+    }
+
+    pub fn copy(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(from, Access::Read)?;
+        self.check(to, Access::Write)?;
+        self.configuration.vfs.copy(from, to)
+    }
+
+    pub fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.check(path, Access::Write)?;
+        self.configuration.vfs.create_dir_all(path)
+    }
+
+    pub fn hard_link(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(from, Access::Read)?;
+        self.check(to, Access::Write)?;
+        self.configuration.vfs.hard_link(from, to)
+    }
+
+    pub fn reflink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(from, Access::Read)?;
+        self.check(to, Access::Write)?;
+        self.configuration.vfs.reflink(from, to)
+    }
+
+    pub fn symlink(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(from, Access::Read)?;
+        self.check(to, Access::Write)?;
+        self.configuration.vfs.symlink(from, to)
+    }
+
+    pub fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(from, Access::Write)?;
+        self.check(to, Access::Write)?;
+        self.configuration.vfs.rename(from, to)
+    }
+
+    pub fn exists(&self, path: &Path) -> bool {
+        self.configuration.vfs.exists(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use configuration::Configuration;
+    use super::{Memory, Operation, Access};
+
+    #[test]
+    fn strict_sandbox_confines_access() {
+        let configuration = Configuration::default().strict(true);
+        let sandbox = configuration.sandbox();
+
+        assert!(sandbox.permits(Path::new("input/posts/a.md"), Access::Read));
+        assert!(sandbox.permits(Path::new("output/a.html"), Access::Write));
+        assert!(!sandbox.permits(Path::new("input/posts/a.md"), Access::Write));
+        assert!(!sandbox.permits(Path::new("output/../input/a.md"), Access::Write));
+    }
+
+    #[test]
+    fn memory_records_operations() {
+        let memory = Arc::new(Memory::new());
+        memory.insert("input/a.md", "hello");
+
+        let configuration = Configuration::default().strict(true).vfs(memory.clone());
+        let sandbox = configuration.sandbox();
+
+        let contents = sandbox.read(Path::new("input/a.md")).unwrap();
+        sandbox.write(Path::new("output/a.md"), &contents).unwrap();
+
+        assert!(sandbox.write(Path::new("elsewhere/a.md"), &contents).is_err());
+
+        assert_eq!(memory.contents(Path::new("output/a.md")), Some(b"hello".to_vec()));
+        assert_eq!(memory.operations(),
+                   vec![Operation::Read(From::from("input/a.md")),
+                        Operation::Write(From::from("output/a.md"))]);
+    }
+}